@@ -0,0 +1,247 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use clap::{Parser, Subcommand};
+use delta_api::data_model::deploy_subject::DeploySubject;
+use delta_api::data_model::result::add_result::AddResult;
+use delta_api::data_model::result::connect_result::ConnectResult;
+use delta_api::data_model::result::run_result::RunResult;
+use delta_api::data_model::run_options::RunOptions;
+use delta_api::data_model::delta_error::DeltaError;
+use delta_api::obj_model::json_log::init_json_logging;
+use delta_api::obj_model::node_pool::NodePool;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "delta-cli", version, about = "Deploys and runs servers on the nodes of a delta-api inventory")]
+struct Cli {
+    #[arg(short, long, env = "DELTA_INVENTORY",
+          help = "Inventory: JSON as written by NodePool::save(), or a YAML inventory spec")]
+    inventory: PathBuf,
+    #[arg(long, help = "Print results as one JSON object keyed by node")]
+    json: bool,
+    #[arg(short, long, help = "Log to stderr, as JSON lines")]
+    verbose: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "List nodes with their connection and subject state")]
+    List,
+    #[command(about = "Add a node to a JSON inventory, creating the file if needed")]
+    Add {
+        name: String,
+        fqdn: String,
+        #[arg(short = 'p', long = "param", value_name = "KEY=VALUE", value_parser = parse_pair,
+              help = "Node parameter, e.g. -p Username=deploy")]
+        params: Vec<(String, String)>,
+    },
+    #[command(about = "Check that nodes accept an SSH session")]
+    Connect {
+        #[arg(required = true)]
+        nodes: Vec<String>,
+    },
+    #[command(about = "Upload and install the subject's distribution")]
+    Deploy {
+        #[arg(value_parser = parse_subject)]
+        subject: DeploySubject,
+        #[arg(required = true)]
+        nodes: Vec<String>,
+    },
+    #[command(about = "Start the subject's server")]
+    Run {
+        #[arg(value_parser = parse_subject)]
+        subject: DeploySubject,
+        #[arg(required = true)]
+        nodes: Vec<String>,
+        #[arg(long = "arg", value_name = "ARG", help = "Extra server argument, repeatable")]
+        args: Vec<String>,
+        #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_pair,
+              help = "Server environment variable, repeatable")]
+        env: Vec<(String, String)>,
+        #[arg(long, help = "Remote directory to start the server in")]
+        working_dir: Option<String>,
+    },
+    #[command(about = "Report whether the subject's server is running")]
+    Status {
+        #[arg(value_parser = parse_subject)]
+        subject: DeploySubject,
+        #[arg(required = true)]
+        nodes: Vec<String>,
+    },
+}
+
+/* What a command did on one node */
+struct Outcome {
+    ok: bool,
+    value: Value,
+}
+
+impl Outcome {
+    fn of<R: Serialize>(ok: bool, result: &R) -> Outcome {
+        return Outcome { ok, value: serde_json::to_value(result).unwrap_or(Value::Null) };
+    }
+
+    fn error(e: &DeltaError) -> Outcome {
+        let mut value = Map::new();
+        value.insert("error".to_string(), Value::from(e.to_string()));
+        return Outcome { ok: false, value: Value::Object(value) };
+    }
+}
+
+fn parse_subject(value: &str) -> Result<DeploySubject, String> {
+    return DeploySubject::all().into_iter()
+        .find(|s| s.to_string().eq_ignore_ascii_case(value))
+        .ok_or(format!("unknown subject '{}', expected sa or delta", value));
+}
+
+fn parse_pair(value: &str) -> Result<(String, String), String> {
+    return value.split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or(format!("expected KEY=VALUE, got '{}'", value));
+}
+
+fn is_yaml(path: &Path) -> bool {
+    return matches!(path.extension().and_then(|e| e.to_str()), Some("yaml") | Some("yml"));
+}
+
+fn load_pool(path: &Path) -> Result<NodePool, DeltaError> {
+    if is_yaml(path) {
+        return NodePool::from_inventory(path);
+    }
+    return NodePool::load(path);
+}
+
+/* Commands other than connect need a session first; a failed connect is the node's outcome */
+fn connected(pool: &mut NodePool, name: &str) -> Option<Outcome> {
+    let result = pool.connect(name.to_string());
+    return if result == ConnectResult::Ok { None } else { Some(Outcome::of(false, &result)) };
+}
+
+fn execute(cli: &Cli) -> Result<Vec<(String, Outcome)>, DeltaError> {
+    let path = cli.inventory.as_path();
+    let mut pool = match &cli.command {
+        Command::Add { .. } if is_yaml(path) => {
+            /* YAML inventories are maintained by hand */
+            return Err(DeltaError::InvalidParameter("inventory".to_string(), path.display().to_string()));
+        }
+        Command::Add { .. } if !path.exists() => NodePool::new(),
+        _ => load_pool(path)?,
+    };
+
+    let mut outcomes = Vec::new();
+    match &cli.command {
+        Command::Add { name, fqdn, params } => {
+            let result = pool.add(name.clone(), fqdn.clone(), params.iter().cloned().collect());
+            if result == AddResult::Ok {
+                pool.save(path)?;
+            }
+            outcomes.push((name.clone(), Outcome::of(result == AddResult::Ok, &result)));
+        }
+        Command::List => {
+            for summary in pool.list() {
+                outcomes.push((summary.name.clone(), Outcome::of(true, &summary)));
+            }
+        }
+        Command::Connect { nodes } => {
+            for name in nodes {
+                let result = pool.connect(name.clone());
+                outcomes.push((name.clone(), Outcome::of(result == ConnectResult::Ok, &result)));
+            }
+        }
+        Command::Deploy { subject, nodes } => {
+            for name in nodes {
+                let outcome = connected(&mut pool, name).unwrap_or_else(|| {
+                    let result = pool.deploy(name.clone(), subject.clone());
+                    return Outcome::of(result.is_ok(), &result);
+                });
+                outcomes.push((name.clone(), outcome));
+            }
+        }
+        Command::Run { subject, nodes, args, env, working_dir } => {
+            let options = RunOptions {
+                extra_args: args.clone(),
+                env: env.iter().cloned().collect::<HashMap<_, _>>(),
+                working_dir: working_dir.clone(),
+            };
+            for name in nodes {
+                let outcome = connected(&mut pool, name).unwrap_or_else(|| {
+                    let result = pool.run_with_options(name.clone(), subject.clone(), options.clone());
+                    return Outcome::of(result == RunResult::Ok, &result);
+                });
+                outcomes.push((name.clone(), outcome));
+            }
+        }
+        Command::Status { subject, nodes } => {
+            for name in nodes {
+                let outcome = connected(&mut pool, name).unwrap_or_else(|| {
+                    return match pool.status(name.clone(), subject.clone()) {
+                        Ok(status) => Outcome::of(true, &status),
+                        Err(e) => Outcome::error(&e),
+                    };
+                });
+                outcomes.push((name.clone(), outcome));
+            }
+        }
+    }
+
+    let _ = pool.disconnect_all();
+    return Ok(outcomes);
+}
+
+/* 0 when every node succeeded, 1 when any failed, 2 for bad usage or an unreadable inventory */
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if cli.verbose {
+        let _ = init_json_logging(tracing::Level::INFO);
+    }
+
+    let outcomes = match execute(&cli) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("delta-cli: {}: {}", cli.inventory.display(), e);
+            return ExitCode::from(2);
+        }
+    };
+
+    if cli.json {
+        let report: Map<String, Value> = outcomes.iter().map(|(name, o)| (name.clone(), o.value.clone())).collect();
+        println!("{}", Value::Object(report));
+    } else {
+        for (name, outcome) in &outcomes {
+            match &outcome.value {
+                Value::String(s) => println!("{}: {}", name, s),
+                value => println!("{}: {}", name, value),
+            }
+        }
+    }
+
+    return if outcomes.iter().all(|(_, o)| o.ok) { ExitCode::SUCCESS } else { ExitCode::from(1) };
+}
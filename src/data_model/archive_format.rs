@@ -0,0 +1,99 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "object_model")]
+use crate::obj_model::shell::shell_quote;
+
+#[derive(strum_macros::Display)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub enum ArchiveFormat {
+    TarXz,
+    TarGz,
+    Tar,
+    Zip,
+    Directory,
+}
+
+impl ArchiveFormat {
+    pub fn from_extension(path: &str) -> Option<ArchiveFormat> {
+        let path = path.to_lowercase();
+        if path.ends_with(".tar.xz") || path.ends_with(".txz") {
+            return Some(ArchiveFormat::TarXz);
+        }
+        if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+            return Some(ArchiveFormat::TarGz);
+        }
+        if path.ends_with(".tar") {
+            return Some(ArchiveFormat::Tar);
+        }
+        if path.ends_with(".zip") {
+            return Some(ArchiveFormat::Zip);
+        }
+
+        return None;
+    }
+
+    pub fn from_magic(header: &[u8]) -> Option<ArchiveFormat> {
+        if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            return Some(ArchiveFormat::TarXz);
+        }
+        if header.starts_with(&[0x1f, 0x8b]) {
+            return Some(ArchiveFormat::TarGz);
+        }
+        if header.starts_with(b"PK\x03\x04") {
+            return Some(ArchiveFormat::Zip);
+        }
+        if header.len() >= 262 && &header[257..262] == b"ustar" {
+            return Some(ArchiveFormat::Tar);
+        }
+
+        return None;
+    }
+
+    pub fn extension(&self) -> &'static str {
+        return match self {
+            ArchiveFormat::TarXz => ".tar.xz",
+            ArchiveFormat::TarGz => ".tar.gz",
+            ArchiveFormat::Tar => ".tar",
+            ArchiveFormat::Zip => ".zip",
+            ArchiveFormat::Directory => "",
+        };
+    }
+
+    /* Shell command unpacking archive into dir; None for plain directories */
+    #[cfg(feature = "object_model")]
+    pub fn extract_command(&self, archive: &str, dir: &str) -> Option<String> {
+        let (archive, dir) = (shell_quote(archive), shell_quote(dir));
+        let unpack = match self {
+            ArchiveFormat::TarXz => format!("tar xJf {} -C {}", archive, dir),
+            ArchiveFormat::TarGz => format!("tar xzf {} -C {}", archive, dir),
+            ArchiveFormat::Tar => format!("tar xf {} -C {}", archive, dir),
+            ArchiveFormat::Zip => format!("unzip -o -q {} -d {}", archive, dir),
+            ArchiveFormat::Directory => return None,
+        };
+
+        return Some(format!("mkdir -p {} && {}", dir, unpack));
+    }
+}
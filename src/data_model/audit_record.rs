@@ -0,0 +1,63 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use serde::{Deserialize, Serialize};
+
+#[derive(strum_macros::Display)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum AuditAction {
+    Connect,
+    Execute,
+    Upload,
+    Download,
+    Deploy,
+    Undeploy,
+    Rollback,
+    Activate,
+    Run,
+    Stop,
+    Restart,
+}
+
+/* One remote operation: who did what on which node, and how it ended */
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct AuditRecord {
+    /* Unix time, seconds */
+    pub timestamp: u64,
+    /* Local user running the API */
+    pub user: String,
+    /* User the node is accessed as */
+    pub remote_user: String,
+    pub node: String,
+    pub fqdn: String,
+    pub action: AuditAction,
+    /* Command, subject or transferred path; credentials are masked */
+    pub detail: String,
+    pub result: String,
+    pub success: bool,
+    /* Operation the record belongs to, shared with the trace spans */
+    #[serde(default)]
+    pub operation_id: String,
+}
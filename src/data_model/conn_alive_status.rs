@@ -27,6 +27,7 @@ use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SubjectAliveStatus {
     pub alive: bool,
     pub bind_addr: String,
@@ -44,14 +45,19 @@ impl SubjectAliveStatus {
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ConnAliveStatus {
     pub subjects: HashMap<DeploySubject, SubjectAliveStatus>,
+    /* Unix time, seconds, of the check; 0 if it never ran */
+    #[serde(default)]
+    pub last_checked: u64,
 }
 
 impl ConnAliveStatus {
     pub fn new() -> ConnAliveStatus {
         return ConnAliveStatus {
-            subjects: HashMap::new()
+            subjects: HashMap::new(),
+            last_checked: 0,
         };
     }
 }
@@ -28,4 +28,26 @@ use serde::{Deserialize, Serialize};
 pub enum ConnMethod {
     None,
     Ssh,
+    /* Commands run on the pool's own machine, without SSH */
+    Local,
+    /* Commands run in a container through docker exec */
+    Docker,
+    /* Commands run in a pod through the Kubernetes API */
+    Kubernetes,
+    /* Transport from the pool's connector */
+    Custom,
+}
+
+impl ConnMethod {
+    /* "local", "docker", "kubernetes" or "ssh"; left empty, a node at localhost is reached locally, any other over SSH */
+    pub fn from_param(value: &str, fqdn: &str) -> ConnMethod {
+        return match value.to_lowercase().as_str() {
+            "local" => ConnMethod::Local,
+            "docker" => ConnMethod::Docker,
+            "kubernetes" | "k8s" => ConnMethod::Kubernetes,
+            "ssh" => ConnMethod::Ssh,
+            "" if fqdn == "localhost" => ConnMethod::Local,
+            _ => ConnMethod::Ssh,
+        };
+    }
 }
@@ -23,16 +23,36 @@
  */
 
 use crate::data_model::deploy_subject::DeploySubject;
+use crate::data_model::node_facts::NodeFacts;
+use crate::data_model::platform::Platform;
+use crate::data_model::platform_kind::PlatformKind;
+use crate::data_model::run_options::RunOptions;
 use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SubjectStatus {
     pub deploy_archive_copied: bool,
     pub deploy_archive_extracted: bool,
     pub deploy_archive_tested: bool,
     pub deployed: bool,
     pub running: bool,
+    #[serde(default)]
+    pub checksum: String,
+    #[serde(default)]
+    pub prev_checksum: String,
+    #[serde(default)]
+    pub version: String,
+    /* Options of the last run(), reused when the instance is started again */
+    #[serde(default)]
+    pub run_options: Option<RunOptions>,
+    /* Supervisor restarts since the last explicit run() or stop() */
+    #[serde(default)]
+    pub restarts: u32,
+    /* Unix time, ms, before which the supervisor won't restart again */
+    #[serde(default)]
+    pub restart_after: Option<u64>,
 }
 
 unsafe impl Send for SubjectStatus {}
@@ -45,22 +65,41 @@ impl SubjectStatus {
             deploy_archive_tested: false,
             deployed: false,
             running: false,
+            checksum: "".to_string(),
+            prev_checksum: "".to_string(),
+            version: "".to_string(),
+            run_options: None,
+            restarts: 0,
+            restart_after: None,
         };
     }
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ConnStatus {
     pub connected: bool,
     pub subjects: HashMap<DeploySubject, SubjectStatus>,
-    pub platform: String,
+    #[serde(default)]
+    pub facts: NodeFacts,
+    #[serde(default)]
+    pub platform_kind: PlatformKind,
+    /* Same as facts.os */
+    #[serde(default)]
+    pub os: Platform,
+    /* Address the session was established with, out of those the host resolved to */
+    #[serde(default)]
+    pub address: String,
 }
 
 impl ConnStatus {
     pub fn new(connected: bool) -> ConnStatus {
         return ConnStatus { connected: connected,
             subjects: HashMap::new(),
-            platform: "".to_string() }
+            facts: NodeFacts::default(),
+            platform_kind: PlatformKind::Unix,
+            os: Platform::Linux,
+            address: "".to_string() }
     }
 
     pub fn get_subject(&mut self, subject: DeploySubject) -> SubjectStatus {
@@ -0,0 +1,70 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::obj_model::net::is_ssh_timeout;
+use std::io;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum DeltaError {
+    #[error("node not found: {0}")]
+    NodeNotFound(String),
+    #[error("node not connected: {0}")]
+    NodeNotConnected(String),
+    #[error("timed out: {0}")]
+    Timeout(String),
+    #[error("remote command failed: {0}")]
+    CommandFailed(String),
+    #[error("invalid parameter {0}: '{1}'")]
+    InvalidParameter(String, String),
+    #[error("environment variable not set: {0}")]
+    MissingEnvVar(String),
+    #[error("parse error: {0}")]
+    Parse(String),
+    #[error("credential store error: {0}")]
+    Crypto(String),
+    #[error("ssh error: {0}")]
+    Ssh(#[from] ssh2::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("i/o error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("no free port in {0}")]
+    NoFreePort(String),
+    #[error("cancelled")]
+    Cancelled,
+    #[error("not supported: {0}")]
+    Unsupported(String),
+}
+
+impl DeltaError {
+    pub fn is_timeout(&self) -> bool {
+        return match self {
+            DeltaError::Timeout(_) => true,
+            DeltaError::Ssh(e) => is_ssh_timeout(e),
+            DeltaError::Io(e) => e.kind() == io::ErrorKind::TimedOut,
+            _ => false,
+        };
+    }
+}
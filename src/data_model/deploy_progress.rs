@@ -0,0 +1,61 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::deploy_subject::DeploySubject;
+use serde::{Deserialize, Serialize};
+
+#[derive(strum_macros::Display)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum DeployPhase {
+    Uploading,
+    /* The node downloads the archive itself */
+    Fetching,
+    Verifying,
+    Syncing,
+    Extracting,
+    Testing,
+    Finished,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DeployProgress {
+    pub subject: DeploySubject,
+    pub phase: DeployPhase,
+    pub bytes_sent: u64,
+    pub bytes_total: u64,
+}
+
+impl DeployProgress {
+    pub fn new(subject: DeploySubject) -> DeployProgress {
+        return DeployProgress {
+            subject,
+            phase: DeployPhase::Uploading,
+            bytes_sent: 0,
+            bytes_total: 0,
+        };
+    }
+}
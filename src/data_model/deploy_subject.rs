@@ -23,14 +23,86 @@
  */
 
 
+use crate::data_model::node_parameters::NodeParameters;
 use serde::{Serialize, Deserialize};
 
 #[allow(non_camel_case_types)]
 #[derive(strum_macros::Display)]
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Eq)]
 #[derive(Hash)]
 pub enum DeploySubject {
     Sa,
     Delta,
 }
+
+impl DeploySubject {
+    pub fn all() -> Vec<DeploySubject> {
+        return vec![DeploySubject::Sa, DeploySubject::Delta];
+    }
+
+    /* Name of the executable under bin/ of the extracted tree */
+    pub fn binary(&self) -> &'static str {
+        return match self {
+            DeploySubject::Sa => "visao",
+            DeploySubject::Delta => "delta",
+        };
+    }
+
+    pub fn default_remote_dir(&self) -> &'static str {
+        return match self {
+            DeploySubject::Sa => "/tmp/visao",
+            DeploySubject::Delta => "/tmp/delta",
+        };
+    }
+
+    pub fn default_bind_port(&self) -> u16 {
+        return match self {
+            DeploySubject::Sa => 5700,
+            DeploySubject::Delta => 5701,
+        };
+    }
+
+    pub fn distr_param(&self) -> NodeParameters {
+        return match self {
+            DeploySubject::Sa => NodeParameters::Distr,
+            DeploySubject::Delta => NodeParameters::DeltaDistr,
+        };
+    }
+
+    pub fn remote_dir_param(&self) -> NodeParameters {
+        return match self {
+            DeploySubject::Sa => NodeParameters::RemoteDir,
+            DeploySubject::Delta => NodeParameters::DeltaRemoteDir,
+        };
+    }
+
+    pub fn bind_port_param(&self) -> NodeParameters {
+        return match self {
+            DeploySubject::Sa => NodeParameters::BindPort,
+            DeploySubject::Delta => NodeParameters::DeltaBindPort,
+        };
+    }
+
+    pub fn test_command_param(&self) -> NodeParameters {
+        return match self {
+            DeploySubject::Sa => NodeParameters::TestCommand,
+            DeploySubject::Delta => NodeParameters::DeltaTestCommand,
+        };
+    }
+
+    pub fn launch_command_param(&self) -> NodeParameters {
+        return match self {
+            DeploySubject::Sa => NodeParameters::LaunchCmd,
+            DeploySubject::Delta => NodeParameters::DeltaLaunchCmd,
+        };
+    }
+
+    pub fn alive_command_param(&self) -> NodeParameters {
+        return match self {
+            DeploySubject::Sa => NodeParameters::AliveCmd,
+            DeploySubject::Delta => NodeParameters::DeltaAliveCmd,
+        };
+    }
+}
@@ -23,23 +23,70 @@
  */
 
 use ssh2::Session;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::data_model::conn_alive_status::ConnAliveStatus;
 use crate::data_model::conn_method::*;
 use crate::data_model::conn_status::*;
+use crate::data_model::deploy_subject::DeploySubject;
+use crate::data_model::operation_record::OperationRecord;
+use crate::obj_model::port_forward::PortForward;
+use crate::obj_model::transport::{SshTransport, Transport};
 
 #[repr(C)]
 pub struct Instance {
     pub conn_method: ConnMethod,
     pub conn_status: ConnStatus,
-    pub ssh_session: Option<Session>,
+    pub transport: Option<Box<dyn Transport>>,
+    /* Most recent operations, oldest first; recorded from &self, hence the lock */
+    pub history: Mutex<VecDeque<OperationRecord>>,
+    /* Local tunnels to the servers, closed along with the connection */
+    pub forwards: HashMap<DeploySubject, PortForward>,
+    /* Last is_alive() result and when it was taken; any recorded operation drops it */
+    pub alive: Mutex<Option<(Instant, ConnAliveStatus)>>,
 }
 
+/* Operations kept per node before the oldest ones are dropped */
+pub const HISTORY_KEPT: usize = 50;
+
 unsafe impl Send for Instance {}
 
 impl Instance {
-    pub fn new_ssh(session: Session, connected: bool) -> Instance {
-        return Instance { conn_method: ConnMethod::Ssh,
+    pub fn new(conn_method: ConnMethod, transport: Box<dyn Transport>, connected: bool) -> Instance {
+        return Instance { conn_method,
             conn_status: ConnStatus::new(connected),
-            ssh_session: Some(session)
+            transport: Some(transport),
+            history: Mutex::new(VecDeque::new()),
+            forwards: HashMap::new(),
+            alive: Mutex::new(None),
         };
     }
+
+    pub fn new_ssh(session: Session, connected: bool) -> Instance {
+        return Instance::new(ConnMethod::Ssh, Box::new(SshTransport::new(session)), connected);
+    }
+
+    pub fn record(&self, record: OperationRecord) {
+        let mut history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        if history.len() >= HISTORY_KEPT {
+            history.pop_front();
+        }
+        history.push_back(record);
+        *self.alive.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    pub fn history(&self) -> Vec<OperationRecord> {
+        return self.history.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect();
+    }
+
+    pub fn cache_alive(&self, status: ConnAliveStatus) {
+        *self.alive.lock().unwrap_or_else(|e| e.into_inner()) = Some((Instant::now(), status));
+    }
+
+    /* The cached is_alive() result unless it is older than max_age */
+    pub fn cached_alive(&self, max_age: Duration) -> Option<ConnAliveStatus> {
+        let alive = self.alive.lock().unwrap_or_else(|e| e.into_inner());
+        return alive.as_ref().filter(|(at, _)| at.elapsed() <= max_age).map(|(_, status)| status.clone());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,154 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::deploy_subject::DeploySubject;
+use crate::data_model::node_parameters::NodeParameters;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/*
+ * Native inventory file:
+ *
+ *   defaults:
+ *     params: { Username: deploy }
+ *     subjects: { Sa: { distr: ./visao.tar.xz } }
+ *   groups:
+ *     prod:
+ *       params: { StrictHostKeyChecking: "yes" }
+ *   nodes:
+ *     web1:
+ *       fqdn: 10.0.0.1:22
+ *       groups: [prod]
+ *       tags: [canary]
+ *       subjects: { Delta: { remote_dir: /opt/delta, bind_port: 5800 } }
+ *
+ * Node settings override its groups' (in listed order), which override defaults.
+ */
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct InventorySpec {
+    #[serde(default)]
+    pub defaults: SettingsSpec,
+    #[serde(default)]
+    pub groups: BTreeMap<String, SettingsSpec>,
+    #[serde(default)]
+    pub nodes: BTreeMap<String, NodeSpec>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct SettingsSpec {
+    #[serde(default)]
+    pub params: HashMap<NodeParameters, String>,
+    #[serde(default)]
+    pub subjects: HashMap<DeploySubject, SubjectSpec>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct NodeSpec {
+    pub fqdn: String,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub params: HashMap<NodeParameters, String>,
+    #[serde(default)]
+    pub subjects: HashMap<DeploySubject, SubjectSpec>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct SubjectSpec {
+    pub distr: Option<String>,
+    pub remote_dir: Option<String>,
+    pub bind_port: Option<u16>,
+    pub test_command: Option<String>,
+}
+
+impl SettingsSpec {
+    /* Params and subject settings flattened to str_params keys */
+    pub fn to_params(&self) -> HashMap<String, String> {
+        let mut params: HashMap<String, String> = self.params.iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect();
+        for (subject, spec) in &self.subjects {
+            params.extend(spec.to_params(subject));
+        }
+        return params;
+    }
+}
+
+impl NodeSpec {
+    pub fn settings(&self) -> SettingsSpec {
+        return SettingsSpec { params: self.params.clone(), subjects: self.subjects.clone() };
+    }
+}
+
+impl SubjectSpec {
+    pub fn to_params(&self, subject: &DeploySubject) -> HashMap<String, String> {
+        let mut params = HashMap::new();
+        let values = [
+            (subject.distr_param(), self.distr.clone()),
+            (subject.remote_dir_param(), self.remote_dir.clone()),
+            (subject.bind_port_param(), self.bind_port.map(|p| p.to_string())),
+            (subject.test_command_param(), self.test_command.clone()),
+        ];
+        for (param, value) in values {
+            if let Some(v) = value {
+                params.insert(param.to_string(), v);
+            }
+        }
+        return params;
+    }
+}
+
+impl InventorySpec {
+    /* Every problem found, each prefixed with the path of the offending entry */
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = vec![];
+
+        for (name, node) in &self.nodes {
+            if name.trim().is_empty() {
+                errors.push("nodes: empty node name".to_string());
+            }
+            if node.fqdn.trim().is_empty() {
+                errors.push(format!("nodes.{}.fqdn: must not be empty", name));
+            }
+            for group in &node.groups {
+                if !self.groups.contains_key(group) {
+                    errors.push(format!("nodes.{}.groups: unknown group '{}'", name, group));
+                }
+            }
+            for tag in &node.tags {
+                if tag.trim().is_empty() {
+                    errors.push(format!("nodes.{}.tags: empty tag", name));
+                }
+            }
+        }
+
+        return errors;
+    }
+}
@@ -23,12 +23,49 @@
  */
 
 pub mod result;
+pub mod add_node_request;
+pub mod archive_format;
+pub mod audit_record;
 pub mod conn_alive_status;
 pub mod conn_method;
 pub mod conn_status;
+#[cfg(feature = "object_model")]
+pub mod delta_error;
+pub mod deploy_progress;
 pub mod deploy_subject;
+pub mod endpoint;
+pub mod error_response;
+pub mod exec_output;
+pub mod file_info;
 pub mod global_parameters;
+pub mod health_event;
+pub mod installed_versions;
+pub mod instance_logs;
+pub mod inventory_spec;
 #[cfg(feature = "object_model")]
 pub mod instance;
 
+pub mod job_status;
+pub mod log_line;
+pub mod node_facts;
 pub mod node_parameters;
+pub mod node_summary;
+#[cfg(feature = "schema")]
+pub mod openapi;
+pub mod operation_record;
+pub mod operation_result;
+pub mod platform;
+pub mod platform_kind;
+pub mod pool_event;
+pub mod pool_limits;
+pub mod prerequisite_report;
+pub mod resource_usage;
+pub mod restart_policy;
+pub mod retry_policy;
+pub mod run_mode;
+pub mod run_options;
+pub mod run_status;
+pub mod secret;
+pub mod stop_method;
+pub mod supervisor_event;
+pub mod transfer_method;
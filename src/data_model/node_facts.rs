@@ -0,0 +1,61 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::platform::Platform;
+use serde::{Deserialize, Serialize};
+
+/* What a node runs on, gathered when it connects */
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct NodeFacts {
+    pub os: Platform,
+    /* Kernel name and release, "Linux 6.1.0-18-amd64" */
+    pub kernel: String,
+    /* As the node names it: x86_64, arm64, AMD64 */
+    pub arch: String,
+    /* Online CPUs; 0 where the node wouldn't tell */
+    pub cpus: u32,
+    pub memory_bytes: u64,
+    /* Distribution or product name, "Debian GNU/Linux 12 (bookworm)" */
+    pub distro: String,
+}
+
+impl NodeFacts {
+    /* Out of the key=value lines the facts scripts print; what's missing stays empty */
+    pub fn parse(os: Platform, output: &str) -> NodeFacts {
+        let mut facts = NodeFacts { os, ..NodeFacts::default() };
+        for (key, value) in output.lines().filter_map(|line| line.trim().split_once('=')) {
+            let value = value.trim();
+            match key {
+                "kernel" => facts.kernel = value.to_string(),
+                "arch" => facts.arch = value.to_string(),
+                "cpus" => facts.cpus = value.parse().unwrap_or(0),
+                "memory" => facts.memory_bytes = value.parse().unwrap_or(0),
+                "distro" => facts.distro = value.to_string(),
+                _ => {}
+            }
+        }
+        return facts;
+    }
+}
@@ -22,12 +22,70 @@
  * DEALINGS IN THE SOFTWARE.
  */
 
+use serde::{Deserialize, Serialize};
+
 #[allow(non_camel_case_types)]
 #[derive(strum_macros::Display)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug)]
 pub enum NodeParameters {
     Username,
     Password,
+    IdentityFile,
+    KeyPassphrase,
     Distr,
     BindAddr,
     BindPort,
+    KnownHostsFile,
+    StrictHostKeyChecking,
+    SshPort,
+    JumpHost,
+    JumpUsername,
+    JumpPassword,
+    ConnectTimeout,
+    HandshakeTimeout,
+    KeepaliveInterval,
+    CommandTimeout,
+    TransferMethod,
+    ResumableUpload,
+    Compression,
+    SyncMode,
+    RemoteDir,
+    RemoteTmpDir,
+    ArtifactCacheDir,
+    ArtifactCacheSize,
+    VersionedDeploy,
+    TestCommand,
+    LaunchCmd,
+    AliveCmd,
+    DeltaDistr,
+    DeltaRemoteDir,
+    DeltaBindPort,
+    DeltaTestCommand,
+    DeltaLaunchCmd,
+    DeltaAliveCmd,
+    HealthTimeout,
+    PreDeployCmd,
+    PostDeployCmd,
+    StopSignal,
+    StopGracePeriod,
+    RunMode,
+    Transport,
+    DockerContainer,
+    DockerUser,
+    KubeNamespace,
+    KubePod,
+    KubeContainer,
+}
+
+impl NodeParameters {
+    /* Params holding credentials: masked in logs, encrypted at rest */
+    pub const SENSITIVE: [NodeParameters; 3] = [
+        NodeParameters::Password,
+        NodeParameters::KeyPassphrase,
+        NodeParameters::JumpPassword,
+    ];
+
+    pub fn is_sensitive(key: &str) -> bool {
+        return NodeParameters::SENSITIVE.iter().any(|p| p.to_string() == key);
+    }
 }
@@ -0,0 +1,148 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::add_node_request::AddNodeRequest;
+use crate::data_model::conn_alive_status::ConnAliveStatus;
+use crate::data_model::conn_status::ConnStatus;
+use crate::data_model::deploy_progress::DeployProgress;
+use crate::data_model::deploy_subject::DeploySubject;
+use crate::data_model::endpoint::Endpoint;
+use crate::data_model::error_response::ErrorResponse;
+use crate::data_model::log_line::LogLine;
+use crate::data_model::node_summary::NodeSummary;
+use crate::data_model::operation_result::OperationResult;
+use crate::data_model::pool_event::PoolEvent;
+use crate::data_model::result::activate_result::ActivateResult;
+use crate::data_model::result::add_result::AddResult;
+use crate::data_model::result::connect_result::ConnectResult;
+use crate::data_model::result::deploy_result::DeployResult;
+use crate::data_model::result::disconnect_result::DisconnectResult;
+use crate::data_model::result::remove_result::RemoveResult;
+use crate::data_model::result::rename_result::RenameResult;
+use crate::data_model::result::restart_result::RestartResult;
+use crate::data_model::result::rollback_result::RollbackResult;
+use crate::data_model::result::run_result::RunResult;
+use crate::data_model::result::stop_result::StopResult;
+use crate::data_model::result::undeploy_result::UndeployResult;
+use crate::data_model::result::update_result::UpdateResult;
+use crate::data_model::result::upgrade_result::UpgradeResult;
+use crate::data_model::run_options::RunOptions;
+use crate::data_model::run_status::RunStatus;
+use schemars::generate::SchemaSettings;
+use schemars::{JsonSchema, SchemaGenerator};
+use serde_json::{json, Value};
+
+fn schema<T: JsonSchema>(generator: &mut SchemaGenerator) -> Value {
+    return serde_json::to_value(generator.subschema_for::<T>()).unwrap_or(Value::Null);
+}
+
+fn json_content(schema: Value) -> Value {
+    return json!({ "application/json": { "schema": schema } });
+}
+
+fn operation(summary: &str, params: &[&Value], response: Value, error: &Value) -> Value {
+    return json!({
+        "summary": summary,
+        "parameters": params,
+        "responses": {
+            "200": { "description": "Result of the operation", "content": json_content(response) },
+            "default": { "description": "The request could not be carried out", "content": json_content(error.clone()) },
+        },
+    });
+}
+
+/*
+ * OpenAPI 3 description of the REST server, with the schemas of the data
+ * model types it and the other front ends serialize under components.
+ */
+pub fn generate_openapi() -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    let error = schema::<ErrorResponse>(&mut gen);
+    let name = json!({ "name": "name", "in": "path", "required": true, "schema": { "type": "string" } });
+    let subject = json!({ "name": "subject", "in": "path", "required": true, "schema": schema::<DeploySubject>(&mut gen) });
+
+    let mut add = operation("Add a node", &[], schema::<AddResult>(&mut gen), &error);
+    add["requestBody"] = json!({ "required": true, "content": json_content(schema::<AddNodeRequest>(&mut gen)) });
+    let mut run = operation("Start the subject's server", &[&name, &subject],
+                            schema::<OperationResult<RunResult>>(&mut gen), &error);
+    run["requestBody"] = json!({ "required": false, "content": json_content(schema::<RunOptions>(&mut gen)) });
+    let mut events = json!({
+        "summary": "WebSocket of pool events",
+        "description": "Upgrades to a WebSocket; each text message is one PoolEvent as JSON.",
+        "responses": { "101": { "description": "Switching to the WebSocket protocol" } },
+    });
+    events["x-websocket-message"] = schema::<PoolEvent>(&mut gen);
+
+    let paths = json!({
+        "/nodes": {
+            "get": operation("List nodes", &[], schema::<Vec<NodeSummary>>(&mut gen), &error),
+            "post": add,
+        },
+        "/nodes/{name}": {
+            "delete": operation("Remove a node", &[&name], schema::<RemoveResult>(&mut gen), &error),
+        },
+        "/nodes/{name}/connect": {
+            "post": operation("Connect to a node", &[&name],
+                              schema::<OperationResult<ConnectResult>>(&mut gen), &error),
+        },
+        "/nodes/{name}/disconnect": {
+            "post": operation("Disconnect from a node", &[&name], schema::<DisconnectResult>(&mut gen), &error),
+        },
+        "/nodes/{name}/deploy/{subject}": {
+            "post": operation("Deploy the subject", &[&name, &subject],
+                              schema::<OperationResult<DeployResult>>(&mut gen), &error),
+        },
+        "/nodes/{name}/run/{subject}": {
+            "post": run,
+        },
+        "/nodes/{name}/status/{subject}": {
+            "get": operation("Status of the subject's server", &[&name, &subject], schema::<RunStatus>(&mut gen), &error),
+        },
+        "/events": {
+            "get": events,
+        },
+    });
+
+    /* Served by no route, but part of what in-process and gRPC callers see */
+    schema::<ConnStatus>(&mut gen);
+    schema::<ConnAliveStatus>(&mut gen);
+    schema::<DeployProgress>(&mut gen);
+    schema::<Endpoint>(&mut gen);
+    schema::<LogLine>(&mut gen);
+    schema::<ActivateResult>(&mut gen);
+    schema::<RenameResult>(&mut gen);
+    schema::<RestartResult>(&mut gen);
+    schema::<RollbackResult>(&mut gen);
+    schema::<StopResult>(&mut gen);
+    schema::<UndeployResult>(&mut gen);
+    schema::<UpdateResult>(&mut gen);
+    schema::<UpgradeResult>(&mut gen);
+
+    return json!({
+        "openapi": "3.0.3",
+        "info": { "title": "delta-api", "version": env!("CARGO_PKG_VERSION") },
+        "paths": paths,
+        "components": { "schemas": gen.take_definitions(true) },
+    });
+}
@@ -0,0 +1,60 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use serde::{Deserialize, Serialize};
+
+/* Operating system of a node, as far as the commands sent to it care */
+#[derive(strum_macros::Display)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Platform {
+    #[default]
+    Linux,
+    MacOs,
+    FreeBsd,
+    /* Some other uname; treated like Linux, probing for /proc at run time */
+    OtherUnix,
+    Windows,
+}
+
+impl Platform {
+    /* Kernel name, the first word of "uname -a" */
+    pub fn from_uname(uname: &str) -> Platform {
+        return match uname.split_whitespace().next() {
+            Some("Linux") => Platform::Linux,
+            Some("Darwin") => Platform::MacOs,
+            Some("FreeBSD") => Platform::FreeBsd,
+            _ => Platform::OtherUnix,
+        };
+    }
+
+    /* Whether /proc may hold Linux-style per-process stat files */
+    pub fn has_procfs(&self) -> bool {
+        return matches!(self, Platform::Linux | Platform::OtherUnix);
+    }
+
+    pub fn has_systemd(&self) -> bool {
+        return matches!(self, Platform::Linux | Platform::OtherUnix);
+    }
+}
@@ -0,0 +1,49 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::deploy_progress::DeployPhase;
+use crate::data_model::deploy_subject::DeploySubject;
+use crate::data_model::result::deploy_result::DeployResult;
+use serde::{Deserialize, Serialize};
+
+/* State changes published to EventBus subscribers */
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum PoolEvent {
+    NodeConnected { node: String },
+    NodeDisconnected { node: String },
+    /* Found dead when the session was about to be used; a reconnect follows */
+    ConnectionLost { node: String },
+    DeployStarted { node: String, subject: DeploySubject },
+    DeployPhaseChanged { node: String, subject: DeploySubject, phase: DeployPhase },
+    DeployFinished { node: String, subject: DeploySubject, result: DeployResult },
+    InstanceStarted { node: String, subject: DeploySubject },
+    InstanceStopped { node: String, subject: DeploySubject },
+    /* Seen alive by a health check after not being so */
+    InstanceUp { node: String, subject: DeploySubject },
+    /* Gone without stop() being called, as noticed by a health check or the supervisor */
+    InstanceDied { node: String, subject: DeploySubject },
+    InstanceRestarted { node: String, subject: DeploySubject, restarts: u32 },
+    RestartsExhausted { node: String, subject: DeploySubject, restarts: u32 },
+}
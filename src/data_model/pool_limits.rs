@@ -0,0 +1,49 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use serde::{Deserialize, Serialize};
+
+/*
+ * Pool-wide caps on how hard the controller hits the network, shared by
+ * every batch running on the pool. Zero means no limit.
+ */
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct PoolLimits {
+    /* Nodes a batch call may work on at once, counted across all batches */
+    pub max_sessions: usize,
+    /* Uploads in flight at once */
+    pub max_uploads: usize,
+    /* Least time between two connection attempts to the same host */
+    pub connect_interval_ms: u64,
+}
+
+impl PoolLimits {
+    pub fn new() -> PoolLimits {
+        return PoolLimits {
+            max_sessions: 0,
+            max_uploads: 0,
+            connect_interval_ms: 0,
+        };
+    }
+}
@@ -0,0 +1,93 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use serde::{Deserialize, Serialize};
+
+/* One tool deploys rely on, as found on a node */
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ToolCheck {
+    pub tool: String,
+    pub present: bool,
+    /* As the tool reports it; None when not asked for or not understood */
+    pub version: Option<String>,
+    pub minimum: Option<String>,
+    pub ok: bool,
+}
+
+impl ToolCheck {
+    /*
+     * A tool whose version can't be read passes: minimal images ship
+     * builds, like busybox's, that don't answer --version but do the job.
+     */
+    pub fn new(tool: &str, minimum: Option<&str>, present: bool, version_line: &str) -> ToolCheck {
+        let version = minimum.and_then(|_| parse_version(version_line));
+        let ok = present && match (minimum, &version) {
+            (Some(minimum), Some(version)) => version_at_least(version, minimum),
+            _ => true,
+        };
+        return ToolCheck {
+            tool: tool.to_string(),
+            present,
+            version,
+            minimum: minimum.map(|m| m.to_string()),
+            ok,
+        };
+    }
+}
+
+/* What check_prerequisites() found on a node */
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PrerequisiteReport {
+    pub node: String,
+    pub tools: Vec<ToolCheck>,
+}
+
+impl PrerequisiteReport {
+    pub fn is_ok(&self) -> bool {
+        return self.tools.iter().all(|t| t.ok);
+    }
+
+    pub fn failed(&self) -> Vec<&ToolCheck> {
+        return self.tools.iter().filter(|t| !t.ok).collect();
+    }
+}
+
+/* First dotted number in a --version line: "tar (GNU tar) 1.34" gives 1.34 */
+fn parse_version(line: &str) -> Option<String> {
+    return line.split_whitespace()
+        .map(|word| word.chars().take_while(|c| c.is_ascii_digit() || *c == '.').collect::<String>())
+        .map(|number| number.trim_end_matches('.').to_string())
+        .find(|number| number.contains('.') && number.starts_with(|c: char| c.is_ascii_digit()));
+}
+
+fn version_at_least(version: &str, minimum: &str) -> bool {
+    let parts = |v: &str| v.split('.').map(|p| p.parse::<u64>().unwrap_or(0)).collect::<Vec<u64>>();
+    let (mut version, mut minimum) = (parts(version), parts(minimum));
+    let len = version.len().max(minimum.len());
+    version.resize(len, 0);
+    minimum.resize(len, 0);
+    return version >= minimum;
+}
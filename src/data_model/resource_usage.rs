@@ -0,0 +1,55 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::deploy_subject::DeploySubject;
+use serde::{Deserialize, Serialize};
+
+/* One sample of what an instance costs on its node */
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct ResourceUsage {
+    pub subject: DeploySubject,
+    pub pid: u32,
+    /* Of one core, over the sampling interval; lifetime average where only ps is there */
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+    /* Unknown without /proc, or when it isn't readable */
+    pub open_fds: Option<u64>,
+    pub threads: Option<u32>,
+    /* Unix time, seconds */
+    pub sampled_at: u64,
+}
+
+impl ResourceUsage {
+    pub fn new(subject: DeploySubject, pid: u32) -> ResourceUsage {
+        return ResourceUsage {
+            subject,
+            pid,
+            cpu_percent: 0.0,
+            rss_bytes: 0,
+            open_fds: None,
+            threads: None,
+            sampled_at: 0,
+        };
+    }
+}
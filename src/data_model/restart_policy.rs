@@ -0,0 +1,70 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(strum_macros::Display)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub enum RestartMode {
+    Never,
+    /* Unless the instance exited by itself with status 0, as far as that can be told */
+    OnFailure,
+    Always,
+}
+
+/* What the supervisor does when an instance that should be running is gone */
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct RestartPolicy {
+    pub mode: RestartMode,
+    /* Restarts allowed since the last explicit run(), 0 for no limit */
+    pub max_restarts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl RestartPolicy {
+    pub fn new(mode: RestartMode) -> RestartPolicy {
+        return RestartPolicy {
+            mode,
+            max_restarts: 5,
+            base_delay_ms: 1000,
+            max_delay_ms: 60000,
+        };
+    }
+
+    pub fn never() -> RestartPolicy {
+        return RestartPolicy::new(RestartMode::Never);
+    }
+
+    pub fn allows(&self, restarts: u32) -> bool {
+        return self.mode != RestartMode::Never && (self.max_restarts == 0 || restarts < self.max_restarts);
+    }
+
+    /* Minimum time after the given restart (1-based) before the next one: exponential backoff */
+    pub fn delay(&self, restart: u32) -> Duration {
+        let shift = restart.saturating_sub(1).min(31);
+        return Duration::from_millis(self.base_delay_ms.saturating_mul(1u64 << shift).min(self.max_delay_ms));
+    }
+}
@@ -25,8 +25,26 @@
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum ConnectResult {
     Ok,
     NodeNotFound,
+    InvalidParameter,
     NotAuthenticated,
+    HostKeyMismatch,
+    HostKeyUnknown,
+    /* The host key couldn't be checked: no key from the server, or known_hosts unreadable */
+    HostKeyCheckFailed,
+    JumpHostFailed,
+    ConnectionFailed,
+    Timeout,
+}
+
+impl ConnectResult {
+    pub fn is_transient(&self) -> bool {
+        return matches!(self,
+            ConnectResult::JumpHostFailed
+            | ConnectResult::ConnectionFailed
+            | ConnectResult::Timeout);
+    }
 }
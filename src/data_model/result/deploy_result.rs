@@ -25,12 +25,26 @@
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum DeployResult {
     Ok,
     InvalidArgument,
     NodeNotFound,
     NodeNotConnected,
     DeployCopyFailed,
+    ChecksumMismatch,
     DeployExtractionFailed,
     DeployTestFailed,
+    HookFailed,
+    Cancelled,
+    /* Deployed from the archive a previous deploy left on the node, nothing uploaded */
+    AlreadyDeployed,
+    /* The filesystem holding path has fewer bytes available than the deploy needs */
+    InsufficientDiskSpace { path: String, needed: u64, available: u64 },
+}
+
+impl DeployResult {
+    pub fn is_ok(&self) -> bool {
+        return matches!(self, DeployResult::Ok | DeployResult::AlreadyDeployed);
+    }
 }
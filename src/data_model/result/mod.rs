@@ -22,9 +22,17 @@
  * DEALINGS IN THE SOFTWARE.
  */
 
+pub mod activate_result;
 pub mod add_result;
 pub mod connect_result;
 pub mod deploy_result;
 pub mod disconnect_result;
 pub mod remove_result;
+pub mod rename_result;
+pub mod restart_result;
+pub mod rollback_result;
 pub mod run_result;
+pub mod stop_result;
+pub mod undeploy_result;
+pub mod update_result;
+pub mod upgrade_result;
@@ -25,9 +25,14 @@
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum RunResult {
     Ok,
+    InvalidArgument,
     NodeNotFound,
     NodeNotConnected,
     RunFailed,
+    /* Something else listens on the bind port; its pid if the node would tell */
+    PortInUse { pid: Option<u32> },
+    Cancelled,
 }
@@ -0,0 +1,52 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use serde::{Deserialize, Serialize};
+
+/* How run() keeps the server going on the node */
+#[derive(strum_macros::Display)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub enum RunMode {
+    /* Backgrounded from the login shell, tracked through a pid file */
+    Background,
+    /* System unit; needs root or passwordless sudo */
+    Systemd,
+    /* Unit of the login user's systemd instance */
+    SystemdUser,
+}
+
+impl RunMode {
+    /* Unknown or empty values select the default, background */
+    pub fn from_param(value: &str) -> RunMode {
+        return match value.to_lowercase().replace('_', "-").as_str() {
+            "systemd" => RunMode::Systemd,
+            "systemd-user" => RunMode::SystemdUser,
+            _ => RunMode::Background,
+        };
+    }
+
+    pub fn is_systemd(&self) -> bool {
+        return *self != RunMode::Background;
+    }
+}
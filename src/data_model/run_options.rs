@@ -0,0 +1,65 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+/* What run() passes to the launched server on top of the bind address */
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RunOptions {
+    /* Appended after --server, one shell word each */
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /* Remote directory to start in; the login directory when unset */
+    #[serde(default)]
+    pub working_dir: Option<String>,
+}
+
+impl RunOptions {
+    pub fn new() -> RunOptions {
+        return RunOptions {
+            extra_args: Vec::new(),
+            env: HashMap::new(),
+            working_dir: None,
+        };
+    }
+
+    pub fn arg(mut self, arg: &str) -> RunOptions {
+        self.extra_args.push(arg.to_string());
+        return self;
+    }
+
+    pub fn env(mut self, key: &str, value: &str) -> RunOptions {
+        self.env.insert(key.to_string(), value.to_string());
+        return self;
+    }
+
+    pub fn working_dir(mut self, dir: &str) -> RunOptions {
+        self.working_dir = Some(dir.to_string());
+        return self;
+    }
+}
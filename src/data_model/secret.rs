@@ -0,0 +1,125 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::node_parameters::NodeParameters;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::sync::{OnceLock, RwLock};
+
+pub const REDACTED: &str = "***";
+
+/* Shorter values are not scrubbed, they would mask ordinary text */
+const MIN_SCRUB_LEN: usize = 4;
+
+/*
+ * Holds a sensitive value. Debug, Display and Serialize all print "***";
+ * the value itself is only reachable through expose().
+ */
+#[derive(Clone, PartialEq)]
+pub struct Secret<T>(T);
+
+impl<T> Secret<T> {
+    pub fn new(value: T) -> Secret<T> {
+        return Secret(value);
+    }
+
+    pub fn expose(&self) -> &T {
+        return &self.0;
+    }
+
+    pub fn into_inner(self) -> T {
+        return self.0;
+    }
+}
+
+impl Secret<String> {
+    /* Takes as long wherever the values differ, for tokens checked on every request */
+    pub fn matches(&self, candidate: &str) -> bool {
+        let (a, b) = (self.0.as_bytes(), candidate.as_bytes());
+        let diff = a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y));
+        return diff == 0 && a.len() == b.len();
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "Secret({})", REDACTED);
+    }
+}
+
+impl<T> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str(REDACTED);
+    }
+}
+
+impl<T> Serialize for Secret<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.serialize_str(REDACTED);
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        return T::deserialize(deserializer).map(Secret);
+    }
+}
+
+/* Copy of params with sensitive values masked, for Debug output */
+pub fn redact_params(params: &HashMap<String, String>) -> BTreeMap<&str, &str> {
+    return params.iter()
+        .map(|(k, v)| (k.as_str(), if NodeParameters::is_sensitive(k) { REDACTED } else { v.as_str() }))
+        .collect();
+}
+
+fn known_secrets() -> &'static RwLock<HashSet<String>> {
+    static SECRETS: OnceLock<RwLock<HashSet<String>>> = OnceLock::new();
+    return SECRETS.get_or_init(|| RwLock::new(HashSet::new()));
+}
+
+/* Remembers a resolved credential so scrub() can mask it */
+pub fn register_secret(value: &str) {
+    if value.len() < MIN_SCRUB_LEN {
+        return;
+    }
+
+    let secrets = known_secrets().read().unwrap_or_else(|e| e.into_inner());
+    if !secrets.contains(value) {
+        drop(secrets);
+        known_secrets().write().unwrap_or_else(|e| e.into_inner()).insert(value.to_string());
+    }
+}
+
+/* Masks every registered credential in text meant for logs or errors */
+pub fn scrub(text: &str) -> String {
+    let secrets = known_secrets().read().unwrap_or_else(|e| e.into_inner());
+    let mut text = text.to_string();
+    for secret in secrets.iter() {
+        if text.contains(secret.as_str()) {
+            text = text.replace(secret.as_str(), REDACTED);
+        }
+    }
+    return text;
+}
@@ -0,0 +1,289 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::delta_error::DeltaError;
+use crate::data_model::node_parameters::NodeParameters;
+use crate::obj_model::ssh_config::expand_home;
+use serde_yaml::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+const ALL: &str = "all";
+const UNGROUPED: &str = "ungrouped";
+
+/* A host of an Ansible inventory, with its groups as tags */
+pub struct AnsibleHost {
+    pub name: String,
+    pub fqdn: String,
+    pub params: HashMap<String, String>,
+    pub tags: HashSet<String>,
+}
+
+#[derive(Default)]
+struct Group {
+    hosts: Vec<String>,
+    vars: HashMap<String, String>,
+    children: Vec<String>,
+}
+
+#[derive(Default)]
+struct Inventory {
+    groups: BTreeMap<String, Group>,
+    host_vars: BTreeMap<String, HashMap<String, String>>,
+}
+
+/* .yml/.yaml files are read as YAML inventories, anything else as INI */
+pub fn read_ansible_inventory(path: &Path) -> Result<Vec<AnsibleHost>, DeltaError> {
+    let text = fs::read_to_string(path)?;
+    let yaml = matches!(path.extension().and_then(|e| e.to_str()), Some("yml") | Some("yaml"));
+    let inventory = if yaml { parse_yaml(&text)? } else { parse_ini(&text)? };
+    return Ok(inventory.resolve());
+}
+
+fn parse_ini(text: &str) -> Result<Inventory, DeltaError> {
+    let mut inv = Inventory::default();
+    let mut section = (UNGROUPED.to_string(), "hosts".to_string());
+
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = match header.split_once(':') {
+                Some((group, kind)) if kind == "vars" || kind == "children" => (group.to_string(), kind.to_string()),
+                Some(_) => return Err(parse_error(n, line)),
+                None => (header.to_string(), "hosts".to_string()),
+            };
+            inv.groups.entry(section.0.clone()).or_default();
+            continue;
+        }
+
+        let group = inv.groups.entry(section.0.clone()).or_default();
+        match section.1.as_str() {
+            "vars" => {
+                let (key, value) = line.split_once('=').ok_or_else(|| parse_error(n, line))?;
+                group.vars.insert(key.trim().to_string(), unquote(value.trim()));
+            }
+            "children" => group.children.push(line.to_string()),
+            _ => {
+                let mut words = line.split_whitespace();
+                let pattern = words.next().ok_or_else(|| parse_error(n, line))?;
+                let mut vars = HashMap::new();
+                for word in words {
+                    let (key, value) = word.split_once('=').ok_or_else(|| parse_error(n, line))?;
+                    vars.insert(key.to_string(), unquote(value));
+                }
+
+                for host in expand_range(pattern).ok_or_else(|| parse_error(n, line))? {
+                    group.hosts.push(host.clone());
+                    inv.host_vars.entry(host).or_default().extend(vars.clone());
+                }
+            }
+        }
+    }
+
+    return Ok(inv);
+}
+
+fn parse_yaml(text: &str) -> Result<Inventory, DeltaError> {
+    let root: Value = serde_yaml::from_str(text).map_err(|e| DeltaError::Parse(e.to_string()))?;
+    let mut inv = Inventory::default();
+
+    let groups = root.as_mapping().ok_or_else(|| DeltaError::Parse("inventory is not a mapping".to_string()))?;
+    for (name, group) in groups {
+        parse_yaml_group(&mut inv, &scalar(name), group)?;
+    }
+
+    return Ok(inv);
+}
+
+fn parse_yaml_group(inv: &mut Inventory, name: &str, value: &Value) -> Result<(), DeltaError> {
+    let mut group = Group::default();
+
+    if let Some(hosts) = value.get("hosts").and_then(Value::as_mapping) {
+        for (host, vars) in hosts {
+            let host = scalar(host);
+            for h in expand_range(&host).ok_or_else(|| DeltaError::Parse(format!("bad host pattern: {}", host)))? {
+                inv.host_vars.entry(h.clone()).or_default().extend(yaml_vars(vars));
+                group.hosts.push(h);
+            }
+        }
+    }
+
+    if let Some(vars) = value.get("vars") {
+        group.vars = yaml_vars(vars);
+    }
+
+    if let Some(children) = value.get("children").and_then(Value::as_mapping) {
+        for (child, child_value) in children {
+            let child = scalar(child);
+            parse_yaml_group(inv, &child, child_value)?;
+            group.children.push(child);
+        }
+    }
+
+    /* A group may be listed in several places; merge the occurrences */
+    let existing = inv.groups.entry(name.to_string()).or_default();
+    existing.hosts.extend(group.hosts);
+    existing.vars.extend(group.vars);
+    existing.children.extend(group.children);
+    return Ok(());
+}
+
+fn yaml_vars(value: &Value) -> HashMap<String, String> {
+    return match value.as_mapping() {
+        Some(m) => m.iter().map(|(k, v)| (scalar(k), scalar(v))).collect(),
+        None => HashMap::new(),
+    };
+}
+
+fn scalar(value: &Value) -> String {
+    return match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => "".to_string(),
+    };
+}
+
+impl Inventory {
+    fn resolve(&self) -> Vec<AnsibleHost> {
+        /* Depth of every group below "all"; deeper groups override their parents' vars */
+        let mut depth: HashMap<String, usize> = HashMap::new();
+        let mut parents: HashMap<String, Vec<String>> = HashMap::new();
+        for (name, group) in &self.groups {
+            for child in &group.children {
+                parents.entry(child.clone()).or_default().push(name.clone());
+            }
+        }
+        for name in self.groups.keys() {
+            group_depth(name, &parents, &mut depth, &mut HashSet::new());
+        }
+
+        let mut hosts = vec![];
+        for (name, host_vars) in &self.host_vars {
+            let mut groups: Vec<&String> = self.groups.iter()
+                .filter(|(_, g)| g.hosts.contains(name))
+                .map(|(n, _)| n)
+                .collect();
+
+            /* Add ancestors, so vars and tags of parent groups apply */
+            let mut i = 0;
+            while i < groups.len() {
+                for p in parents.get(groups[i]).map(|v| v.as_slice()).unwrap_or_default() {
+                    if !groups.contains(&p) {
+                        groups.push(p);
+                    }
+                }
+                i += 1;
+            }
+            groups.sort_by_key(|g| (depth.get(*g).copied().unwrap_or(0), *g));
+
+            let mut vars = self.groups.get(ALL).map(|g| g.vars.clone()).unwrap_or_default();
+            for g in &groups {
+                vars.extend(self.groups[*g].vars.clone());
+            }
+            vars.extend(host_vars.clone());
+
+            let tags = groups.into_iter()
+                .filter(|g| *g != ALL && *g != UNGROUPED)
+                .cloned()
+                .collect();
+            hosts.push(to_host(name, &vars, tags));
+        }
+
+        return hosts;
+    }
+}
+
+fn group_depth(name: &str, parents: &HashMap<String, Vec<String>>,
+               depth: &mut HashMap<String, usize>, visiting: &mut HashSet<String>) -> usize {
+    if let Some(d) = depth.get(name) {
+        return *d;
+    }
+
+    /* Cycles are invalid in Ansible too; just stop descending */
+    if !visiting.insert(name.to_string()) {
+        return 0;
+    }
+
+    let d = parents.get(name)
+        .map(|ps| ps.iter().map(|p| group_depth(p, parents, depth, visiting) + 1).max().unwrap_or(0))
+        .unwrap_or(0);
+    depth.insert(name.to_string(), d);
+    return d;
+}
+
+fn to_host(name: &str, vars: &HashMap<String, String>, tags: HashSet<String>) -> AnsibleHost {
+    let fqdn = vars.get("ansible_host").cloned().unwrap_or_else(|| name.to_string());
+
+    /* Kept apart from the host, "host:port" would misread an IPv6 ansible_host */
+    let mut params = HashMap::new();
+    if let Some(port) = vars.get("ansible_port").or_else(|| vars.get("ansible_ssh_port")) {
+        params.insert(NodeParameters::SshPort.to_string(), port.clone());
+    }
+    let mapping = [
+        (&["ansible_user", "ansible_ssh_user"][..], NodeParameters::Username),
+        (&["ansible_password", "ansible_ssh_pass"][..], NodeParameters::Password),
+        (&["ansible_ssh_private_key_file"][..], NodeParameters::IdentityFile),
+    ];
+    for (keys, param) in mapping {
+        if let Some(value) = keys.iter().find_map(|k| vars.get(*k)) {
+            params.insert(param.to_string(), value.clone());
+        }
+    }
+    if let Some(key) = params.get_mut(&NodeParameters::IdentityFile.to_string()) {
+        *key = expand_home(key);
+    }
+
+    return AnsibleHost { name: name.to_string(), fqdn, params, tags };
+}
+
+/* Expands one numeric "web[01:03]" range, keeping zero padding */
+fn expand_range(pattern: &str) -> Option<Vec<String>> {
+    let (prefix, rest) = match pattern.split_once('[') {
+        Some(p) => p,
+        None => return Some(vec![pattern.to_string()]),
+    };
+    let (range, suffix) = rest.split_once(']')?;
+    let (start, end) = range.split_once(':')?;
+    let width = start.len();
+    let (start, end) = (start.parse::<u64>().ok()?, end.parse::<u64>().ok()?);
+    if start > end {
+        return None;
+    }
+
+    return Some((start..=end).map(|i| format!("{}{:0width$}{}", prefix, i, suffix, width = width)).collect());
+}
+
+fn unquote(value: &str) -> String {
+    return value.trim_matches(|c| c == '"' || c == '\'').to_string();
+}
+
+fn parse_error(line: usize, text: &str) -> DeltaError {
+    return DeltaError::Parse(format!("line {}: {}", line + 1, text));
+}
@@ -0,0 +1,197 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::archive_format::ArchiveFormat;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/* Assumed compression ratio where the archive doesn't record its unpacked size */
+const FALLBACK_RATIO: u64 = 4;
+
+/* Detects the artifact format by extension, falling back to magic bytes */
+pub fn detect_format(path: &Path) -> io::Result<ArchiveFormat> {
+    if path.is_dir() {
+        return Ok(ArchiveFormat::Directory);
+    }
+
+    if let Some(format) = ArchiveFormat::from_extension(&path.to_string_lossy()) {
+        return Ok(format);
+    }
+
+    let mut header = Vec::new();
+    File::open(path)?.take(512).read_to_end(&mut header)?;
+    return match ArchiveFormat::from_magic(&header) {
+        Some(format) => Ok(format),
+        None => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                   format!("unknown archive format: {}", path.display()))),
+    };
+}
+
+/* Lists directories and files below root, relative to it, parents first */
+pub fn walk_dir(root: &Path) -> io::Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(rel) = pending.pop() {
+        let mut entries: Vec<_> = fs::read_dir(root.join(&rel))?.collect::<Result<_, _>>()?;
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries {
+            let rel_path = rel.join(entry.file_name());
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                dirs.push(rel_path.clone());
+                pending.push(rel_path);
+            } else if file_type.is_file() {
+                files.push(rel_path);
+            }
+        }
+    }
+
+    return Ok((dirs, files));
+}
+
+/*
+ * Bytes the artifact takes once unpacked, read from what the format
+ * records without unpacking it: the gzip trailer, the xz index or the zip
+ * central directory. Where that fails, the packed size times
+ * FALLBACK_RATIO.
+ */
+pub fn extracted_size(path: &Path, format: &ArchiveFormat) -> io::Result<u64> {
+    if *format == ArchiveFormat::Directory {
+        let (_dirs, files) = walk_dir(path)?;
+        let mut total = 0;
+        for file in files {
+            total += path.join(file).metadata()?.len();
+        }
+        return Ok(total);
+    }
+
+    let mut file = File::open(path)?;
+    let packed = file.metadata()?.len();
+    let recorded = match format {
+        ArchiveFormat::Tar => Some(packed),
+        ArchiveFormat::TarGz => gzip_size(&mut file, packed),
+        ArchiveFormat::TarXz => xz_size(&mut file, packed),
+        ArchiveFormat::Zip => zip_size(&mut file, packed),
+        ArchiveFormat::Directory => None,
+    };
+    return Ok(recorded.unwrap_or(packed.saturating_mul(FALLBACK_RATIO)));
+}
+
+fn read_at(file: &mut File, offset: u64, len: usize) -> Option<Vec<u8>> {
+    let mut buffer = vec![0; len];
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    file.read_exact(&mut buffer).ok()?;
+    return Some(buffer);
+}
+
+fn le_u32(bytes: &[u8]) -> u64 {
+    return u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as u64;
+}
+
+/* ISIZE of the last member, the size modulo 4 GiB; distrusted when smaller than the archive */
+fn gzip_size(file: &mut File, packed: u64) -> Option<u64> {
+    let isize = le_u32(&read_at(file, packed.checked_sub(4)?, 4)?);
+    return if isize >= packed { Some(isize) } else { None };
+}
+
+/* Sum of the uncompressed sizes in the index of the last stream */
+fn xz_size(file: &mut File, packed: u64) -> Option<u64> {
+    let footer = read_at(file, packed.checked_sub(12)?, 12)?;
+    if &footer[10..12] != b"YZ" {
+        return None;
+    }
+    let index_len = (le_u32(&footer[4..8]) + 1) * 4;
+    let index = read_at(file, packed.checked_sub(12 + index_len)?, index_len as usize)?;
+    if index.first() != Some(&0) {
+        return None;
+    }
+
+    let mut pos = 1;
+    let mut varint = || -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..63).step_by(7) {
+            let byte = *index.get(pos)?;
+            pos += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        return None;
+    };
+
+    let records = varint()?;
+    let mut total = 0u64;
+    for _ in 0..records {
+        let _unpadded = varint()?;
+        total = total.checked_add(varint()?)?;
+    }
+    return Some(total);
+}
+
+/* Sum of the uncompressed sizes in the central directory; no zip64 */
+fn zip_size(file: &mut File, packed: u64) -> Option<u64> {
+    let tail_len = packed.min(22 + 65535);
+    let tail = read_at(file, packed - tail_len, tail_len as usize)?;
+    let eocd = tail.windows(4).rposition(|w| w == b"PK\x05\x06")?;
+    let record = tail.get(eocd..eocd + 22)?;
+    let entries = u16::from_le_bytes([record[10], record[11]]);
+    let (offset, len) = (le_u32(&record[16..20]), le_u32(&record[12..16]));
+    if offset.checked_add(len)? > packed {
+        return None;
+    }
+    let directory = read_at(file, offset, len as usize)?;
+
+    let mut pos = 0;
+    let mut total = 0u64;
+    for _ in 0..entries {
+        let header = directory.get(pos..pos + 46)?;
+        if &header[0..4] != b"PK\x01\x02" {
+            return None;
+        }
+        total += le_u32(&header[24..28]);
+        let name_len = u16::from_le_bytes([header[28], header[29]]) as usize;
+        let extra_len = u16::from_le_bytes([header[30], header[31]]) as usize;
+        let comment_len = u16::from_le_bytes([header[32], header[33]]) as usize;
+        pos += 46 + name_len + extra_len + comment_len;
+    }
+    return Some(total);
+}
+
+#[cfg(unix)]
+pub fn file_mode(path: &Path) -> i32 {
+    use std::os::unix::fs::PermissionsExt;
+    return match fs::metadata(path) {
+        Ok(m) => (m.permissions().mode() & 0o7777) as i32,
+        Err(_e) => 0o644,
+    };
+}
+
+#[cfg(not(unix))]
+pub fn file_mode(_path: &Path) -> i32 {
+    return 0o644;
+}
@@ -0,0 +1,76 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::archive_format::ArchiveFormat;
+use crate::data_model::delta_error::DeltaError;
+use crate::obj_model::shell::is_shell_safe;
+
+const URL_SCHEMES: [&str; 3] = ["http://", "https://", "s3://"];
+
+/*
+ * A distr the node downloads itself, written as a URL with the expected
+ * checksum in the fragment: https://host/app.tar.xz#sha256=<hex>. The
+ * fragment never reaches the server, and the checksum is what the
+ * download is verified against and cached under.
+ */
+#[derive(PartialEq, Clone, Debug)]
+pub struct ArtifactUrl {
+    pub url: String,
+    pub sha256: String,
+}
+
+impl ArtifactUrl {
+    pub fn is_url(distr: &str) -> bool {
+        let lower = distr.trim().to_lowercase();
+        return URL_SCHEMES.iter().any(|s| lower.starts_with(s));
+    }
+
+    pub fn parse(distr: &str) -> Result<ArtifactUrl, DeltaError> {
+        let invalid = |why: &str| DeltaError::InvalidParameter("distr".to_string(), format!("{} ({})", distr, why));
+        let (url, fragment) = distr.trim().split_once('#').ok_or_else(|| invalid("no #sha256= checksum"))?;
+        let sha256 = fragment.strip_prefix("sha256=").ok_or_else(|| invalid("no #sha256= checksum"))?;
+        if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid("bad checksum"));
+        }
+        if !ArtifactUrl::is_url(url) || !is_shell_safe(url) {
+            return Err(invalid("bad URL"));
+        }
+
+        return Ok(ArtifactUrl { url: url.to_string(), sha256: sha256.to_lowercase() });
+    }
+
+    pub fn with_checksum(url: &str, sha256: &str) -> String {
+        return format!("{}#sha256={}", url, sha256);
+    }
+
+    pub fn is_s3(&self) -> bool {
+        return self.url.to_lowercase().starts_with("s3://");
+    }
+
+    /* Going by the extension, as there is nothing to look at before the download */
+    pub fn format(&self) -> Option<ArchiveFormat> {
+        let path = self.url.split(['?', '#']).next().unwrap_or("");
+        return ArchiveFormat::from_extension(path);
+    }
+}
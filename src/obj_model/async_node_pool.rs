@@ -0,0 +1,381 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::conn_alive_status::{ConnAliveStatus, SubjectAliveStatus};
+use crate::data_model::conn_status::ConnStatus;
+use crate::data_model::delta_error::DeltaError;
+use crate::data_model::deploy_progress::DeployProgress;
+use crate::data_model::deploy_subject::DeploySubject;
+use crate::data_model::endpoint::Endpoint;
+use crate::data_model::exec_output::ExecOutput;
+use crate::data_model::file_info::FileInfo;
+use crate::data_model::installed_versions::InstalledVersions;
+use crate::data_model::log_line::LogLine;
+use crate::data_model::instance_logs::InstanceLogs;
+use crate::data_model::node_summary::NodeSummary;
+use crate::data_model::operation_record::OperationRecord;
+use crate::data_model::operation_result::OperationResult;
+use crate::data_model::pool_event::PoolEvent;
+use crate::data_model::pool_limits::PoolLimits;
+use crate::data_model::prerequisite_report::PrerequisiteReport;
+use crate::data_model::resource_usage::ResourceUsage;
+use crate::data_model::run_options::RunOptions;
+use crate::data_model::run_status::RunStatus;
+use crate::data_model::supervisor_event::SupervisorEvent;
+use crate::data_model::result::activate_result::ActivateResult;
+use crate::data_model::result::add_result::AddResult;
+use crate::data_model::result::connect_result::ConnectResult;
+use crate::data_model::result::deploy_result::DeployResult;
+use crate::data_model::result::disconnect_result::DisconnectResult;
+use crate::data_model::result::remove_result::RemoveResult;
+use crate::data_model::result::rename_result::RenameResult;
+use crate::data_model::result::restart_result::RestartResult;
+use crate::data_model::result::rollback_result::RollbackResult;
+use crate::data_model::result::run_result::RunResult;
+use crate::data_model::result::stop_result::StopResult;
+use crate::data_model::result::undeploy_result::UndeployResult;
+use crate::data_model::result::update_result::UpdateResult;
+use crate::data_model::result::upgrade_result::UpgradeResult;
+#[cfg(feature = "encryption")]
+use crate::obj_model::credential_store::CredentialStore;
+use crate::obj_model::node_builder::NodeBuilder;
+use crate::obj_model::node_pool::NodePool;
+use crate::obj_model::shared_node_pool::SharedNodePool;
+use std::collections::HashMap;
+use std::net::{SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::panic;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/* Lines stream_logs() buffers for a slow receiver */
+const LOG_LINE_BUFFER: usize = 1024;
+
+/*
+ * Async facade over SharedNodePool. ssh2 is blocking, so every operation runs
+ * on tokio's blocking thread pool; operations on different nodes proceed
+ * concurrently.
+ */
+#[derive(Clone)]
+pub struct AsyncNodePool {
+    inner: Arc<SharedNodePool>,
+}
+
+impl AsyncNodePool {
+    pub fn new(pool: NodePool) -> AsyncNodePool {
+        return AsyncNodePool::from_shared(Arc::new(SharedNodePool::from_pool(pool)));
+    }
+
+    pub fn from_shared(pool: Arc<SharedNodePool>) -> AsyncNodePool {
+        return AsyncNodePool { inner: pool };
+    }
+
+    pub async fn with<R, F>(&self, f: F) -> R
+    where
+        R: Send + 'static,
+        F: FnOnce(&SharedNodePool) -> R + Send + 'static,
+    {
+        let inner = self.inner.clone();
+        let handle = tokio::task::spawn_blocking(move || {
+            return f(&inner);
+        });
+
+        return match handle.await {
+            Ok(r) => r,
+            Err(e) => panic::resume_unwind(e.into_panic()),
+        };
+    }
+
+    pub async fn add(&self, name: String, fqdn: String,
+                     node_params: HashMap<String, String>) -> AddResult {
+        return self.with(move |pool| pool.add(name, fqdn, node_params)).await;
+    }
+
+    pub async fn add_node(&self, builder: NodeBuilder) -> Result<AddResult, DeltaError> {
+        return self.with(move |pool| pool.add_node(builder)).await;
+    }
+
+    pub async fn remove(&self, name: String) -> RemoveResult {
+        return self.with(move |pool| pool.remove(name)).await;
+    }
+
+    pub async fn connect(&self, name: String) -> ConnectResult {
+        return self.with(move |pool| pool.connect(name)).await;
+    }
+
+    pub async fn connect_tracked(&self, name: String) -> OperationResult<ConnectResult> {
+        return self.with(move |pool| pool.connect_tracked(name)).await;
+    }
+
+    pub async fn rename(&self, old: String, new: String) -> RenameResult {
+        return self.with(move |pool| pool.rename(old, new)).await;
+    }
+
+    pub async fn update(&self, name: String, fqdn: Option<String>,
+                        params: HashMap<String, String>) -> UpdateResult {
+        return self.with(move |pool| pool.update(name, fqdn, params)).await;
+    }
+
+    pub async fn disconnect(&self, name: String) -> DisconnectResult {
+        return self.with(move |pool| pool.disconnect(name)).await;
+    }
+
+    pub async fn is_connected(&self, name: String) -> ConnStatus {
+        return self.with(move |pool| pool.is_connected(name)).await;
+    }
+
+    pub async fn is_alive(&self, name: String) -> ConnAliveStatus {
+        return self.with(move |pool| pool.is_alive(name)).await;
+    }
+
+    pub async fn is_alive_cached(&self, name: String, max_age: Duration) -> ConnAliveStatus {
+        return self.with(move |pool| pool.is_alive_cached(name, max_age)).await;
+    }
+
+    pub async fn is_subject_alive(&self, name: String, subject: DeploySubject) -> SubjectAliveStatus {
+        return self.with(move |pool| pool.is_subject_alive(name, subject)).await;
+    }
+
+    pub async fn is_alive_all(&self) -> HashMap<String, ConnAliveStatus> {
+        return self.with(move |pool| pool.is_alive_all()).await;
+    }
+
+    pub async fn is_alive_all_cached(&self, max_age: Duration) -> HashMap<String, ConnAliveStatus> {
+        return self.with(move |pool| pool.is_alive_all_cached(max_age)).await;
+    }
+
+    pub async fn deploy(&self, name: String, subject: DeploySubject) -> DeployResult {
+        return self.with(move |pool| pool.deploy(name, subject)).await;
+    }
+
+    pub async fn get_deploy_progress(&self, name: String) -> Option<DeployProgress> {
+        return self.with(move |pool| pool.get_deploy_progress(name)).await;
+    }
+
+    pub async fn deploy_tracked(&self, name: String, subject: DeploySubject) -> OperationResult<DeployResult> {
+        return self.with(move |pool| pool.deploy_tracked(name, subject)).await;
+    }
+
+    pub async fn run(&self, name: String, subject: DeploySubject) -> RunResult {
+        return self.with(move |pool| pool.run(name, subject)).await;
+    }
+
+    pub async fn run_with_options(&self, name: String, subject: DeploySubject,
+                                  options: RunOptions) -> RunResult {
+        return self.with(move |pool| pool.run_with_options(name, subject, options)).await;
+    }
+
+    pub async fn run_tracked(&self, name: String, subject: DeploySubject,
+                             options: RunOptions) -> OperationResult<RunResult> {
+        return self.with(move |pool| pool.run_tracked(name, subject, options)).await;
+    }
+
+    pub async fn restart(&self, name: String, subject: DeploySubject) -> RestartResult {
+        return self.with(move |pool| pool.restart(name, subject)).await;
+    }
+
+    pub async fn stop(&self, name: String, subject: DeploySubject) -> StopResult {
+        return self.with(move |pool| pool.stop(name, subject)).await;
+    }
+
+    pub async fn deploy_many(&self, names: Vec<String>, subject: DeploySubject) -> HashMap<String, DeployResult> {
+        return self.with(move |pool| pool.deploy_many(names, subject)).await;
+    }
+
+    pub async fn execute_command(&self, name: String, cmd: String) -> Result<ExecOutput, DeltaError> {
+        return self.with(move |pool| pool.execute_command(name, cmd)).await;
+    }
+
+    pub async fn execute_on(&self, selector: String, cmd: String)
+                            -> Result<HashMap<String, Result<ExecOutput, DeltaError>>, DeltaError> {
+        return self.with(move |pool| pool.execute_on(&selector, cmd)).await;
+    }
+
+    pub async fn list(&self) -> Vec<NodeSummary> {
+        return self.with(move |pool| pool.list()).await;
+    }
+
+    pub async fn history(&self, name: String) -> Option<Vec<OperationRecord>> {
+        return self.with(move |pool| pool.history(name)).await;
+    }
+
+    pub async fn tag(&self, name: String, tag: String) -> bool {
+        return self.with(move |pool| pool.tag(name, tag)).await;
+    }
+
+    pub async fn untag(&self, name: String, tag: String) -> bool {
+        return self.with(move |pool| pool.untag(name, tag)).await;
+    }
+
+    pub async fn resolve_tags(&self, expr: String) -> Result<Vec<String>, DeltaError> {
+        return self.with(move |pool| pool.resolve_tags(&expr)).await;
+    }
+
+    pub async fn select(&self, pattern: String, with_fqdn: bool) -> Result<Vec<String>, DeltaError> {
+        return self.with(move |pool| pool.select(&pattern, with_fqdn)).await;
+    }
+
+    pub async fn connect_all(&self) -> HashMap<String, ConnectResult> {
+        return self.with(move |pool| pool.connect_all()).await;
+    }
+
+    pub async fn connect_all_tracked(&self) -> HashMap<String, OperationResult<ConnectResult>> {
+        return self.with(move |pool| pool.connect_all_tracked()).await;
+    }
+
+    pub async fn disconnect_all(&self) -> HashMap<String, DisconnectResult> {
+        return self.with(move |pool| pool.disconnect_all()).await;
+    }
+
+    pub async fn rollback(&self, name: String, subject: DeploySubject) -> RollbackResult {
+        return self.with(move |pool| pool.rollback(name, subject)).await;
+    }
+
+    pub async fn upgrade(&self, name: String, subject: DeploySubject, new_distr: String) -> UpgradeResult {
+        return self.with(move |pool| pool.upgrade(name, subject, new_distr)).await;
+    }
+
+    pub async fn undeploy(&self, name: String, subject: DeploySubject) -> UndeployResult {
+        return self.with(move |pool| pool.undeploy(name, subject)).await;
+    }
+
+    pub async fn adopt(&self, name: String) -> Result<ConnAliveStatus, DeltaError> {
+        return self.with(move |pool| pool.adopt(name)).await;
+    }
+
+    pub async fn status(&self, name: String, subject: DeploySubject) -> Result<RunStatus, DeltaError> {
+        return self.with(move |pool| pool.status(name, subject)).await;
+    }
+
+    pub async fn endpoint(&self, name: String, subject: DeploySubject) -> Result<Endpoint, DeltaError> {
+        return self.with(move |pool| pool.endpoint(name, subject)).await;
+    }
+
+    pub async fn remote_stat(&self, name: String, path: String) -> Result<Option<FileInfo>, DeltaError> {
+        return self.with(move |pool| pool.remote_stat(name, path)).await;
+    }
+
+    pub async fn remote_exists(&self, name: String, path: String) -> Result<bool, DeltaError> {
+        return self.with(move |pool| pool.remote_exists(name, path)).await;
+    }
+
+    pub async fn check_prerequisites(&self, name: String) -> Result<PrerequisiteReport, DeltaError> {
+        return self.with(move |pool| pool.check_prerequisites(name)).await;
+    }
+
+    pub async fn download_file(&self, name: String, remote_path: String, local_path: PathBuf) -> Result<u64, DeltaError> {
+        return self.with(move |pool| pool.download_file(name, remote_path, &local_path)).await;
+    }
+
+    pub async fn upload_dir(&self, name: String, local_dir: PathBuf, remote_dir: String) -> Result<u64, DeltaError> {
+        return self.with(move |pool| pool.upload_dir(name, &local_dir, remote_dir)).await;
+    }
+
+    pub async fn copy_between(&self, src_node: String, src_path: String,
+                              dst_node: String, dst_path: String) -> Result<u64, DeltaError> {
+        return self.with(move |pool| pool.copy_between(src_node, src_path, dst_node, dst_path)).await;
+    }
+
+    pub async fn forward(&self, name: String, subject: DeploySubject) -> Result<SocketAddr, DeltaError> {
+        return self.with(move |pool| pool.forward(name, subject)).await;
+    }
+
+    pub async fn unforward(&self, name: String, subject: DeploySubject) -> bool {
+        return self.with(move |pool| pool.unforward(name, subject)).await;
+    }
+
+    pub async fn connect_client(&self, name: String, subject: DeploySubject) -> Result<TcpStream, DeltaError> {
+        return self.with(move |pool| pool.connect_client(name, subject)).await;
+    }
+
+    pub async fn fetch_logs(&self, name: String, subject: DeploySubject,
+                            tail_lines: Option<usize>) -> Result<InstanceLogs, DeltaError> {
+        return self.with(move |pool| pool.fetch_logs(name, subject, tail_lines)).await;
+    }
+
+    /*
+     * Log lines as they arrive; the stream is read on a blocking thread that
+     * ends once the receiver is dropped and the next line comes in.
+     */
+    pub async fn stream_logs(&self, name: String, subject: DeploySubject)
+                             -> Result<mpsc::Receiver<Result<LogLine, DeltaError>>, DeltaError> {
+        let stream = self.with(move |pool| pool.stream_logs(name, subject)).await?;
+        let (tx, rx) = mpsc::channel(LOG_LINE_BUFFER);
+        tokio::task::spawn_blocking(move || {
+            for line in stream {
+                if tx.blocking_send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        return Ok(rx);
+    }
+
+    pub async fn resource_usage(&self, name: String, subject: DeploySubject)
+                                -> Result<Option<ResourceUsage>, DeltaError> {
+        return self.with(move |pool| pool.resource_usage(name, subject)).await;
+    }
+
+    pub async fn resource_snapshot(&self) -> HashMap<String, Vec<ResourceUsage>> {
+        return self.with(move |pool| pool.resource_snapshot()).await;
+    }
+
+    /* Doesn't block: subscribing only registers a sender */
+    pub fn subscribe(&self) -> mpsc::UnboundedReceiver<PoolEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.inner.event_bus().subscribe_with(Box::new(move |event| tx.send(event.clone()).is_ok()));
+        return rx;
+    }
+
+    pub fn limits(&self) -> PoolLimits {
+        return self.inner.limits();
+    }
+
+    /* Doesn't block either: it only swaps the caps and wakes the waiters */
+    pub fn set_limits(&self, limits: PoolLimits) {
+        self.inner.set_limits(limits);
+    }
+
+    pub async fn supervise(&self) -> Vec<SupervisorEvent> {
+        return self.with(move |pool| pool.supervise()).await;
+    }
+
+    pub async fn list_versions(&self, name: String, subject: DeploySubject) -> Result<InstalledVersions, DeltaError> {
+        return self.with(move |pool| pool.list_versions(name, subject)).await;
+    }
+
+    pub async fn activate(&self, name: String, subject: DeploySubject, version: String) -> ActivateResult {
+        return self.with(move |pool| pool.activate(name, subject, version)).await;
+    }
+
+    pub async fn save(&self, path: PathBuf) -> Result<(), DeltaError> {
+        return self.with(move |pool| pool.save(&path)).await;
+    }
+
+    #[cfg(feature = "encryption")]
+    pub async fn save_encrypted(&self, path: PathBuf, store: CredentialStore) -> Result<(), DeltaError> {
+        return self.with(move |pool| pool.save_encrypted(&path, &store)).await;
+    }
+}
@@ -0,0 +1,79 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::audit_record::AuditRecord;
+use crate::data_model::delta_error::DeltaError;
+use tracing::error;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/*
+ * Receives an AuditRecord for every connect, execute, upload, deploy, run,
+ * stop and restart. Closures taking &AuditRecord are sinks too.
+ */
+pub trait AuditSink {
+    fn record(&self, record: &AuditRecord);
+}
+
+pub type AuditSinkRef = Arc<dyn AuditSink + Send + Sync>;
+
+impl<F: Fn(&AuditRecord)> AuditSink for F {
+    fn record(&self, record: &AuditRecord) {
+        self(record);
+    }
+}
+
+/* Appends records to a file, one JSON object per line */
+pub struct FileAuditSink {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl FileAuditSink {
+    pub fn open(path: &Path) -> Result<FileAuditSink, DeltaError> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        return Ok(FileAuditSink { path: path.to_path_buf(), file: Mutex::new(file) });
+    }
+}
+
+impl AuditSink for FileAuditSink {
+    fn record(&self, record: &AuditRecord) {
+        let mut line = match serde_json::to_string(record) {
+            Ok(l) => l,
+            Err(e) => {
+                error!("Failed to serialize audit record: {}", e);
+                return;
+            }
+        };
+        line.push('\n');
+
+        /* One write per record, so concurrent pools appending to the same file don't interleave */
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = file.write_all(line.as_bytes()) {
+            error!("Failed to write audit log {}: {}", self.path.display(), e);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::delta_error::DeltaError;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+thread_local! {
+    static CURRENT_TOKEN: RefCell<Option<CancellationToken>> = const { RefCell::new(None) };
+}
+
+/*
+ * Asks an operation to stop. Transports check the token of the operation
+ * running on their thread between chunks of output and upload data; once
+ * cancelled they close the channel (or kill the process) and fail with
+ * DeltaError::Cancelled, which the operation reports as its Cancelled result.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        return CancellationToken { cancelled: Arc::new(AtomicBool::new(false)) };
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        return self.cancelled.load(Ordering::SeqCst);
+    }
+}
+
+/* Makes token the one this thread's operations check, until dropped */
+pub struct CancelScope {
+    previous: Option<CancellationToken>,
+}
+
+impl CancelScope {
+    pub fn enter(token: &CancellationToken) -> CancelScope {
+        let previous = CURRENT_TOKEN.with(|current| current.borrow_mut().replace(token.clone()));
+        return CancelScope { previous };
+    }
+}
+
+impl Drop for CancelScope {
+    fn drop(&mut self) {
+        CURRENT_TOKEN.with(|current| *current.borrow_mut() = self.previous.take());
+    }
+}
+
+/* Token of the operation running on this thread, if it can be cancelled */
+pub fn current_token() -> Option<CancellationToken> {
+    return CURRENT_TOKEN.with(|current| current.borrow().clone());
+}
+
+pub fn is_cancelled() -> bool {
+    return CURRENT_TOKEN.with(|current| current.borrow().as_ref().is_some_and(|t| t.is_cancelled()));
+}
+
+pub fn check_cancelled() -> Result<(), DeltaError> {
+    if is_cancelled() {
+        return Err(DeltaError::Cancelled);
+    }
+    return Ok(());
+}
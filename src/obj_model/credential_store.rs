@@ -0,0 +1,125 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::delta_error::DeltaError;
+use crate::data_model::node_parameters::NodeParameters;
+use crate::obj_model::checksum::{from_hex, to_hex};
+use crate::obj_model::inventory::{Inventory, ENCRYPTED_PREFIX};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/* Encrypted values read "enc:v1:<hex of nonce and ciphertext>" */
+const NONCE_LEN: usize = 12;
+
+/*
+ * Encrypts credentials in a saved inventory with AES-256-GCM under a master
+ * key. Only the sensitive params are touched; the rest stays readable.
+ */
+#[derive(Clone)]
+pub struct CredentialStore {
+    key: Key<Aes256Gcm>,
+}
+
+impl CredentialStore {
+    pub fn new(key: [u8; 32]) -> CredentialStore {
+        return CredentialStore { key: key.into() };
+    }
+
+    /* The key file holds the master key as 64 hex digits */
+    pub fn from_key_file(path: &Path) -> Result<CredentialStore, DeltaError> {
+        let text = fs::read_to_string(path)?;
+        let key = from_hex(text.trim())
+            .and_then(|k| <[u8; 32]>::try_from(k).ok())
+            .ok_or_else(|| DeltaError::Crypto(format!("{}: expected a 256-bit hex key", path.display())))?;
+        return Ok(CredentialStore::new(key));
+    }
+
+    pub fn generate_key() -> [u8; 32] {
+        return Aes256Gcm::generate_key(&mut OsRng).into();
+    }
+
+    pub fn is_encrypted(value: &str) -> bool {
+        return value.starts_with(ENCRYPTED_PREFIX);
+    }
+
+    pub fn encrypt(&self, value: &str) -> Result<String, DeltaError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let mut data = nonce.to_vec();
+        data.extend(Aes256Gcm::new(&self.key)
+            .encrypt(&nonce, value.as_bytes())
+            .map_err(|_| DeltaError::Crypto("encryption failed".to_string()))?);
+        return Ok(format!("{}{}", ENCRYPTED_PREFIX, to_hex(&data)));
+    }
+
+    pub fn decrypt(&self, value: &str) -> Result<String, DeltaError> {
+        let data = value.strip_prefix(ENCRYPTED_PREFIX)
+            .and_then(from_hex)
+            .filter(|d| d.len() > NONCE_LEN)
+            .ok_or_else(|| DeltaError::Crypto("malformed encrypted value".to_string()))?;
+
+        /* Fails on a wrong key as well as on tampered data */
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let plain = Aes256Gcm::new(&self.key)
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| DeltaError::Crypto("cannot decrypt credential, wrong master key?".to_string()))?;
+        return String::from_utf8(plain).map_err(|_| DeltaError::Crypto("decrypted credential is not UTF-8".to_string()));
+    }
+
+    pub fn seal(&self, inventory: &mut Inventory) -> Result<(), DeltaError> {
+        self.seal_params(&mut inventory.str_params)?;
+        for node in inventory.nodes.values_mut() {
+            self.seal_params(&mut node.str_params)?;
+        }
+        return Ok(());
+    }
+
+    pub fn unseal(&self, inventory: &mut Inventory) -> Result<(), DeltaError> {
+        self.unseal_params(&mut inventory.str_params)?;
+        for node in inventory.nodes.values_mut() {
+            self.unseal_params(&mut node.str_params)?;
+        }
+        return Ok(());
+    }
+
+    fn seal_params(&self, params: &mut HashMap<String, String>) -> Result<(), DeltaError> {
+        for param in &NodeParameters::SENSITIVE {
+            if let Some(value) = params.get_mut(&param.to_string()) {
+                if !CredentialStore::is_encrypted(value) {
+                    *value = self.encrypt(value)?;
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    fn unseal_params(&self, params: &mut HashMap<String, String>) -> Result<(), DeltaError> {
+        for value in params.values_mut().filter(|v| CredentialStore::is_encrypted(v)) {
+            *value = self.decrypt(value)?;
+        }
+        return Ok(());
+    }
+}
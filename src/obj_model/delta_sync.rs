@@ -0,0 +1,156 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::delta_error::DeltaError;
+use crate::data_model::exec_output::ExecOutput;
+use crate::data_model::transfer_method::TransferMethod;
+use crate::obj_model::checksum::{parse_sha256sum, to_hex};
+use crate::obj_model::shell::shell_quote;
+use crate::obj_model::transport::Transport;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use tar::{Archive, EntryType};
+use xz2::read::XzDecoder;
+
+pub struct ArchiveFile {
+    pub checksum: String,
+    pub mode: i32,
+    pub contents: Vec<u8>,
+}
+
+/* Regular files of a .tar.xz archive keyed by their normalized relative path */
+pub fn read_archive(path: &Path) -> io::Result<HashMap<String, ArchiveFile>> {
+    let mut archive = Archive::new(XzDecoder::new(File::open(path)?));
+    let mut files = HashMap::new();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type() != EntryType::Regular {
+            continue;
+        }
+
+        let rel = normalize(&entry.path()?.to_string_lossy());
+        let mode = entry.header().mode().unwrap_or(0o644) as i32;
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents)?;
+
+        let checksum = to_hex(&Sha256::digest(&contents));
+        files.insert(rel, ArchiveFile { checksum, mode, contents });
+    }
+
+    return Ok(files);
+}
+
+/*
+ * Parses checksum tool output for a tree listed relative to its root; the
+ * digest is followed by two spaces (sha256sum, shasum) or one (sha256 -r).
+ */
+pub fn parse_manifest(output: &str) -> HashMap<String, String> {
+    let mut manifest = HashMap::new();
+    for line in output.lines() {
+        let (digest, path) = match line.split_once(' ') {
+            Some((digest, path)) => (digest, path.strip_prefix(' ').unwrap_or(path)),
+            None => continue,
+        };
+
+        if let Some(digest) = parse_sha256sum(digest) {
+            manifest.insert(normalize(path), digest);
+        }
+    }
+
+    return manifest;
+}
+
+/* Paths whose content differs from, or is missing in, the remote tree */
+pub fn changed_files(local: &HashMap<String, ArchiveFile>,
+                     remote: &HashMap<String, String>) -> Vec<String> {
+    let mut changed: Vec<String> = local.iter()
+        .filter(|(path, file)| remote.get(*path) != Some(&file.checksum))
+        .map(|(path, _file)| path.clone())
+        .collect();
+    changed.sort();
+    return changed;
+}
+
+/*
+ * Brings remote_dir in line with the archive by uploading only the files
+ * whose checksum changed. Files that exist only remotely are left alone,
+ * since the tree also holds runtime state. Checksums are taken remotely with
+ * the checksum tool given. Returns the number of files sent.
+ */
+pub fn sync_tree(transport: &dyn Transport, archive: &Path, remote_dir: &str, checksum: &str,
+                 exec: &dyn Fn(String) -> Result<ExecOutput, DeltaError>) -> Result<usize, DeltaError> {
+    let local = read_archive(archive)?;
+
+    let out = exec(format!("cd {} && find . -type f -exec {} {{}} +", shell_quote(remote_dir), checksum))?;
+    if !out.success() {
+        return Err(DeltaError::CommandFailed(
+            format!("failed to list {}: {}", remote_dir, out.stderr.trim())));
+    }
+
+    let remote = parse_manifest(&out.stdout);
+    let changed = changed_files(&local, &remote);
+    if changed.is_empty() {
+        return Ok(0);
+    }
+
+    let dirs: BTreeSet<String> = changed.iter()
+        .filter_map(|p| Path::new(p).parent())
+        .map(|d| d.to_string_lossy().to_string())
+        .filter(|d| !d.is_empty())
+        .collect();
+    if !dirs.is_empty() {
+        let list: Vec<String> = dirs.iter().map(|d| shell_quote(d)).collect();
+        let out = exec(format!("cd {} && mkdir -p {}", shell_quote(remote_dir), list.join(" ")))?;
+        if !out.success() {
+            return Err(DeltaError::CommandFailed(
+                format!("failed to create directories: {}", out.stderr.trim())));
+        }
+    }
+
+    for path in &changed {
+        let file = &local[path];
+        let remote_path = format!("{}/{}", remote_dir, path);
+        transport.upload(&mut &file.contents[..], &remote_path, file.mode, file.contents.len() as u64, 0,
+                         &TransferMethod::Scp, &mut |_| {})?;
+    }
+
+    /* Verify what was sent */
+    let list: Vec<String> = changed.iter().map(|p| shell_quote(p)).collect();
+    let out = exec(format!("cd {} && {} {}", shell_quote(remote_dir), checksum, list.join(" ")))?;
+    let sent = parse_manifest(&out.stdout);
+    let mismatched = changed.iter().any(|p| sent.get(p) != Some(&local[p].checksum));
+    if !out.success() || mismatched {
+        return Err(DeltaError::CommandFailed("checksum mismatch after sync".to_string()));
+    }
+
+    return Ok(changed.len());
+}
+
+fn normalize(path: &str) -> String {
+    return path.trim_start_matches("./").trim_start_matches('/').to_string();
+}
@@ -0,0 +1,173 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::delta_error::DeltaError;
+use crate::data_model::exec_output::ExecOutput;
+use crate::data_model::result::connect_result::ConnectResult;
+use crate::data_model::transfer_method::TransferMethod;
+use crate::obj_model::local_transport::run_process;
+use crate::obj_model::shell::shell_quote;
+use crate::obj_model::stream_reader::LineSink;
+use crate::obj_model::transport::{copy_stream, Transport};
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use tracing::error;
+
+/*
+ * Transport into a container on the pool's machine. Commands go through
+ * "docker exec", files are streamed through cat inside the container, so
+ * the image needs a shell but no sshd.
+ */
+pub struct DockerTransport {
+    container: String,
+    user: String,
+}
+
+impl DockerTransport {
+    pub fn new(container: &str, user: &str) -> DockerTransport {
+        return DockerTransport { container: container.to_string(), user: user.to_string() };
+    }
+
+    /* Transport to a container that has to be running already */
+    pub fn open(container: &str, user: &str) -> Result<DockerTransport, ConnectResult> {
+        if container.is_empty() {
+            error!("No container given");
+            return Err(ConnectResult::InvalidParameter);
+        }
+
+        let transport = DockerTransport::new(container, user);
+        if !transport.is_alive() {
+            error!("Container is not running: {}", container);
+            return Err(ConnectResult::ConnectionFailed);
+        }
+        return Ok(transport);
+    }
+
+    fn exec(&self, interactive: bool) -> Command {
+        let mut command = Command::new("docker");
+        command.arg("exec");
+        if interactive {
+            command.arg("-i");
+        }
+        if !self.user.is_empty() {
+            command.arg("-u").arg(&self.user);
+        }
+        command.arg(&self.container);
+        return command;
+    }
+
+    fn shell(&self, script: &str) -> Command {
+        let mut command = self.exec(false);
+        command.arg("sh").arg("-c").arg(script);
+        return command;
+    }
+}
+
+impl Transport for DockerTransport {
+    fn execute(&self, cmd: &str, timeout: Option<Duration>,
+               sink: Option<&LineSink>) -> Result<ExecOutput, DeltaError> {
+        return run_process(self.shell(cmd), cmd, timeout, sink);
+    }
+
+    fn execute_script(&self, commands: &[String], timeout: Option<Duration>,
+                      sink: Option<&LineSink>) -> Result<ExecOutput, DeltaError> {
+        return run_process(self.shell(&commands.join("\n")), &commands.join("; "), timeout, sink);
+    }
+
+    fn upload(&self, reader: &mut dyn Read, remote_path: &str, mode: i32, _len: u64, offset: u64,
+              _method: &TransferMethod, on_progress: &mut dyn FnMut(u64)) -> Result<(), DeltaError> {
+        /* Like scp, an upload at an offset appends to what is already there */
+        let path = shell_quote(remote_path);
+        let redirect = if offset == 0 { ">" } else { ">>" };
+        let script = format!("cat {} {} && chmod {:o} {}", redirect, path, mode, path);
+        let mut child = self.exec(true)
+            .arg("sh").arg("-c").arg(&script)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let mut stdin = child.stdin.take().ok_or(io::Error::from(io::ErrorKind::BrokenPipe))?;
+        let copied = copy_stream(reader, &mut stdin, on_progress);
+        drop(stdin);
+        let out = child.wait_with_output()?;
+        copied?;
+        if !out.status.success() {
+            return Err(DeltaError::CommandFailed(format!("failed to upload to {}: {}",
+                remote_path, String::from_utf8_lossy(&out.stderr).trim())));
+        }
+
+        return Ok(());
+    }
+
+    fn download(&self, remote_path: &str, writer: &mut dyn Write,
+                _method: &TransferMethod) -> Result<u64, DeltaError> {
+        let mut child = self.exec(false)
+            .arg("cat").arg("--").arg(remote_path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let mut stdout = child.stdout.take().ok_or(io::Error::from(io::ErrorKind::BrokenPipe))?;
+        let copied = copy_stream(&mut stdout, writer, &mut |_| {});
+        drop(stdout);
+        if let Err(e) = copied {
+            /* cat would block on a full pipe nobody reads any more */
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+        let out = child.wait_with_output()?;
+        if !out.status.success() {
+            return Err(DeltaError::CommandFailed(format!("failed to download {}: {}",
+                remote_path, String::from_utf8_lossy(&out.stderr).trim())));
+        }
+
+        return copied;
+    }
+
+    fn file_size(&self, remote_path: &str, _method: &TransferMethod) -> Option<u64> {
+        let out = self.execute(&format!("wc -c < {}", shell_quote(remote_path)), None, None).ok()?;
+        if !out.success() {
+            return None;
+        }
+        return out.stdout.trim().parse::<u64>().ok();
+    }
+
+    fn is_alive(&self) -> bool {
+        let out = Command::new("docker")
+            .arg("inspect").arg("-f").arg("{{.State.Running}}").arg(&self.container)
+            .stdin(Stdio::null())
+            .output();
+        return match out {
+            Ok(out) => out.status.success() && String::from_utf8_lossy(&out.stdout).trim() == "true",
+            Err(e) => {
+                error!("Failed to inspect container {}: {}", self.container, e);
+                false
+            }
+        };
+    }
+}
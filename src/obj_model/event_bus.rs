@@ -0,0 +1,63 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::pool_event::PoolEvent;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+
+/* Delivers an event; false once the subscriber is gone and should be dropped */
+pub type EventSubscriber = Box<dyn Fn(&PoolEvent) -> bool + Send>;
+
+/*
+ * Fans pool events out to subscribers. Clones share the subscriber list,
+ * which is how the per-node pools of SharedNodePool publish to one bus.
+ */
+#[derive(Clone)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<EventSubscriber>>>,
+}
+
+impl EventBus {
+    pub fn new() -> EventBus {
+        return EventBus {
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+        };
+    }
+
+    /* Every event published from now on, until the receiver is dropped */
+    pub fn subscribe(&self) -> Receiver<PoolEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribe_with(Box::new(move |event| tx.send(event.clone()).is_ok()));
+        return rx;
+    }
+
+    pub fn subscribe_with(&self, subscriber: EventSubscriber) {
+        self.subscribers.lock().unwrap_or_else(|e| e.into_inner()).push(subscriber);
+    }
+
+    pub fn publish(&self, event: PoolEvent) {
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        subscribers.retain(|deliver| deliver(&event));
+    }
+}
@@ -0,0 +1,328 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+/* tonic::Status is large, but it is what the service trait returns */
+#![allow(clippy::result_large_err)]
+
+use crate::data_model::delta_error::DeltaError;
+use crate::data_model::deploy_progress::DeployProgress;
+use crate::data_model::deploy_subject::DeploySubject;
+use crate::data_model::exec_output::OutputStream;
+use crate::data_model::log_line::LogLine;
+use crate::data_model::node_summary::NodeSummary;
+use crate::data_model::result::add_result::AddResult;
+use crate::data_model::result::connect_result::ConnectResult;
+use crate::data_model::result::disconnect_result::DisconnectResult;
+use crate::data_model::result::remove_result::RemoveResult;
+use crate::data_model::result::restart_result::RestartResult;
+use crate::data_model::result::run_result::RunResult;
+use crate::data_model::result::stop_result::StopResult;
+use crate::data_model::result::undeploy_result::UndeployResult;
+use crate::data_model::run_options::RunOptions;
+use crate::data_model::run_status::RunStatus;
+use crate::data_model::secret::{register_secret, scrub, Secret};
+use crate::obj_model::async_node_pool::AsyncNodePool;
+use serde::Serialize;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::{Request, Response, Status};
+use tracing::info;
+
+#[allow(clippy::all)]
+pub mod pb {
+    tonic::include_proto!("delta_api");
+}
+
+use pb::node_pool_server::{NodePool as NodePoolService, NodePoolServer};
+
+/* How often Deploy looks for new progress to stream */
+const PROGRESS_POLL: Duration = Duration::from_millis(250);
+/* Updates buffered for a slow Deploy client */
+const DEPLOY_UPDATE_BUFFER: usize = 64;
+
+type ReplyStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+/*
+ * gRPC front end for a pool, as defined in proto/delta_api.proto. As with
+ * the REST server, a token makes every call need "authorization: Bearer
+ * <token>" metadata, and without one serve() only binds loopback addresses.
+ */
+pub struct GrpcServer {
+    pool: AsyncNodePool,
+    token: Option<Secret<String>>,
+}
+
+/* Turns away calls without the server's token; lets everything through when there is none */
+#[derive(Clone)]
+pub struct TokenCheck {
+    token: Option<Secret<String>>,
+}
+
+impl Interceptor for TokenCheck {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        let Some(token) = &self.token else {
+            return Ok(request);
+        };
+        let presented = request.metadata().get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        if !presented.is_some_and(|p| token.matches(p)) {
+            return Err(Status::unauthenticated("unauthorized"));
+        }
+        return Ok(request);
+    }
+}
+
+impl GrpcServer {
+    pub fn new(pool: AsyncNodePool) -> GrpcServer {
+        return GrpcServer { pool, token: None };
+    }
+
+    pub fn with_token(mut self, token: String) -> GrpcServer {
+        register_secret(&token);
+        self.token = Some(Secret::new(token));
+        return self;
+    }
+
+    /* For adding the service to an application's own tonic server; the token check comes along */
+    pub fn into_service(self) -> InterceptedService<NodePoolServer<GrpcServer>, TokenCheck> {
+        let check = TokenCheck { token: self.token.clone() };
+        return NodePoolServer::with_interceptor(self, check);
+    }
+
+    pub async fn serve(self, addr: SocketAddr) -> Result<(), DeltaError> {
+        if self.token.is_none() && !addr.ip().is_loopback() {
+            return Err(DeltaError::InvalidParameter(
+                "addr".to_string(), format!("{} is not loopback and no token is set", addr)));
+        }
+        info!("gRPC server listening on {}", addr);
+        tonic::transport::Server::builder()
+            .add_service(self.into_service())
+            .serve(addr)
+            .await
+            .map_err(io::Error::other)?;
+        return Ok(());
+    }
+}
+
+fn to_status(e: DeltaError) -> Status {
+    let message = scrub(&e.to_string());
+    return match e {
+        DeltaError::NodeNotFound(_) => Status::not_found(message),
+        DeltaError::NodeNotConnected(_) => Status::failed_precondition(message),
+        DeltaError::InvalidParameter(_, _) | DeltaError::Parse(_) => Status::invalid_argument(message),
+        DeltaError::NoFreePort(_) => Status::resource_exhausted(message),
+        DeltaError::Cancelled => Status::cancelled(message),
+        DeltaError::Unsupported(_) => Status::unimplemented(message),
+        e if e.is_timeout() => Status::deadline_exceeded(message),
+        _ => Status::internal(message),
+    };
+}
+
+fn subject_from(value: i32) -> Result<DeploySubject, Status> {
+    return match pb::Subject::try_from(value) {
+        Ok(pb::Subject::Sa) => Ok(DeploySubject::Sa),
+        Ok(pb::Subject::Delta) => Ok(DeploySubject::Delta),
+        Err(_) => Err(Status::invalid_argument(format!("unknown subject: {}", value))),
+    };
+}
+
+fn subject_to(subject: &DeploySubject) -> pb::Subject {
+    return match subject {
+        DeploySubject::Sa => pb::Subject::Sa,
+        DeploySubject::Delta => pb::Subject::Delta,
+    };
+}
+
+fn reply<R: Serialize>(operation_id: String, ok: bool, result: &R) -> pb::OperationReply {
+    return pb::OperationReply {
+        operation_id,
+        ok,
+        result: serde_json::to_string(result).unwrap_or_default(),
+    };
+}
+
+fn summary_to(summary: NodeSummary) -> pb::NodeSummary {
+    return pb::NodeSummary {
+        name: summary.name,
+        fqdn: summary.fqdn,
+        connected: summary.connected,
+        tags: summary.tags,
+        subjects: summary.subjects.iter()
+            .map(|(subject, st)| (subject.to_string(), serde_json::to_string(st).unwrap_or_default()))
+            .collect(),
+        last_error: summary.last_error,
+    };
+}
+
+fn progress_to(progress: &DeployProgress) -> pb::DeployProgress {
+    return pb::DeployProgress {
+        subject: subject_to(&progress.subject) as i32,
+        phase: progress.phase.to_string(),
+        bytes_sent: progress.bytes_sent,
+        bytes_total: progress.bytes_total,
+    };
+}
+
+fn status_to(status: RunStatus) -> pb::RunStatus {
+    return pb::RunStatus {
+        running: status.running,
+        pid: status.pid,
+        bind_addr: status.bind_addr,
+        bind_port: status.bind_port.map(u32::from),
+        started_at: status.started_at,
+        uptime_secs: status.uptime_secs,
+    };
+}
+
+fn log_line_to(line: LogLine) -> pb::LogLine {
+    let stream = match line.stream {
+        OutputStream::Stdout => pb::OutputStream::Stdout,
+        OutputStream::Stderr => pb::OutputStream::Stderr,
+    };
+    return pb::LogLine { stream: stream as i32, text: line.text };
+}
+
+#[tonic::async_trait]
+impl NodePoolService for GrpcServer {
+    type DeployStream = ReplyStream<pb::DeployUpdate>;
+    type TailLogsStream = ReplyStream<pb::LogLine>;
+
+    async fn list(&self, _request: Request<pb::ListRequest>) -> Result<Response<pb::ListReply>, Status> {
+        let nodes = self.pool.list().await.into_iter().map(summary_to).collect();
+        return Ok(Response::new(pb::ListReply { nodes }));
+    }
+
+    async fn add(&self, request: Request<pb::AddRequest>) -> Result<Response<pb::OperationReply>, Status> {
+        let req = request.into_inner();
+        let result = self.pool.add(req.name, req.fqdn, req.params).await;
+        return Ok(Response::new(reply(String::new(), result == AddResult::Ok, &result)));
+    }
+
+    async fn remove(&self, request: Request<pb::NodeRequest>) -> Result<Response<pb::OperationReply>, Status> {
+        let result = self.pool.remove(request.into_inner().name).await;
+        return Ok(Response::new(reply(String::new(), result == RemoveResult::Ok, &result)));
+    }
+
+    async fn connect(&self, request: Request<pb::NodeRequest>) -> Result<Response<pb::OperationReply>, Status> {
+        let op = self.pool.connect_tracked(request.into_inner().name).await;
+        return Ok(Response::new(reply(op.operation_id, op.result == ConnectResult::Ok, &op.result)));
+    }
+
+    async fn disconnect(&self, request: Request<pb::NodeRequest>) -> Result<Response<pb::OperationReply>, Status> {
+        let result = self.pool.disconnect(request.into_inner().name).await;
+        return Ok(Response::new(reply(String::new(), result == DisconnectResult::Ok, &result)));
+    }
+
+    async fn deploy(&self, request: Request<pb::SubjectRequest>) -> Result<Response<Self::DeployStream>, Status> {
+        let req = request.into_inner();
+        let subject = subject_from(req.subject)?;
+        let pool = self.pool.clone();
+        let (tx, rx) = mpsc::channel(DEPLOY_UPDATE_BUFFER);
+
+        tokio::spawn(async move {
+            /* Progress left over from an earlier deployment isn't news */
+            let mut last = pool.get_deploy_progress(req.name.clone()).await;
+            let deploy = pool.deploy_tracked(req.name.clone(), subject);
+            tokio::pin!(deploy);
+            loop {
+                tokio::select! {
+                    op = &mut deploy => {
+                        let finished = reply(op.operation_id, op.result.is_ok(), &op.result);
+                        let update = pb::DeployUpdate { update: Some(pb::deploy_update::Update::Finished(finished)) };
+                        let _ = tx.send(Ok(update)).await;
+                        return;
+                    }
+                    _ = tokio::time::sleep(PROGRESS_POLL) => {
+                        let progress = pool.get_deploy_progress(req.name.clone()).await;
+                        if progress == last {
+                            continue;
+                        }
+                        if let Some(p) = &progress {
+                            let update = pb::DeployUpdate { update: Some(pb::deploy_update::Update::Progress(progress_to(p))) };
+                            if tx.send(Ok(update)).await.is_err() {
+                                return;
+                            }
+                        }
+                        last = progress;
+                    }
+                }
+            }
+        });
+
+        return Ok(Response::new(Box::pin(ReceiverStream::new(rx))));
+    }
+
+    async fn run(&self, request: Request<pb::RunRequest>) -> Result<Response<pb::OperationReply>, Status> {
+        let req = request.into_inner();
+        let subject = subject_from(req.subject)?;
+        let options = RunOptions {
+            extra_args: req.extra_args,
+            env: req.env,
+            working_dir: req.working_dir,
+        };
+        let op = self.pool.run_tracked(req.name, subject, options).await;
+        return Ok(Response::new(reply(op.operation_id, op.result == RunResult::Ok, &op.result)));
+    }
+
+    async fn stop(&self, request: Request<pb::SubjectRequest>) -> Result<Response<pb::OperationReply>, Status> {
+        let req = request.into_inner();
+        let result = self.pool.stop(req.name, subject_from(req.subject)?).await;
+        let ok = matches!(result, StopResult::Ok(_) | StopResult::NotRunning);
+        return Ok(Response::new(reply(String::new(), ok, &result)));
+    }
+
+    async fn restart(&self, request: Request<pb::SubjectRequest>) -> Result<Response<pb::OperationReply>, Status> {
+        let req = request.into_inner();
+        let result = self.pool.restart(req.name, subject_from(req.subject)?).await;
+        let ok = matches!(result, RestartResult::Ok { .. });
+        return Ok(Response::new(reply(String::new(), ok, &result)));
+    }
+
+    async fn undeploy(&self, request: Request<pb::SubjectRequest>) -> Result<Response<pb::OperationReply>, Status> {
+        let req = request.into_inner();
+        let result = self.pool.undeploy(req.name, subject_from(req.subject)?).await;
+        return Ok(Response::new(reply(String::new(), result == UndeployResult::Ok, &result)));
+    }
+
+    async fn status(&self, request: Request<pb::SubjectRequest>) -> Result<Response<pb::RunStatus>, Status> {
+        let req = request.into_inner();
+        let status = self.pool.status(req.name, subject_from(req.subject)?).await.map_err(to_status)?;
+        return Ok(Response::new(status_to(status)));
+    }
+
+    async fn tail_logs(&self, request: Request<pb::SubjectRequest>) -> Result<Response<Self::TailLogsStream>, Status> {
+        let req = request.into_inner();
+        let lines = self.pool.stream_logs(req.name, subject_from(req.subject)?).await.map_err(to_status)?;
+        let stream = ReceiverStream::new(lines).map(|line| line.map(log_line_to).map_err(to_status));
+        return Ok(Response::new(Box::pin(stream)));
+    }
+}
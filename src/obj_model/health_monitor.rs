@@ -0,0 +1,139 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::conn_alive_status::ConnAliveStatus;
+use crate::data_model::health_event::HealthEvent;
+use crate::data_model::pool_event::PoolEvent;
+use crate::obj_model::shared_node_pool::SharedNodePool;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+pub type HealthCallback = Arc<dyn Fn(&HealthEvent) + Send + Sync>;
+
+type HealthCache = Arc<Mutex<HashMap<String, ConnAliveStatus>>>;
+
+/*
+ * Background thread checking every connected node with is_alive() each
+ * interval. The latest results are cached for cheap reads, and changes of
+ * an instance's alive state are passed to the callback and the pool's
+ * event bus. Like Supervisor it
+ * ends with the pool, on stop() or on drop.
+ */
+pub struct HealthMonitor {
+    cache: HealthCache,
+    stopped: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl HealthMonitor {
+    pub fn start(pool: &Arc<SharedNodePool>, interval: Duration,
+                 callback: Option<HealthCallback>) -> HealthMonitor {
+        let pool = Arc::downgrade(pool);
+        let cache: HealthCache = Arc::new(Mutex::new(HashMap::new()));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let (thread_cache, flag) = (cache.clone(), stopped.clone());
+
+        let handle = thread::spawn(move || {
+            while !flag.load(Ordering::SeqCst) {
+                let Some(pool) = pool.upgrade() else {
+                    break;
+                };
+
+                let names = pool.names();
+                let results = pool.is_alive_all();
+                for event in HealthMonitor::update(&thread_cache, &names, results) {
+                    let (node, subject) = (event.node.clone(), event.subject.clone());
+                    pool.event_bus().publish(match event.alive {
+                        true => PoolEvent::InstanceUp { node, subject },
+                        false => PoolEvent::InstanceDied { node, subject },
+                    });
+                    if let Some(callback) = &callback {
+                        callback(&event);
+                    }
+                }
+                drop(pool);
+
+                thread::park_timeout(interval);
+            }
+        });
+
+        return HealthMonitor { cache, stopped, handle: Some(handle) };
+    }
+
+    /* Last result for the node, None until it was checked while connected */
+    pub fn status(&self, name: &str) -> Option<ConnAliveStatus> {
+        return self.cache.lock().unwrap_or_else(|e| e.into_inner()).get(name).cloned();
+    }
+
+    pub fn statuses(&self) -> HashMap<String, ConnAliveStatus> {
+        return self.cache.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    }
+
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    /* Stores the results and returns the transitions; nodes no longer in the pool are dropped */
+    fn update(cache: &HealthCache, names: &[String],
+              results: HashMap<String, ConnAliveStatus>) -> Vec<HealthEvent> {
+        let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache.retain(|name, _| names.contains(name));
+
+        let mut events = Vec::new();
+        for (name, status) in results {
+            for (subject, subject_status) in &status.subjects {
+                let was_alive = cache.get(&name)
+                    .and_then(|prev| prev.subjects.get(subject))
+                    .is_some_and(|prev| prev.alive);
+                if was_alive != subject_status.alive {
+                    events.push(HealthEvent {
+                        node: name.clone(),
+                        subject: subject.clone(),
+                        alive: subject_status.alive,
+                        checked_at: status.last_checked,
+                    });
+                }
+            }
+            cache.insert(name, status);
+        }
+        return events;
+    }
+
+    fn shutdown(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for HealthMonitor {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
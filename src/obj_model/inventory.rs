@@ -0,0 +1,116 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::delta_error::DeltaError;
+#[cfg(feature = "inventory")]
+use crate::data_model::inventory_spec::InventorySpec;
+use crate::data_model::pool_limits::PoolLimits;
+use crate::data_model::retry_policy::RetryPolicy;
+use crate::data_model::secret::redact_params;
+use crate::obj_model::node::Node;
+#[cfg(feature = "inventory")]
+use crate::obj_model::tag_expr::is_valid_tag;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/* Marks a param encrypted by a CredentialStore */
+pub const ENCRYPTED_PREFIX: &str = "enc:v1:";
+
+/*
+ * On-disk form of a pool: nodes with their params and tags, plus pool-wide
+ * params. Sessions and runtime state are not part of it. Params are stored
+ * as is, passwords included, unless sealed with a CredentialStore.
+ */
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct Inventory {
+    #[serde(default)]
+    pub str_params: HashMap<String, String>,
+    #[serde(default)]
+    pub retry_policy: Option<RetryPolicy>,
+    #[serde(default)]
+    pub limits: Option<PoolLimits>,
+    #[serde(default)]
+    pub nodes: BTreeMap<String, Node>,
+}
+
+impl fmt::Debug for Inventory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.debug_struct("Inventory")
+            .field("str_params", &redact_params(&self.str_params))
+            .field("retry_policy", &self.retry_policy)
+            .field("limits", &self.limits)
+            .field("nodes", &self.nodes)
+            .finish();
+    }
+}
+
+impl Inventory {
+    pub fn read(path: &Path) -> Result<Inventory, DeltaError> {
+        let data = fs::read(path)?;
+        return Ok(serde_json::from_slice(&data)?);
+    }
+
+    pub fn is_sealed(&self) -> bool {
+        return self.str_params.values()
+            .chain(self.nodes.values().flat_map(|n| n.str_params.values()))
+            .any(|v| v.starts_with(ENCRYPTED_PREFIX));
+    }
+
+    /* Written next to the target and renamed, so a crash never leaves half a file */
+    pub fn write(&self, path: &Path) -> Result<(), DeltaError> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&tmp, path)?;
+        return Ok(());
+    }
+}
+
+/* Reads and validates a native YAML inventory, see InventorySpec */
+#[cfg(feature = "inventory")]
+pub fn read_inventory_spec(path: &Path) -> Result<InventorySpec, DeltaError> {
+    let text = fs::read_to_string(path)?;
+    let spec: InventorySpec = serde_yaml::from_str(&text)
+        .map_err(|e| DeltaError::Parse(format!("{}: {}", path.display(), e)))?;
+
+    let mut errors = spec.validate();
+    for name in spec.groups.keys().filter(|g| !is_valid_tag(g)) {
+        errors.push(format!("groups.{}: group names are used as tags and must be valid ones", name));
+    }
+    for (name, node) in &spec.nodes {
+        for tag in node.tags.iter().filter(|t| !is_valid_tag(t)) {
+            errors.push(format!("nodes.{}.tags: invalid tag '{}'", name, tag));
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(DeltaError::Parse(format!("{}: {}", path.display(), errors.join("; "))));
+    }
+
+    return Ok(spec);
+}
@@ -0,0 +1,243 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::job_status::JobStatus;
+use crate::obj_model::cancellation::{CancelScope, CancellationToken};
+use crate::obj_model::operation::OperationScope;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+use tracing::error;
+use uuid::Uuid;
+
+/* Finished jobs whose status can still be looked up by id */
+const FINISHED_JOBS_KEPT: usize = 256;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/*
+ * Runs submitted operations on a fixed number of worker threads, so long
+ * deploys can go on in the background. Each job runs under an operation
+ * scope with the job's id, so logs and audit records carry it.
+ */
+pub struct JobQueue {
+    sender: Sender<Job>,
+    statuses: Arc<Mutex<JobStatuses>>,
+}
+
+struct JobStatuses {
+    by_id: HashMap<String, JobStatus>,
+    finished: VecDeque<String>,
+}
+
+impl JobStatuses {
+    fn set(&mut self, id: &str, status: JobStatus) {
+        let done = matches!(status, JobStatus::Finished | JobStatus::Panicked);
+        self.by_id.insert(id.to_string(), status);
+        if done {
+            self.finished.push_back(id.to_string());
+            while self.finished.len() > FINISHED_JOBS_KEPT {
+                if let Some(old) = self.finished.pop_front() {
+                    self.by_id.remove(&old);
+                }
+            }
+        }
+    }
+}
+
+impl JobQueue {
+    pub fn new(workers: usize) -> JobQueue {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..workers.max(1) {
+            let receiver = receiver.clone();
+            thread::spawn(move || JobQueue::work(&receiver));
+        }
+
+        let statuses = JobStatuses { by_id: HashMap::new(), finished: VecDeque::new() };
+        return JobQueue { sender, statuses: Arc::new(Mutex::new(statuses)) };
+    }
+
+    /* Queues op; it starts as soon as a worker is free */
+    pub fn submit<R, F>(&self, op: F) -> JobHandle<R>
+    where
+        R: Send + 'static,
+        F: FnOnce() -> R + Send + 'static,
+    {
+        let id = Uuid::new_v4().to_string();
+        let token = CancellationToken::new();
+        let shared = Arc::new(JobShared {
+            slot: Mutex::new(JobSlot { status: JobStatus::Queued, result: None, waker: None }),
+            done: Condvar::new(),
+        });
+        self.statuses.lock().unwrap_or_else(|e| e.into_inner()).set(&id, JobStatus::Queued);
+
+        let (job_id, job_shared, statuses, job_token) = (id.clone(), shared.clone(), self.statuses.clone(), token.clone());
+        let job: Job = Box::new(move || {
+            job_shared.update(&job_id, &statuses, JobStatus::Running, None);
+            let result = {
+                let _op = OperationScope::enter_as(&job_id);
+                let _cancel = CancelScope::enter(&job_token);
+                panic::catch_unwind(AssertUnwindSafe(op))
+            };
+            match result {
+                Ok(r) => job_shared.update(&job_id, &statuses, JobStatus::Finished, Some(r)),
+                Err(_e) => {
+                    error!("Job panicked: {}", job_id);
+                    job_shared.update(&job_id, &statuses, JobStatus::Panicked, None);
+                }
+            }
+        });
+
+        if self.sender.send(job).is_err() {
+            /* Only when every worker is gone, which takes a panic outside any job */
+            error!("No workers left for job: {}", id);
+            shared.update(&id, &self.statuses, JobStatus::Panicked, None);
+        }
+        return JobHandle { id, shared, token };
+    }
+
+    /* Status of a job still queued, running or among the recently finished */
+    pub fn status(&self, id: &str) -> Option<JobStatus> {
+        return self.statuses.lock().unwrap_or_else(|e| e.into_inner()).by_id.get(id).cloned();
+    }
+
+    fn work(receiver: &Mutex<Receiver<Job>>) {
+        loop {
+            let job = match receiver.lock().unwrap_or_else(|e| e.into_inner()).recv() {
+                Ok(job) => job,
+                /* The queue was dropped */
+                Err(_e) => return,
+            };
+            job();
+        }
+    }
+}
+
+struct JobSlot<R> {
+    status: JobStatus,
+    result: Option<R>,
+    waker: Option<Waker>,
+}
+
+struct JobShared<R> {
+    slot: Mutex<JobSlot<R>>,
+    done: Condvar,
+}
+
+impl<R> JobShared<R> {
+    fn update(&self, id: &str, statuses: &Mutex<JobStatuses>, status: JobStatus, result: Option<R>) {
+        statuses.lock().unwrap_or_else(|e| e.into_inner()).set(id, status.clone());
+        let mut slot = self.slot.lock().unwrap_or_else(|e| e.into_inner());
+        slot.status = status;
+        slot.result = result;
+        if slot.is_done() {
+            self.done.notify_all();
+            if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<R> JobSlot<R> {
+    fn is_done(&self) -> bool {
+        return matches!(self.status, JobStatus::Finished | JobStatus::Panicked);
+    }
+}
+
+/*
+ * A submitted job: its id, its status, and its result once it is done,
+ * either blocking in wait() or by awaiting the handle. The result is None
+ * when the operation panicked.
+ */
+pub struct JobHandle<R> {
+    id: String,
+    shared: Arc<JobShared<R>>,
+    token: CancellationToken,
+}
+
+impl<R> JobHandle<R> {
+    pub fn id(&self) -> &str {
+        return &self.id;
+    }
+
+    pub fn status(&self) -> JobStatus {
+        return self.shared.slot.lock().unwrap_or_else(|e| e.into_inner()).status.clone();
+    }
+
+    /*
+     * Asks the job to stop: deploys and runs end with a Cancelled result;
+     * a job still queued runs with its token already cancelled.
+     */
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_done(&self) -> bool {
+        return self.shared.slot.lock().unwrap_or_else(|e| e.into_inner()).is_done();
+    }
+
+    pub fn wait(self) -> Option<R> {
+        let mut slot = self.shared.slot.lock().unwrap_or_else(|e| e.into_inner());
+        while !slot.is_done() {
+            slot = self.shared.done.wait(slot).unwrap_or_else(|e| e.into_inner());
+        }
+        return slot.result.take();
+    }
+
+    /* Like wait(), giving the handle back if the job isn't done in time */
+    pub fn wait_timeout(self, timeout: Duration) -> Result<Option<R>, JobHandle<R>> {
+        let result = {
+            let slot = self.shared.slot.lock().unwrap_or_else(|e| e.into_inner());
+            let (mut slot, _timed_out) = self.shared.done.wait_timeout_while(slot, timeout, |s| !s.is_done())
+                .unwrap_or_else(|e| e.into_inner());
+            match slot.is_done() {
+                true => Some(slot.result.take()),
+                false => None,
+            }
+        };
+        return result.ok_or(self);
+    }
+}
+
+impl<R> Future for JobHandle<R> {
+    type Output = Option<R>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<R>> {
+        let mut slot = self.shared.slot.lock().unwrap_or_else(|e| e.into_inner());
+        if slot.is_done() {
+            return Poll::Ready(slot.result.take());
+        }
+
+        slot.waker = Some(cx.waker().clone());
+        return Poll::Pending;
+    }
+}
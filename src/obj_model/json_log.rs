@@ -0,0 +1,155 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use serde_json::{Map, Value};
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::SetGlobalDefaultError;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{Layer, Registry};
+
+/* Spans opened by this crate; the outermost one names the operation */
+const CRATE_TARGET: &str = "delta_api";
+
+/*
+ * Writes every log record as one line of JSON. The fields of the enclosing
+ * spans are flattened into the record, so node, subject and op_id come with
+ * each line; operation, phase and outcome are always present, null when
+ * they don't apply.
+ */
+pub struct JsonLogLayer {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+struct SpanFields(Map<String, Value>);
+
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().to_string(), Value::from(format!("{:?}", value)));
+    }
+}
+
+impl JsonLogLayer {
+    pub fn new<W: Write + Send + 'static>(writer: W) -> JsonLogLayer {
+        return JsonLogLayer { writer: Mutex::new(Box::new(writer)) };
+    }
+
+    pub fn stderr() -> JsonLogLayer {
+        return JsonLogLayer::new(io::stderr());
+    }
+}
+
+impl<S> Layer<S> for JsonLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let mut fields = Map::new();
+        attrs.record(&mut JsonVisitor(&mut fields));
+        span.extensions_mut().insert(SpanFields(fields));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let mut extensions = span.extensions_mut();
+        if let Some(fields) = extensions.get_mut::<SpanFields>() {
+            values.record(&mut JsonVisitor(&mut fields.0));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let meta = event.metadata();
+        let mut record = Map::new();
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+        record.insert("timestamp".to_string(), Value::from(timestamp));
+        record.insert("level".to_string(), Value::from(meta.level().as_str()));
+        record.insert("target".to_string(), Value::from(meta.target()));
+        for key in ["node", "operation", "phase", "outcome"] {
+            record.insert(key.to_string(), Value::Null);
+        }
+
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if record["operation"].is_null() && span.metadata().target().starts_with(CRATE_TARGET) {
+                    record.insert("operation".to_string(), Value::from(span.name()));
+                }
+                if let Some(fields) = span.extensions().get::<SpanFields>() {
+                    record.extend(fields.0.clone());
+                }
+            }
+        }
+
+        event.record(&mut JsonVisitor(&mut record));
+
+        let mut line = match serde_json::to_vec(&record) {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        line.push(b'\n');
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writer.write_all(&line);
+    }
+}
+
+/* Installs JSON logging to stderr as the global subscriber, for applications without one */
+pub fn init_json_logging(level: Level) -> Result<(), SetGlobalDefaultError> {
+    let subscriber = Registry::default().with(JsonLogLayer::stderr().with_filter(LevelFilter::from_level(level)));
+    return tracing::subscriber::set_global_default(subscriber);
+}
@@ -0,0 +1,275 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::secret::Secret;
+use crate::obj_model::known_hosts::*;
+use crate::obj_model::net::*;
+use tracing::error;
+use tracing::info;
+use ssh2::{Channel, Session};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::thread;
+use std::time::Duration;
+
+pub struct JumpHost {
+    pub fqdn: String,
+    pub username: String,
+    pub password: Secret<String>,
+    pub known_hosts: KnownHosts,
+    /* StrictHostKeyChecking: refuse a bastion whose key isn't known */
+    pub strict: bool,
+    pub connect_timeout: Duration,
+    pub handshake_timeout: Duration,
+}
+
+impl JumpHost {
+    /*
+     * Connects to the bastion, opens a direct-tcpip channel to the target and
+     * exposes it as a local stream suitable for Session::set_tcp_stream.
+     */
+    pub fn open_tunnel(&self, target_host: &str, target_port: u16) -> Option<TunnelStream> {
+        let sess = self.connect()?;
+
+        let channel = match sess.channel_direct_tcpip(target_host, target_port, None) {
+            Ok(c) => c,
+            Err(e) => {
+                error!("Jump host {} failed to open channel to {}: {}",
+                       self.fqdn, format_host_port(target_host, target_port), e);
+                return None;
+            }
+        };
+
+        let stream = session_tunnel(sess, channel)?;
+        info!("Tunnel to {} via {} opened", format_host_port(target_host, target_port), self.fqdn);
+        return Some(stream);
+    }
+
+    fn connect(&self) -> Option<Session> {
+        let (host, port) = match parse_host_port(&self.fqdn) {
+            Ok((host, port)) => (host, port.unwrap_or(DEFAULT_SSH_PORT)),
+            Err(e) => {
+                error!("Invalid jump host address: {}", e);
+                return None;
+            }
+        };
+
+        let tcp = match connect_tcp(&host, port, self.connect_timeout) {
+            Ok(t) => t,
+            Err(e) => {
+                error!("Failed to connect to jump host {}: {}", self.fqdn, e);
+                return None;
+            }
+        };
+
+        let mut sess = match Session::new() {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Failed to create session: {}", e);
+                return None;
+            }
+        };
+        sess.set_tcp_stream(tcp);
+        sess.set_timeout(self.handshake_timeout.as_millis() as u32);
+        if let Err(e) = sess.handshake() {
+            error!("Handshake with jump host {} failed: {}", self.fqdn, e);
+            return None;
+        }
+        sess.set_timeout(0);
+
+        match self.known_hosts.verify(&sess, &host, port) {
+            HostKeyCheck::Match => {}
+            HostKeyCheck::NotFound => {
+                if self.strict {
+                    error!("Jump host key unknown: {} ({})", self.fqdn, KnownHosts::fingerprint(&sess));
+                    return None;
+                }
+                info!("Jump host key not in known hosts, accepting: {} ({})",
+                      self.fqdn, KnownHosts::fingerprint(&sess));
+            }
+            HostKeyCheck::Mismatch | HostKeyCheck::Failure => {
+                error!("Jump host key verification failed: {} ({})",
+                       self.fqdn, KnownHosts::fingerprint(&sess));
+                return None;
+            }
+        }
+
+        if let Err(e) = sess.userauth_password(&self.username, self.password.expose()) {
+            error!("Jump host credentials not accepted: {} (error '{}')", self.fqdn, e);
+            return None;
+        }
+
+        if !sess.authenticated() {
+            error!("Failed to authenticate on jump host: {}", self.fqdn);
+            return None;
+        }
+
+        return Some(sess);
+    }
+}
+
+/* What open_tunnel() hands to the node's session */
+#[cfg(unix)]
+pub type TunnelStream = UnixStream;
+#[cfg(not(unix))]
+pub type TunnelStream = TcpStream;
+
+/* A socket pair where there is one: no listener another local process could connect to first */
+#[cfg(unix)]
+fn session_tunnel(sess: Session, channel: Channel) -> Option<UnixStream> {
+    let (ours, theirs) = match UnixStream::pair() {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Failed to create tunnel socket pair: {}", e);
+            return None;
+        }
+    };
+    if theirs.set_nonblocking(true).is_err() {
+        return None;
+    }
+
+    thread::spawn(move || pump(sess, channel, theirs));
+    return Some(ours);
+}
+
+#[cfg(not(unix))]
+fn session_tunnel(sess: Session, channel: Channel) -> Option<TcpStream> {
+    return local_tunnel(sess, channel);
+}
+
+/*
+ * Loopback TCP stream whose bytes travel over the channel, pumped by a
+ * thread owning the session. Only the connection made here gets the
+ * channel: others reaching the listener first are turned away.
+ */
+pub fn local_tunnel(sess: Session, channel: Channel) -> Option<TcpStream> {
+    let listener = match TcpListener::bind("127.0.0.1:0") {
+        Ok(l) => l,
+        Err(e) => {
+            error!("Failed to bind tunnel listener: {}", e);
+            return None;
+        }
+    };
+
+    let local_addr = match listener.local_addr() {
+        Ok(a) => a,
+        Err(e) => {
+            error!("Failed to get tunnel listener address: {}", e);
+            return None;
+        }
+    };
+
+    let client = match TcpStream::connect(local_addr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to connect to tunnel listener: {}", e);
+            return None;
+        }
+    };
+    let client_addr = match client.local_addr() {
+        Ok(a) => a,
+        Err(e) => {
+            error!("Failed to get tunnel client address: {}", e);
+            return None;
+        }
+    };
+
+    thread::spawn(move || {
+        loop {
+            match listener.accept() {
+                Ok((stream, addr)) if addr == client_addr => {
+                    if stream.set_nonblocking(true).is_ok() {
+                        pump(sess, channel, stream);
+                    }
+                    return;
+                }
+                Ok((_stream, addr)) => error!("Refused tunnel connection from {}", addr),
+                Err(_e) => return,
+            }
+        }
+    });
+
+    return Some(client);
+}
+
+/* Shuffles bytes between the local end of the tunnel, already non-blocking, and the remote channel */
+fn pump<S: Read + Write>(sess: Session, mut channel: Channel, mut stream: S) {
+    sess.set_blocking(false);
+
+    let mut buffer = vec![0; 16384];
+    loop {
+        let mut idle = true;
+
+        match stream.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => {
+                if !write_all(&mut channel, &buffer[..n]) {
+                    break;
+                }
+                idle = false;
+            }
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(_e) => break,
+        }
+
+        match channel.read(&mut buffer) {
+            Ok(0) => {
+                if channel.eof() {
+                    break;
+                }
+            }
+            Ok(n) => {
+                if !write_all(&mut stream, &buffer[..n]) {
+                    break;
+                }
+                idle = false;
+            }
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(_e) => break,
+        }
+
+        if idle {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    let _ = channel.close();
+}
+
+pub fn write_all<W: Write>(w: &mut W, mut data: &[u8]) -> bool {
+    while !data.is_empty() {
+        match w.write(data) {
+            Ok(0) => return false,
+            Ok(n) => data = &data[n..],
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(1));
+            }
+            Err(_e) => return false,
+        }
+    }
+
+    return true;
+}
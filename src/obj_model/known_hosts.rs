@@ -0,0 +1,101 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use tracing::error;
+use ssh2::{CheckResult, HashType, KnownHostFileKind, Session};
+use std::env;
+use std::path::PathBuf;
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum HostKeyCheck {
+    Match,
+    Mismatch,
+    NotFound,
+    Failure,
+}
+
+pub struct KnownHosts {
+    pub path: PathBuf,
+}
+
+impl KnownHosts {
+    pub fn new(path: PathBuf) -> KnownHosts {
+        return KnownHosts { path };
+    }
+
+    /* Empty path selects the user's OpenSSH known_hosts file */
+    pub fn from_param(path: String) -> KnownHosts {
+        if path.is_empty() {
+            return KnownHosts::new(KnownHosts::default_path());
+        }
+
+        return KnownHosts::new(PathBuf::from(path));
+    }
+
+    pub fn default_path() -> PathBuf {
+        let home = env::var("HOME").unwrap_or_default();
+        return PathBuf::from(home).join(".ssh").join("known_hosts");
+    }
+
+    pub fn verify(&self, sess: &Session, host: &str, port: u16) -> HostKeyCheck {
+        let (key, _key_type) = match sess.host_key() {
+            Some(k) => k,
+            None => {
+                error!("Server didn't provide a host key: {}", host);
+                return HostKeyCheck::Failure;
+            }
+        };
+
+        let mut known_hosts = match sess.known_hosts() {
+            Ok(k) => k,
+            Err(e) => {
+                error!("Failed to initialize known hosts: {}", e);
+                return HostKeyCheck::Failure;
+            }
+        };
+
+        if self.path.exists() {
+            if let Err(e) = known_hosts.read_file(&self.path, KnownHostFileKind::OpenSSH) {
+                error!("Failed to read {}: {}", self.path.display(), e);
+                return HostKeyCheck::Failure;
+            }
+        }
+
+        return match known_hosts.check_port(host, port, key) {
+            CheckResult::Match => HostKeyCheck::Match,
+            CheckResult::Mismatch => HostKeyCheck::Mismatch,
+            CheckResult::NotFound => HostKeyCheck::NotFound,
+            CheckResult::Failure => HostKeyCheck::Failure,
+        };
+    }
+
+    pub fn fingerprint(sess: &Session) -> String {
+        let hash = match sess.host_key_hash(HashType::Sha256) {
+            Some(h) => h,
+            None => return "".to_string(),
+        };
+
+        return hash.iter().map(|b| format!("{:02x}", b)).collect::<Vec<String>>().join(":");
+    }
+}
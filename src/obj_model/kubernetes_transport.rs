@@ -0,0 +1,302 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::delta_error::DeltaError;
+use crate::data_model::exec_output::{ExecOutput, OutputStream};
+use crate::data_model::result::connect_result::ConnectResult;
+use crate::data_model::secret::scrub;
+use crate::data_model::transfer_method::TransferMethod;
+use crate::obj_model::cancellation::{current_token, CancellationToken};
+use crate::obj_model::shell::shell_quote;
+use crate::obj_model::stream_reader::LineSink;
+use crate::obj_model::transport::Transport;
+use k8s_openapi::api::core::v1::Pod;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Status;
+use kube::api::{AttachParams, AttachedProcess};
+use kube::{Api, Client};
+use std::future::Future;
+use std::io::{Read, Write};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::runtime::{Builder, Runtime};
+use tracing::{debug_span, error};
+
+/* How often a running call looks at its cancellation token */
+const CANCEL_POLL_MS: u64 = 100;
+
+/*
+ * Transport into a pod through the Kubernetes API: commands are pod exec
+ * calls, files are streamed through cat in the container the way kubectl cp
+ * does it. The client is configured from KUBECONFIG or the in-cluster
+ * service account. Calls block on a runtime of the transport's own.
+ */
+pub struct KubernetesTransport {
+    runtime: Runtime,
+    pods: Api<Pod>,
+    pod: String,
+    container: String,
+}
+
+impl KubernetesTransport {
+    /* Transport to a pod that has to be running already; an empty container picks the pod's default */
+    pub fn open(namespace: &str, pod: &str, container: &str) -> Result<KubernetesTransport, ConnectResult> {
+        if pod.is_empty() {
+            error!("No pod given");
+            return Err(ConnectResult::InvalidParameter);
+        }
+
+        let runtime = match Builder::new_current_thread().enable_all().build() {
+            Ok(r) => r,
+            Err(e) => {
+                error!("Failed to start the Kubernetes client runtime: {}", e);
+                return Err(ConnectResult::ConnectionFailed);
+            }
+        };
+        let client = match runtime.block_on(Client::try_default()) {
+            Ok(c) => c,
+            Err(e) => {
+                error!("Failed to configure the Kubernetes client: {}", e);
+                return Err(ConnectResult::NotAuthenticated);
+            }
+        };
+        let pods = match namespace.is_empty() {
+            true => Api::default_namespaced(client),
+            false => Api::namespaced(client, namespace),
+        };
+
+        let transport = KubernetesTransport {
+            runtime,
+            pods,
+            pod: pod.to_string(),
+            container: container.to_string(),
+        };
+        if !transport.is_alive() {
+            error!("Pod is not running: {}", pod);
+            return Err(ConnectResult::ConnectionFailed);
+        }
+        return Ok(transport);
+    }
+
+    fn params(&self, stdin: bool) -> AttachParams {
+        let params = AttachParams::default().stdin(stdin);
+        return match self.container.is_empty() {
+            true => params,
+            false => params.container(&self.container),
+        };
+    }
+
+    async fn start(&self, script: &str, stdin: bool) -> Result<AttachedProcess, DeltaError> {
+        return self.pods.exec(&self.pod, vec!["sh", "-c", script], &self.params(stdin)).await
+            .map_err(KubernetesTransport::api_error);
+    }
+
+    fn run(&self, what: &str, script: &str, timeout: Option<Duration>,
+           sink: Option<&LineSink>) -> Result<ExecOutput, DeltaError> {
+        let span = debug_span!("command", cmd = %scrub(what));
+        let _entered = span.enter();
+        let task = async {
+            let mut process = self.start(script, false).await?;
+            let mut output = ExecOutput::new();
+            let mut stdout = process.stdout().map(|r| BufReader::new(r).lines());
+            let mut stderr = process.stderr().map(|r| BufReader::new(r).lines());
+            while stdout.is_some() || stderr.is_some() {
+                let (stream, line) = tokio::select! {
+                    line = async { stdout.as_mut()?.next_line().await.transpose() }, if stdout.is_some() => {
+                        if line.is_none() {
+                            stdout = None;
+                        }
+                        (OutputStream::Stdout, line)
+                    }
+                    line = async { stderr.as_mut()?.next_line().await.transpose() }, if stderr.is_some() => {
+                        if line.is_none() {
+                            stderr = None;
+                        }
+                        (OutputStream::Stderr, line)
+                    }
+                };
+                let Some(line) = line.transpose()? else { continue };
+                if let Some(sink) = sink {
+                    sink(stream.clone(), &line);
+                }
+                match stream {
+                    OutputStream::Stdout => output.stdout += &(line + "\n"),
+                    OutputStream::Stderr => output.stderr += &(line + "\n"),
+                }
+            }
+
+            output.exit_code = KubernetesTransport::finish(process).await;
+            return Ok(output);
+        };
+
+        return self.block_on(what, timeout, task);
+    }
+
+    fn block_on<F, T>(&self, what: &str, timeout: Option<Duration>, task: F) -> Result<T, DeltaError>
+    where
+        F: Future<Output = Result<T, DeltaError>>,
+    {
+        /* Dropping the task on cancellation closes the exec stream */
+        let token = current_token();
+        let task = async {
+            tokio::select! {
+                r = task => r,
+                _ = KubernetesTransport::cancelled(token) => Err(DeltaError::Cancelled),
+            }
+        };
+        return match timeout {
+            None => self.runtime.block_on(task),
+            Some(t) => self.runtime.block_on(async { tokio::time::timeout(t, task).await })
+                .unwrap_or_else(|_| {
+                    error!("Command timed out after {} ms: {}", t.as_millis(), scrub(what));
+                    Err(DeltaError::Timeout(scrub(what)))
+                }),
+        };
+    }
+
+    async fn cancelled(token: Option<CancellationToken>) {
+        let Some(token) = token else {
+            return std::future::pending().await;
+        };
+        while !token.is_cancelled() {
+            tokio::time::sleep(Duration::from_millis(CANCEL_POLL_MS)).await;
+        }
+    }
+
+    /* Exit code carried by the status the API server sends once the command is done */
+    async fn finish(mut process: AttachedProcess) -> i32 {
+        let status = match process.take_status() {
+            Some(status) => status.await,
+            None => None,
+        };
+        let _ = process.join().await;
+        return status.map(|s| KubernetesTransport::exit_code(&s)).unwrap_or(-1);
+    }
+
+    fn exit_code(status: &Status) -> i32 {
+        if status.status.as_deref() == Some("Success") {
+            return 0;
+        }
+
+        return status.details.as_ref()
+            .and_then(|d| d.causes.as_ref())
+            .and_then(|causes| causes.iter().find(|c| c.reason.as_deref() == Some("ExitCode")))
+            .and_then(|c| c.message.as_ref()?.parse::<i32>().ok())
+            .unwrap_or(-1);
+    }
+
+    fn api_error(e: kube::Error) -> DeltaError {
+        return DeltaError::CommandFailed(format!("kubernetes: {}", e));
+    }
+}
+
+impl Transport for KubernetesTransport {
+    fn execute(&self, cmd: &str, timeout: Option<Duration>,
+               sink: Option<&LineSink>) -> Result<ExecOutput, DeltaError> {
+        return self.run(cmd, cmd, timeout, sink);
+    }
+
+    fn execute_script(&self, commands: &[String], timeout: Option<Duration>,
+                      sink: Option<&LineSink>) -> Result<ExecOutput, DeltaError> {
+        return self.run(&commands.join("; "), &commands.join("\n"), timeout, sink);
+    }
+
+    fn upload(&self, reader: &mut dyn Read, remote_path: &str, mode: i32, _len: u64, offset: u64,
+              _method: &TransferMethod, on_progress: &mut dyn FnMut(u64)) -> Result<(), DeltaError> {
+        /* Like scp, an upload at an offset appends to what is already there */
+        let path = shell_quote(remote_path);
+        let redirect = if offset == 0 { ">" } else { ">>" };
+        let script = format!("cat {} {} && chmod {:o} {}", redirect, path, mode, path);
+        let exit_code = self.block_on(&script, None, async {
+            let mut process = self.start(&script, true).await?;
+            let mut stdin = process.stdin().ok_or(DeltaError::CommandFailed("no stdin".to_string()))?;
+            let mut buffer = vec![0; 4096];
+            let mut total = 0;
+            loop {
+                let n = reader.read(&mut buffer)?;
+                if n == 0 {
+                    break;
+                }
+
+                stdin.write_all(&buffer[..n]).await?;
+                total += n as u64;
+                on_progress(total);
+            }
+            stdin.shutdown().await?;
+            drop(stdin);
+
+            return Ok(KubernetesTransport::finish(process).await);
+        })?;
+
+        if exit_code != 0 {
+            return Err(DeltaError::CommandFailed(format!("failed to upload to {}", remote_path)));
+        }
+        return Ok(());
+    }
+
+    fn download(&self, remote_path: &str, writer: &mut dyn Write,
+                _method: &TransferMethod) -> Result<u64, DeltaError> {
+        let script = format!("cat {}", shell_quote(remote_path));
+        let (copied, exit_code) = self.block_on(&script, None, async {
+            let mut process = self.start(&script, false).await?;
+            let mut stdout = process.stdout().ok_or(DeltaError::CommandFailed("no stdout".to_string()))?;
+            let mut buffer = vec![0; 4096];
+            let mut total = 0;
+            loop {
+                let n = stdout.read(&mut buffer).await?;
+                if n == 0 {
+                    break;
+                }
+
+                writer.write_all(&buffer[..n])?;
+                total += n as u64;
+            }
+            drop(stdout);
+
+            return Ok((total, KubernetesTransport::finish(process).await));
+        })?;
+
+        if exit_code != 0 {
+            return Err(DeltaError::CommandFailed(format!("failed to download {}", remote_path)));
+        }
+        return Ok(copied);
+    }
+
+    fn file_size(&self, remote_path: &str, _method: &TransferMethod) -> Option<u64> {
+        let out = self.execute(&format!("wc -c < {}", shell_quote(remote_path)), None, None).ok()?;
+        if !out.success() {
+            return None;
+        }
+        return out.stdout.trim().parse::<u64>().ok();
+    }
+
+    fn is_alive(&self) -> bool {
+        return match self.runtime.block_on(self.pods.get(&self.pod)) {
+            Ok(pod) => pod.status.and_then(|s| s.phase).as_deref() == Some("Running"),
+            Err(e) => {
+                error!("Failed to look up pod {}: {}", self.pod, e);
+                false
+            }
+        };
+    }
+}
@@ -0,0 +1,150 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::pool_limits::PoolLimits;
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/*
+ * Enforces PoolLimits. Clones share the counters, which is how the split
+ * pools of a batch and the per-node pools of SharedNodePool draw from one
+ * budget.
+ */
+#[derive(Clone)]
+pub struct Limiter {
+    shared: Arc<(Mutex<LimiterState>, Condvar)>,
+}
+
+struct LimiterState {
+    limits: PoolLimits,
+    sessions: usize,
+    uploads: usize,
+    /* Earliest time the next connection to each host may start */
+    next_connect: HashMap<String, Instant>,
+}
+
+#[derive(Clone, Copy)]
+enum PermitKind {
+    Session,
+    Upload,
+}
+
+/* Held while a session or upload runs, gives the slot back on drop */
+pub struct Permit {
+    limiter: Limiter,
+    kind: PermitKind,
+}
+
+impl Limiter {
+    pub fn new() -> Limiter {
+        return Limiter {
+            shared: Arc::new((Mutex::new(LimiterState {
+                limits: PoolLimits::new(),
+                sessions: 0,
+                uploads: 0,
+                next_connect: HashMap::new(),
+            }), Condvar::new())),
+        };
+    }
+
+    pub fn limits(&self) -> PoolLimits {
+        return self.shared.0.lock().unwrap_or_else(|e| e.into_inner()).limits.clone();
+    }
+
+    /* Takes effect for the next permit; raising a cap wakes the waiters */
+    pub fn set_limits(&self, limits: PoolLimits) {
+        let (state, freed) = &*self.shared;
+        state.lock().unwrap_or_else(|e| e.into_inner()).limits = limits;
+        freed.notify_all();
+    }
+
+    /* Blocks while max_sessions nodes are being worked on */
+    pub fn session(&self) -> Permit {
+        return self.acquire(PermitKind::Session);
+    }
+
+    /* Blocks while max_uploads uploads are in flight */
+    pub fn upload(&self) -> Permit {
+        return self.acquire(PermitKind::Upload);
+    }
+
+    /*
+     * Sleeps until a connection to host is allowed. The slot is booked
+     * before sleeping, so concurrent callers line up one interval apart.
+     */
+    pub fn pace_connect(&self, host: &str) {
+        let wait = {
+            let mut state = self.shared.0.lock().unwrap_or_else(|e| e.into_inner());
+            let interval = Duration::from_millis(state.limits.connect_interval_ms);
+            if interval.is_zero() {
+                return;
+            }
+
+            let now = Instant::now();
+            let start = state.next_connect.get(host).copied().filter(|t| *t > now).unwrap_or(now);
+            state.next_connect.retain(|_, t| *t > now);
+            state.next_connect.insert(host.to_string(), start + interval);
+            start - now
+        };
+
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+
+    fn acquire(&self, kind: PermitKind) -> Permit {
+        let (state, freed) = &*self.shared;
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            let (in_use, max) = match kind {
+                PermitKind::Session => (state.sessions, state.limits.max_sessions),
+                PermitKind::Upload => (state.uploads, state.limits.max_uploads),
+            };
+            if max == 0 || in_use < max {
+                break;
+            }
+            state = freed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+
+        match kind {
+            PermitKind::Session => state.sessions += 1,
+            PermitKind::Upload => state.uploads += 1,
+        }
+        return Permit { limiter: self.clone(), kind };
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let (state, freed) = &*self.limiter.shared;
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        match self.kind {
+            PermitKind::Session => state.sessions -= 1,
+            PermitKind::Upload => state.uploads -= 1,
+        }
+        freed.notify_all();
+    }
+}
@@ -0,0 +1,186 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::delta_error::DeltaError;
+use crate::data_model::exec_output::{ExecOutput, OutputStream};
+use crate::data_model::secret::scrub;
+use crate::data_model::transfer_method::TransferMethod;
+use crate::obj_model::cancellation::is_cancelled;
+use crate::obj_model::stream_reader::LineSink;
+use crate::obj_model::transport::{copy_stream, Transport};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug_span, error, info};
+
+const POLL_INTERVAL_MS: u64 = 100;
+
+/*
+ * Transport for the machine the pool runs on: commands go to sh, files are
+ * plain copies. Lets the whole deploy/run flow be tried without an sshd.
+ */
+pub struct LocalTransport {
+    shell: String,
+}
+
+impl LocalTransport {
+    pub fn new() -> LocalTransport {
+        return LocalTransport { shell: "sh".to_string() };
+    }
+
+    fn run(&self, what: &str, script: &str, timeout: Option<Duration>,
+           sink: Option<&LineSink>) -> Result<ExecOutput, DeltaError> {
+        let mut command = Command::new(&self.shell);
+        command.arg("-c").arg(script);
+        return run_process(command, what, timeout, sink);
+    }
+}
+
+/*
+ * Runs command to completion, collecting its output and passing lines to sink
+ * as they come; past timeout the process is killed. what names it in logs.
+ */
+pub fn run_process(mut command: Command, what: &str, timeout: Option<Duration>,
+                   sink: Option<&LineSink>) -> Result<ExecOutput, DeltaError> {
+    let span = debug_span!("command", cmd = %scrub(what));
+    let _entered = span.enter();
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    /* Both pipes are drained on threads of their own so neither can fill up and stall the child */
+    let (tx, rx) = mpsc::channel();
+    let mut readers = Vec::new();
+    let pipes: [(OutputStream, Option<Box<dyn Read + Send>>); 2] = [
+        (OutputStream::Stdout, child.stdout.take().map(|p| Box::new(p) as Box<dyn Read + Send>)),
+        (OutputStream::Stderr, child.stderr.take().map(|p| Box::new(p) as Box<dyn Read + Send>)),
+    ];
+    for (stream, pipe) in pipes {
+        let Some(pipe) = pipe else { continue };
+        let tx = tx.clone();
+        readers.push(thread::spawn(move || {
+            let mut reader = BufReader::new(pipe);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap_or(0) > 0 {
+                if tx.send((stream.clone(), line.clone())).is_err() {
+                    break;
+                }
+                line.clear();
+            }
+        }));
+    }
+    drop(tx);
+
+    let deadline = timeout.map(|t| Instant::now() + t);
+    let mut output = ExecOutput::new();
+    loop {
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            let _ = child.kill();
+            let _ = child.wait();
+            error!("Command timed out after {} ms: {}",
+                   timeout.unwrap_or_default().as_millis(), scrub(what));
+            return Err(DeltaError::Timeout(scrub(what)));
+        }
+
+        if is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            info!("Command cancelled: {}", scrub(what));
+            return Err(DeltaError::Cancelled);
+        }
+
+        match rx.recv_timeout(Duration::from_millis(POLL_INTERVAL_MS)) {
+            Ok((stream, line)) => {
+                if let Some(sink) = sink {
+                    sink(stream.clone(), line.trim_end_matches(['\r', '\n']));
+                }
+                match stream {
+                    OutputStream::Stdout => output.stdout += &line,
+                    OutputStream::Stderr => output.stderr += &line,
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    for reader in readers {
+        let _ = reader.join();
+    }
+    output.exit_code = child.wait()?.code().unwrap_or(-1);
+    return Ok(output);
+}
+
+impl Transport for LocalTransport {
+    fn execute(&self, cmd: &str, timeout: Option<Duration>,
+               sink: Option<&LineSink>) -> Result<ExecOutput, DeltaError> {
+        return self.run(cmd, cmd, timeout, sink);
+    }
+
+    fn execute_script(&self, commands: &[String], timeout: Option<Duration>,
+                      sink: Option<&LineSink>) -> Result<ExecOutput, DeltaError> {
+        return self.run(&commands.join("; "), &commands.join("\n"), timeout, sink);
+    }
+
+    fn upload(&self, reader: &mut dyn Read, remote_path: &str, mode: i32, _len: u64, offset: u64,
+              _method: &TransferMethod, on_progress: &mut dyn FnMut(u64)) -> Result<(), DeltaError> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(offset == 0)
+            .open(remote_path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        copy_stream(reader, &mut file, on_progress)?;
+        file.flush()?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(remote_path, fs::Permissions::from_mode(mode as u32))?;
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+
+        return Ok(());
+    }
+
+    fn download(&self, remote_path: &str, writer: &mut dyn Write,
+                _method: &TransferMethod) -> Result<u64, DeltaError> {
+        let mut file = File::open(remote_path)?;
+        return copy_stream(&mut file, writer, &mut |_| {});
+    }
+
+    fn file_size(&self, remote_path: &str, _method: &TransferMethod) -> Option<u64> {
+        return fs::metadata(remote_path).ok().map(|m| m.len());
+    }
+
+    fn is_alive(&self) -> bool {
+        return true;
+    }
+}
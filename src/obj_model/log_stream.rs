@@ -0,0 +1,128 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::delta_error::DeltaError;
+use crate::data_model::exec_output::OutputStream;
+use crate::data_model::log_line::LogLine;
+use crate::obj_model::shell::shell_quote;
+use crate::obj_model::stream_reader::read_some;
+use ssh2::{Channel, Session};
+use std::collections::VecDeque;
+use std::thread;
+use std::time::Duration;
+
+/* Lines of each log shown before following it */
+pub const LOG_STREAM_BACKLOG: usize = 10;
+const LOG_STREAM_POLL_MS: u64 = 50;
+
+/*
+ * Lines appended to an instance's logs, as a remote tail -F prints them.
+ * Runs on a session of its own: reads poll it in non-blocking mode, which
+ * would get in the way of anybody else sharing the session. Ends when the
+ * remote side goes away; dropping it closes the channel.
+ */
+pub struct LogStream {
+    sess: Session,
+    channel: Channel,
+    stdout_buf: Vec<u8>,
+    stderr_buf: Vec<u8>,
+    pending: VecDeque<LogLine>,
+}
+
+impl LogStream {
+    pub fn open(sess: Session, log_dir: &str, backlog: usize) -> Result<LogStream, DeltaError> {
+        let mut channel = sess.channel_session()?;
+        /* stderr.log is followed onto the channel's stderr, tail's own complaints are dropped */
+        channel.exec(&format!(
+            "exec 3>&2 2> /dev/null; tail -n {2} -F {0} & tail -n {2} -F {1} >&3",
+            shell_quote(&format!("{}/stdout.log", log_dir)), shell_quote(&format!("{}/stderr.log", log_dir)),
+            backlog))?;
+        sess.set_blocking(false);
+
+        return Ok(LogStream {
+            sess,
+            channel,
+            stdout_buf: Vec::new(),
+            stderr_buf: Vec::new(),
+            pending: VecDeque::new(),
+        });
+    }
+
+    fn split_lines(buf: &mut Vec<u8>, stream: OutputStream, pending: &mut VecDeque<LogLine>, flush: bool) {
+        while let Some(pos) = buf.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buf.drain(..=pos).collect();
+            let text = String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n']).to_string();
+            pending.push_back(LogLine { stream: stream.clone(), text });
+        }
+
+        if flush && !buf.is_empty() {
+            let text = String::from_utf8_lossy(buf).to_string();
+            buf.clear();
+            pending.push_back(LogLine { stream, text });
+        }
+    }
+}
+
+impl Iterator for LogStream {
+    type Item = Result<LogLine, DeltaError>;
+
+    fn next(&mut self) -> Option<Result<LogLine, DeltaError>> {
+        loop {
+            if let Some(line) = self.pending.pop_front() {
+                return Some(Ok(line));
+            }
+
+            let got_out = match read_some(&mut self.channel, &mut self.stdout_buf) {
+                Ok(g) => g,
+                Err(e) => return Some(Err(e)),
+            };
+            let got_err = match read_some(&mut self.channel.stderr(), &mut self.stderr_buf) {
+                Ok(g) => g,
+                Err(e) => return Some(Err(e)),
+            };
+
+            let eof = !got_out && !got_err && self.channel.eof();
+            LogStream::split_lines(&mut self.stdout_buf, OutputStream::Stdout, &mut self.pending, eof);
+            LogStream::split_lines(&mut self.stderr_buf, OutputStream::Stderr, &mut self.pending, eof);
+            if got_out || got_err || !self.pending.is_empty() {
+                continue;
+            }
+
+            if eof {
+                return None;
+            }
+
+            /* Nobody else sends keepalives on this session */
+            let _ = self.sess.keepalive_send();
+            thread::sleep(Duration::from_millis(LOG_STREAM_POLL_MS));
+        }
+    }
+}
+
+impl Drop for LogStream {
+    fn drop(&mut self) {
+        self.sess.set_blocking(true);
+        let _ = self.channel.close();
+    }
+}
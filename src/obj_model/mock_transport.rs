@@ -0,0 +1,450 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::delta_error::DeltaError;
+use crate::data_model::exec_output::{ExecOutput, OutputStream};
+use crate::data_model::result::connect_result::ConnectResult;
+use crate::data_model::transfer_method::TransferMethod;
+use crate::obj_model::node::Node;
+use crate::obj_model::node_pool::NodePool;
+use crate::obj_model::stream_reader::LineSink;
+use crate::obj_model::transport::{Connector, Transport};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+struct MockState {
+    commands: Vec<String>,
+    responses: Vec<(String, Result<ExecOutput, String>)>,
+    files: HashMap<String, (Vec<u8>, i32)>,
+    alive: bool,
+}
+
+/*
+ * Transport that runs nothing: it records the commands it is given and
+ * answers them from scripted responses, and keeps uploaded files in memory.
+ * Clones share state, so a test keeps one while the pool owns another.
+ */
+#[derive(Clone)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockTransport {
+    pub fn new() -> MockTransport {
+        return MockTransport {
+            state: Arc::new(Mutex::new(MockState {
+                commands: Vec::new(),
+                responses: Vec::new(),
+                files: HashMap::new(),
+                alive: true,
+            })),
+        };
+    }
+
+    /*
+     * Answers commands containing needle with output. The latest matching
+     * response wins; unmatched commands succeed with no output.
+     */
+    pub fn respond(&self, needle: &str, output: ExecOutput) {
+        self.lock().responses.push((needle.to_string(), Ok(output)));
+    }
+
+    pub fn respond_ok(&self, needle: &str, stdout: &str) {
+        self.respond(needle, MockTransport::output(0, stdout, ""));
+    }
+
+    pub fn respond_fail(&self, needle: &str, exit_code: i32, stderr: &str) {
+        self.respond(needle, MockTransport::output(exit_code, "", stderr));
+    }
+
+    /* Commands containing needle fail to run at all, as a timeout */
+    pub fn respond_timeout(&self, needle: &str) {
+        self.lock().responses.push((needle.to_string(), Err(needle.to_string())));
+    }
+
+    /* Everything executed so far, in order; script lines are recorded one by one */
+    pub fn commands(&self) -> Vec<String> {
+        return self.lock().commands.clone();
+    }
+
+    /* Whether any command so far contains needle */
+    pub fn issued(&self, needle: &str) -> bool {
+        return self.lock().commands.iter().any(|c| c.contains(needle));
+    }
+
+    /* Position of the first command containing needle, for checking order */
+    pub fn position(&self, needle: &str) -> Option<usize> {
+        return self.lock().commands.iter().position(|c| c.contains(needle));
+    }
+
+    pub fn clear_commands(&self) {
+        self.lock().commands.clear();
+    }
+
+    /* Contents of a file uploaded to path */
+    pub fn file(&self, path: &str) -> Option<Vec<u8>> {
+        return self.lock().files.get(path).map(|(contents, _)| contents.clone());
+    }
+
+    pub fn file_mode(&self, path: &str) -> Option<i32> {
+        return self.lock().files.get(path).map(|(_, mode)| *mode);
+    }
+
+    /* Paths of all files on the mock node, sorted */
+    pub fn files(&self) -> Vec<String> {
+        let mut paths: Vec<String> = self.lock().files.keys().cloned().collect();
+        paths.sort();
+        return paths;
+    }
+
+    /* Places a file on the mock node, e.g. for a download to find */
+    pub fn put_file(&self, path: &str, contents: &[u8]) {
+        self.lock().files.insert(path.to_string(), (contents.to_vec(), 0o644));
+    }
+
+    /* A dead transport fails every call, as a dropped session does */
+    pub fn set_alive(&self, alive: bool) {
+        self.lock().alive = alive;
+    }
+
+    pub fn output(exit_code: i32, stdout: &str, stderr: &str) -> ExecOutput {
+        return ExecOutput {
+            stdout: stdout.to_string(),
+            stderr: stderr.to_string(),
+            exit_code,
+        };
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MockState> {
+        return self.state.lock().unwrap_or_else(|e| e.into_inner());
+    }
+
+    fn check_alive(state: &MockState) -> Result<(), DeltaError> {
+        if !state.alive {
+            return Err(DeltaError::Io(io::Error::from(io::ErrorKind::ConnectionReset)));
+        }
+
+        return Ok(());
+    }
+
+    fn answer(&self, what: &str, sink: Option<&LineSink>) -> Result<ExecOutput, DeltaError> {
+        let response = {
+            let state = self.lock();
+            MockTransport::check_alive(&state)?;
+            state.responses.iter().rev()
+                .find(|(needle, _)| what.contains(needle.as_str()))
+                .map(|(_, response)| response.clone())
+                .unwrap_or(Ok(MockTransport::output(0, "", "")))
+        };
+
+        let output = response.map_err(DeltaError::Timeout)?;
+        if let Some(sink) = sink {
+            for line in output.stdout.lines() {
+                sink(OutputStream::Stdout, line);
+            }
+            for line in output.stderr.lines() {
+                sink(OutputStream::Stderr, line);
+            }
+        }
+        return Ok(output);
+    }
+}
+
+impl Transport for MockTransport {
+    fn execute(&self, cmd: &str, _timeout: Option<Duration>,
+               sink: Option<&LineSink>) -> Result<ExecOutput, DeltaError> {
+        self.lock().commands.push(cmd.to_string());
+        return self.answer(cmd, sink);
+    }
+
+    fn execute_script(&self, commands: &[String], _timeout: Option<Duration>,
+                      sink: Option<&LineSink>) -> Result<ExecOutput, DeltaError> {
+        self.lock().commands.extend(commands.iter().cloned());
+        return self.answer(&commands.join("; "), sink);
+    }
+
+    fn upload(&self, reader: &mut dyn Read, remote_path: &str, mode: i32, _len: u64, offset: u64,
+              _method: &TransferMethod, on_progress: &mut dyn FnMut(u64)) -> Result<(), DeltaError> {
+        MockTransport::check_alive(&self.lock())?;
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents)?;
+
+        let mut state = self.lock();
+        let file = state.files.entry(remote_path.to_string()).or_insert((Vec::new(), mode));
+        file.0.truncate(offset as usize);
+        file.0.extend_from_slice(&contents);
+        if offset == 0 {
+            file.1 = mode;
+        }
+        on_progress(contents.len() as u64);
+        return Ok(());
+    }
+
+    fn download(&self, remote_path: &str, writer: &mut dyn Write,
+                _method: &TransferMethod) -> Result<u64, DeltaError> {
+        let contents = {
+            let state = self.lock();
+            MockTransport::check_alive(&state)?;
+            match state.files.get(remote_path) {
+                Some((contents, _)) => contents.clone(),
+                None => return Err(DeltaError::Io(io::Error::from(io::ErrorKind::NotFound))),
+            }
+        };
+
+        writer.write_all(&contents)?;
+        return Ok(contents.len() as u64);
+    }
+
+    fn file_size(&self, remote_path: &str, _method: &TransferMethod) -> Option<u64> {
+        return self.lock().files.get(remote_path).map(|(contents, _)| contents.len() as u64);
+    }
+
+    fn is_alive(&self) -> bool {
+        return self.lock().alive;
+    }
+}
+
+/*
+ * Connector handing out one MockTransport per node, the same one on every
+ * reconnect, so what a test scripted survives a dropped session.
+ */
+pub struct MockConnector {
+    transports: Mutex<HashMap<String, MockTransport>>,
+    refused: Mutex<HashMap<String, ConnectResult>>,
+}
+
+impl MockConnector {
+    pub fn new() -> MockConnector {
+        return MockConnector {
+            transports: Mutex::new(HashMap::new()),
+            refused: Mutex::new(HashMap::new()),
+        };
+    }
+
+    /* The node's transport, created on first use */
+    pub fn transport(&self, name: &str) -> MockTransport {
+        let mut transports = self.transports.lock().unwrap_or_else(|e| e.into_inner());
+        return transports.entry(name.to_string()).or_insert_with(MockTransport::new).clone();
+    }
+
+    /* Makes connecting to the node fail with result; ConnectResult::Ok lets it through again */
+    pub fn refuse(&self, name: &str, result: ConnectResult) {
+        let mut refused = self.refused.lock().unwrap_or_else(|e| e.into_inner());
+        if result == ConnectResult::Ok {
+            refused.remove(name);
+        } else {
+            refused.insert(name.to_string(), result);
+        }
+    }
+}
+
+impl Connector for MockConnector {
+    fn connect(&self, name: &str, _node: &Node) -> Result<Box<dyn Transport>, ConnectResult> {
+        let refused = self.refused.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(result) = refused.get(name) {
+            return Err(result.clone());
+        }
+
+        return Ok(Box::new(self.transport(name)));
+    }
+}
+
+/*
+ * A pool of the named nodes wired to a MockConnector, for exercising
+ * deploy(), run() and friends without an SSH server. Nodes get
+ * "<name>.mock" addresses and no params; they are not connected yet.
+ */
+pub fn mock_pool(names: &[&str]) -> (NodePool, Arc<MockConnector>) {
+    let connector = Arc::new(MockConnector::new());
+    let mut pool = NodePool::new();
+    for name in names {
+        pool.add(name.to_string(), format!("{}.mock", name), HashMap::new());
+    }
+    pool.set_connector(Some(connector.clone()));
+    return (pool, connector);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_model::deploy_subject::DeploySubject;
+    use crate::data_model::node_parameters::NodeParameters;
+    use crate::data_model::result::deploy_result::DeployResult;
+    use crate::data_model::result::run_result::RunResult;
+    use crate::data_model::run_options::RunOptions;
+    use crate::obj_model::checksum::file_sha256;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /* A pool of one node "a" deploying a throwaway tar; the transport runs nothing, so any bytes do */
+    fn deploy_pool(artifact: &str) -> (NodePool, MockTransport, PathBuf) {
+        let distr = std::env::temp_dir().join(format!("delta-mock-{}-{}.tar", std::process::id(), artifact));
+        fs::write(&distr, artifact.as_bytes()).unwrap();
+        let (mut pool, connector) = mock_pool(&["a"]);
+        pool.str_params.insert(NodeParameters::Distr.to_string(), distr.display().to_string());
+        assert_eq!(pool.connect("a".to_string()), ConnectResult::Ok);
+        return (pool, connector.transport("a"), distr);
+    }
+
+    /* Scripts the answers a healthy node gives to the checks after the upload */
+    fn answer_checks(transport: &MockTransport, distr: &PathBuf, checksum: &str) {
+        let remote = format!("/tmp/delta-artifacts/{}.tar", checksum);
+        let size = fs::metadata(distr).unwrap().len();
+        transport.respond_ok("sha256sum", &format!("{}  {}\n", checksum, remote));
+        transport.respond_ok("stat -L", &format!("size={} mode=644 mtime=1 dir=0\n", size));
+    }
+
+    #[test]
+    fn deploy_uploads_verifies_then_extracts() {
+        let (mut pool, transport, distr) = deploy_pool("deploy-ok");
+        let checksum = file_sha256(&distr).unwrap();
+        answer_checks(&transport, &distr, &checksum);
+
+        assert_eq!(pool.deploy("a".to_string(), DeploySubject::Sa), DeployResult::Ok);
+        let remote = format!("/tmp/delta-artifacts/{}.tar", checksum);
+        assert_eq!(transport.file(&remote), Some(b"deploy-ok".to_vec()));
+        let verified = transport.position("sha256sum").unwrap();
+        let extracted = transport.position("tar xf").unwrap();
+        let tested = transport.position("--version").unwrap();
+        assert!(verified < extracted && extracted < tested, "{:#?}", transport.commands());
+        fs::remove_file(distr).unwrap();
+    }
+
+    #[test]
+    fn deploy_stops_on_checksum_mismatch() {
+        let (mut pool, transport, distr) = deploy_pool("deploy-corrupt");
+        answer_checks(&transport, &distr, &"0".repeat(64));
+
+        assert_eq!(pool.deploy("a".to_string(), DeploySubject::Sa), DeployResult::ChecksumMismatch);
+        assert!(!transport.issued("tar xf"));
+        fs::remove_file(distr).unwrap();
+    }
+
+    #[test]
+    fn deploy_reuses_cached_artifact() {
+        let (mut pool, transport, distr) = deploy_pool("deploy-cached");
+        transport.respond_ok("echo match", "match\n");
+
+        assert_eq!(pool.deploy("a".to_string(), DeploySubject::Sa), DeployResult::AlreadyDeployed);
+        assert!(transport.files().is_empty());
+        assert!(transport.issued("tar xf"));
+        fs::remove_file(distr).unwrap();
+    }
+
+    #[test]
+    fn run_starts_on_bind_address() {
+        let (mut pool, connector) = mock_pool(&["a"]);
+        pool.str_params.insert(NodeParameters::BindPort.to_string(), "5800".to_string());
+        assert_eq!(pool.connect("a".to_string()), ConnectResult::Ok);
+        let transport = connector.transport("a");
+        transport.respond_ok("&& echo pid", "pid 4242\n");
+
+        assert_eq!(pool.run("a".to_string(), DeploySubject::Sa), RunResult::Ok);
+        assert!(transport.issued("'/tmp/visao/bin/visao' --server 'tcp://127.0.0.1:5800'"), "{:#?}", transport.commands());
+        assert!(transport.issued("echo 5800 > '/tmp/visao'/bind_port"));
+        assert!(pool.is_connected("a".to_string()).get_subject(DeploySubject::Sa).running);
+    }
+
+    #[test]
+    fn run_fails_when_instance_dies() {
+        let (mut pool, connector) = mock_pool(&["a"]);
+        assert_eq!(pool.connect("a".to_string()), ConnectResult::Ok);
+        connector.transport("a").respond_fail("&& echo pid", 1, "bind: address in use");
+
+        assert_eq!(pool.run("a".to_string(), DeploySubject::Sa), RunResult::RunFailed);
+        assert!(!pool.is_connected("a".to_string()).get_subject(DeploySubject::Sa).running);
+    }
+
+    #[test]
+    fn run_rejects_line_breaks_in_options() {
+        let (mut pool, connector) = mock_pool(&["a"]);
+        pool.str_params.insert(NodeParameters::RunMode.to_string(), "systemd".to_string());
+        assert_eq!(pool.connect("a".to_string()), ConnectResult::Ok);
+        let transport = connector.transport("a");
+        transport.clear_commands();
+
+        let escape = RunOptions::new().arg("x\nDELTA_API_EOF\ntouch /tmp/pwned");
+        assert_eq!(pool.run_with_options("a".to_string(), DeploySubject::Sa, escape), RunResult::InvalidArgument);
+        let env = RunOptions::new().env("TOKEN", "a\rb");
+        assert_eq!(pool.run_with_options("a".to_string(), DeploySubject::Sa, env), RunResult::InvalidArgument);
+        let dir = RunOptions::new().working_dir("/srv\n/x");
+        assert_eq!(pool.run_with_options("a".to_string(), DeploySubject::Sa, dir), RunResult::InvalidArgument);
+        assert!(!transport.issued("pwned"), "{:#?}", transport.commands());
+    }
+
+    #[test]
+    fn run_rejects_unusable_options() {
+        let (mut pool, connector) = mock_pool(&["a"]);
+        assert_eq!(pool.connect("a".to_string()), ConnectResult::Ok);
+        let transport = connector.transport("a");
+        transport.clear_commands();
+
+        let rejected = [
+            RunOptions::new().env("1ST", "x"),
+            RunOptions::new().env("A-B", "x"),
+            RunOptions::new().env("TOKEN", "a\0b"),
+            RunOptions::new().arg("ok").arg("a\0b"),
+            RunOptions::new().working_dir(""),
+        ];
+        for options in rejected {
+            assert_eq!(pool.run_with_options("a".to_string(), DeploySubject::Sa, options.clone()),
+                       RunResult::InvalidArgument, "{:?}", options);
+        }
+        assert!(!transport.issued("--server"), "{:#?}", transport.commands());
+    }
+
+    #[test]
+    fn run_quotes_options() {
+        let (mut pool, connector) = mock_pool(&["a"]);
+        assert_eq!(pool.connect("a".to_string()), ConnectResult::Ok);
+        let transport = connector.transport("a");
+        transport.respond_ok("&& echo pid", "pid 4242\n");
+
+        let options = RunOptions::new().arg("it's; rm -rf /").env("GREETING", "$(id)");
+        assert_eq!(pool.run_with_options("a".to_string(), DeploySubject::Sa, options), RunResult::Ok);
+        assert!(transport.issued("env GREETING='$(id)' "), "{:#?}", transport.commands());
+        assert!(transport.issued(" 'it'\\''s; rm -rf /'"), "{:#?}", transport.commands());
+    }
+
+    #[test]
+    fn refused_connection_is_reported() {
+        let (mut pool, connector) = mock_pool(&["a"]);
+        connector.refuse("a", ConnectResult::NotAuthenticated);
+        assert_eq!(pool.connect("a".to_string()), ConnectResult::NotAuthenticated);
+
+        connector.refuse("a", ConnectResult::Ok);
+        assert_eq!(pool.connect("a".to_string()), ConnectResult::Ok);
+    }
+
+    #[test]
+    fn log_stream_is_unsupported_on_connector_transports() {
+        let (mut pool, _connector) = mock_pool(&["a"]);
+        assert_eq!(pool.connect("a".to_string()), ConnectResult::Ok);
+
+        let result = pool.stream_logs("a".to_string(), DeploySubject::Sa);
+        assert!(matches!(result, Err(DeltaError::Unsupported(_))));
+    }
+}
@@ -22,8 +22,95 @@
  * DEALINGS IN THE SOFTWARE.
  */
 
+#[cfg(feature = "inventory")]
+pub mod ansible;
+#[cfg(feature = "object_model")]
+pub mod archive;
+#[cfg(feature = "object_model")]
+pub mod artifact_url;
+#[cfg(feature = "async")]
+pub mod async_node_pool;
+#[cfg(feature = "object_model")]
+pub mod audit;
+#[cfg(feature = "object_model")]
+pub mod cancellation;
+#[cfg(feature = "object_model")]
+pub mod checksum;
+#[cfg(feature = "encryption")]
+pub mod credential_store;
+#[cfg(feature = "delta_sync")]
+pub mod delta_sync;
+#[cfg(feature = "object_model")]
+pub mod docker_transport;
+#[cfg(feature = "object_model")]
+pub mod env_expand;
+#[cfg(feature = "object_model")]
+pub mod event_bus;
+#[cfg(feature = "object_model")]
+pub mod fan_out;
+#[cfg(feature = "grpc")]
+pub mod grpc_server;
+#[cfg(feature = "object_model")]
+pub mod health_monitor;
+#[cfg(feature = "object_model")]
+pub mod inventory;
+#[cfg(feature = "object_model")]
+pub mod job_queue;
+#[cfg(feature = "json_log")]
+pub mod json_log;
+#[cfg(feature = "object_model")]
+pub mod jump_host;
+#[cfg(feature = "object_model")]
+pub mod known_hosts;
+#[cfg(feature = "kubernetes")]
+pub mod kubernetes_transport;
+#[cfg(feature = "object_model")]
+pub mod limiter;
+#[cfg(feature = "object_model")]
+pub mod local_transport;
+#[cfg(feature = "object_model")]
+pub mod log_stream;
+#[cfg(feature = "mock")]
+pub mod mock_transport;
+#[cfg(feature = "object_model")]
+pub mod net;
 #[cfg(feature = "object_model")]
-
 pub mod node;
 #[cfg(feature = "object_model")]
+pub mod node_builder;
+#[cfg(feature = "object_model")]
+pub mod node_lock;
+#[cfg(feature = "object_model")]
+pub mod node_pattern;
+#[cfg(feature = "object_model")]
 pub mod node_pool;
+#[cfg(feature = "object_model")]
+pub mod operation;
+#[cfg(feature = "object_model")]
+pub mod port_forward;
+#[cfg(feature = "rest")]
+pub mod rest_server;
+#[cfg(feature = "object_model")]
+pub mod secrets;
+#[cfg(feature = "object_model")]
+pub mod shared_node_pool;
+#[cfg(feature = "object_model")]
+pub mod shell;
+#[cfg(feature = "object_model")]
+pub mod ssh_config;
+#[cfg(feature = "object_model")]
+pub mod systemd;
+#[cfg(feature = "object_model")]
+pub mod stream_reader;
+#[cfg(feature = "object_model")]
+pub mod supervisor;
+#[cfg(feature = "object_model")]
+pub mod tag_expr;
+#[cfg(feature = "object_model")]
+pub mod template;
+#[cfg(feature = "object_model")]
+pub mod transport;
+#[cfg(feature = "object_model")]
+pub mod unix;
+#[cfg(feature = "object_model")]
+pub mod windows;
@@ -0,0 +1,214 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::delta_error::DeltaError;
+use std::collections::HashSet;
+use std::io;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const LIBSSH2_ERROR_TIMEOUT: i32 = -9;
+const LIBSSH2_ERROR_SOCKET_TIMEOUT: i32 = -30;
+
+pub const DEFAULT_SSH_PORT: u16 = 22;
+
+/* IANA dynamic range, for nodes that don't tell their own */
+pub const DEFAULT_EPHEMERAL_PORTS: (u16, u16) = (49152, 65535);
+
+/* Head start each address gets before the next one is tried too (RFC 8305) */
+const CONNECT_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/*
+ * Splits "host", "host:port", "[v6addr]" or "[v6addr]:port"; a bare IPv6
+ * literal is taken as a whole. The port is None when the address has none,
+ * brackets are not part of the returned host.
+ */
+pub fn parse_host_port(fqdn: &str) -> Result<(String, Option<u16>), DeltaError> {
+    let invalid = || DeltaError::InvalidParameter("fqdn".to_string(), fqdn.to_string());
+    let parse_port = |port: &str| match port.parse::<u16>() {
+        Ok(p) if p > 0 => Ok(Some(p)),
+        _ => Err(invalid()),
+    };
+
+    let fqdn = fqdn.trim();
+    let (host, port) = if let Some(rest) = fqdn.strip_prefix('[') {
+        let (addr, tail) = rest.split_once(']').ok_or_else(invalid)?;
+        if addr.parse::<Ipv6Addr>().is_err() {
+            return Err(invalid());
+        }
+        let port = match tail {
+            "" => None,
+            _ => parse_port(tail.strip_prefix(':').ok_or_else(invalid)?)?,
+        };
+        (addr, port)
+    } else if fqdn.matches(':').count() > 1 {
+        if fqdn.parse::<Ipv6Addr>().is_err() {
+            return Err(invalid());
+        }
+        (fqdn, None)
+    } else {
+        match fqdn.split_once(':') {
+            Some((host, port)) => (host, parse_port(port)?),
+            None => (fqdn, None),
+        }
+    };
+
+    if host.is_empty() || host.contains(|c: char| c.is_whitespace() || "/@'\"[]".contains(c)) {
+        return Err(invalid());
+    }
+
+    return Ok((host.to_string(), port));
+}
+
+/* Inverse of parse_host_port(): IPv6 literals get brackets */
+pub fn format_host_port(host: &str, port: u16) -> String {
+    if host.contains(':') {
+        return format!("[{}]:{}", host, port);
+    }
+    return format!("{}:{}", host, port);
+}
+
+/* An IP literal, with or without brackets, or a host name */
+pub fn parse_bind_addr(addr: &str) -> Option<String> {
+    let addr = addr.trim();
+    let bare = addr.strip_prefix('[').and_then(|a| a.strip_suffix(']')).unwrap_or(addr);
+    if bare.parse::<IpAddr>().is_ok() {
+        return Some(bare.to_string());
+    }
+
+    let is_hostname = !bare.is_empty()
+        && bare.split('.').all(|l| !l.is_empty() && l.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'));
+    return if is_hostname { Some(bare.to_string()) } else { None };
+}
+
+/* How an instance's bind port is chosen */
+#[derive(PartialEq, Clone, Debug)]
+pub enum BindPort {
+    Fixed(u16),
+    /* Any port of the node's ephemeral range nothing listens on */
+    Auto,
+    /* The first port of lo-hi nothing listens on */
+    Range(u16, u16),
+}
+
+impl BindPort {
+    /* "auto", "<lo>-<hi>" or a single port */
+    pub fn parse(value: &str) -> Option<BindPort> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("auto") {
+            return Some(BindPort::Auto);
+        }
+
+        let port = |p: &str| p.trim().parse::<u16>().ok().filter(|p| *p > 0);
+        return match value.split_once('-') {
+            Some((lo, hi)) => {
+                let (lo, hi) = (port(lo)?, port(hi)?);
+                if lo > hi {
+                    return None;
+                }
+                Some(BindPort::Range(lo, hi))
+            }
+            None => Some(BindPort::Fixed(port(value)?)),
+        };
+    }
+}
+
+/*
+ * A port of lo-hi not in taken, looking from start on and wrapping around;
+ * start is clamped into the range.
+ */
+pub fn pick_free_port(lo: u16, hi: u16, start: u16, taken: &HashSet<u16>) -> Option<u16> {
+    let start = start.clamp(lo, hi);
+    return (start..=hi).chain(lo..start).find(|p| !taken.contains(p));
+}
+
+/*
+ * Happy eyeballs: tries every address the host resolves to, IPv6 and IPv4
+ * interleaved, starting the next one when the previous fails or hasn't
+ * answered within CONNECT_ATTEMPT_DELAY. The first connection wins; timeout
+ * bounds the whole attempt.
+ */
+pub fn connect_tcp(host: &str, port: u16, timeout: Duration) -> io::Result<TcpStream> {
+    let addrs = interleave_families((host, port).to_socket_addrs()?.collect());
+    match addrs.len() {
+        0 => return Err(io::Error::new(io::ErrorKind::NotFound, format!("No address for {}", host))),
+        1 => return TcpStream::connect_timeout(&addrs[0], timeout),
+        _ => {}
+    }
+
+    let deadline = Instant::now() + timeout;
+    let (tx, rx) = mpsc::channel();
+    let mut pending_addrs = addrs.into_iter();
+    let mut in_flight = 0;
+    let mut last_error = None;
+    while Instant::now() < deadline {
+        let wait = match pending_addrs.next() {
+            Some(addr) => {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    let _ = tx.send(TcpStream::connect_timeout(&addr, timeout));
+                });
+                in_flight += 1;
+                CONNECT_ATTEMPT_DELAY
+            }
+            None if in_flight == 0 => break,
+            None => deadline.saturating_duration_since(Instant::now()),
+        };
+
+        match rx.recv_timeout(wait) {
+            Ok(Ok(stream)) => return Ok(stream),
+            Ok(Err(e)) => {
+                in_flight -= 1;
+                last_error = Some(e);
+            }
+            Err(_) => {}
+        }
+    }
+
+    return Err(last_error.unwrap_or_else(|| io::Error::new(io::ErrorKind::TimedOut,
+                                                            format!("No address of {} answered", host))));
+}
+
+fn interleave_families(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let (v6, v4): (Vec<SocketAddr>, Vec<SocketAddr>) = addrs.into_iter().partition(|a| a.is_ipv6());
+    let mut result = Vec::with_capacity(v6.len() + v4.len());
+    let (mut v6, mut v4) = (v6.into_iter(), v4.into_iter());
+    loop {
+        match (v6.next(), v4.next()) {
+            (None, None) => return result,
+            (a, b) => result.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+pub fn is_timeout(e: &io::Error) -> bool {
+    return e.kind() == io::ErrorKind::TimedOut || e.kind() == io::ErrorKind::WouldBlock;
+}
+
+pub fn is_ssh_timeout(e: &ssh2::Error) -> bool {
+    return e.code() == ssh2::ErrorCode::Session(LIBSSH2_ERROR_TIMEOUT)
+        || e.code() == ssh2::ErrorCode::Session(LIBSSH2_ERROR_SOCKET_TIMEOUT);
+}
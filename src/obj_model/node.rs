@@ -22,18 +22,57 @@
  * DEALINGS IN THE SOFTWARE.
  */
 
+use crate::data_model::deploy_subject::DeploySubject;
+use crate::data_model::platform::Platform;
+use crate::data_model::platform_kind::PlatformKind;
+use crate::data_model::restart_policy::RestartPolicy;
+use crate::data_model::retry_policy::RetryPolicy;
+use crate::data_model::secret::redact_params;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
-#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
 #[repr(C)]
 pub struct Node {
     pub fqdn: String,
     pub str_params: HashMap<String, String>,
+    #[serde(default)]
+    pub retry_policy: Option<RetryPolicy>,
+    #[serde(default)]
+    pub tags: HashSet<String>,
+    #[serde(default)]
+    pub restart_policies: HashMap<DeploySubject, RestartPolicy>,
+    /* Outcome of the last failed operation, kept for status reporting only */
+    #[serde(skip)]
+    pub last_error: Option<String>,
+    /* What the last connect found on the other end, so commands are built for it */
+    #[serde(skip)]
+    pub platform_kind: PlatformKind,
+    #[serde(skip)]
+    pub os: Platform,
+    /* %TEMP% of a Windows node, which stands in for /tmp in default paths */
+    #[serde(skip)]
+    pub temp_dir: String,
 }
 
 unsafe impl Send for Node {}
 
+impl fmt::Debug for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.debug_struct("Node")
+            .field("fqdn", &self.fqdn)
+            .field("str_params", &redact_params(&self.str_params))
+            .field("retry_policy", &self.retry_policy)
+            .field("tags", &self.tags)
+            .field("restart_policies", &self.restart_policies)
+            .field("last_error", &self.last_error)
+            .field("platform_kind", &self.platform_kind)
+            .field("os", &self.os)
+            .finish();
+    }
+}
+
 impl Node {
     pub fn safe_str(&self, name: &str) -> String {
         if self.str_params.contains_key(name) {
@@ -0,0 +1,177 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::delta_error::DeltaError;
+use crate::data_model::deploy_subject::DeploySubject;
+use crate::data_model::node_parameters::NodeParameters;
+use crate::data_model::platform::Platform;
+use crate::data_model::platform_kind::PlatformKind;
+use crate::data_model::restart_policy::RestartPolicy;
+use crate::data_model::retry_policy::RetryPolicy;
+use crate::obj_model::artifact_url::ArtifactUrl;
+use crate::obj_model::net::parse_host_port;
+use crate::obj_model::node::Node;
+use crate::obj_model::tag_expr::is_valid_tag;
+use std::collections::{HashMap, HashSet};
+
+/*
+ * Typed alternative to NodePool::add(): collects params through setters and
+ * validates them in build(), e.g.
+ * NodeBuilder::new("web1", "10.0.0.5:22").username("deploy").bind_port(DeploySubject::Sa, 5800)
+ */
+pub struct NodeBuilder {
+    name: String,
+    fqdn: String,
+    params: HashMap<String, String>,
+    tags: HashSet<String>,
+    retry_policy: Option<RetryPolicy>,
+    restart_policies: HashMap<DeploySubject, RestartPolicy>,
+}
+
+impl NodeBuilder {
+    pub fn new(name: &str, fqdn: &str) -> NodeBuilder {
+        return NodeBuilder {
+            name: name.to_string(),
+            fqdn: fqdn.to_string(),
+            params: HashMap::new(),
+            tags: HashSet::new(),
+            retry_policy: None,
+            restart_policies: HashMap::new(),
+        };
+    }
+
+    pub fn param(mut self, param: NodeParameters, value: &str) -> NodeBuilder {
+        self.params.insert(param.to_string(), value.to_string());
+        return self;
+    }
+
+    pub fn username(self, username: &str) -> NodeBuilder {
+        return self.param(NodeParameters::Username, username);
+    }
+
+    pub fn password(self, password: &str) -> NodeBuilder {
+        return self.param(NodeParameters::Password, password);
+    }
+
+    pub fn identity_file(self, path: &str) -> NodeBuilder {
+        return self.param(NodeParameters::IdentityFile, path);
+    }
+
+    pub fn key_passphrase(self, passphrase: &str) -> NodeBuilder {
+        return self.param(NodeParameters::KeyPassphrase, passphrase);
+    }
+
+    pub fn ssh_port(self, port: u16) -> NodeBuilder {
+        return self.param(NodeParameters::SshPort, &port.to_string());
+    }
+
+    pub fn jump_host(self, fqdn: &str) -> NodeBuilder {
+        return self.param(NodeParameters::JumpHost, fqdn);
+    }
+
+    pub fn bind_addr(self, addr: &str) -> NodeBuilder {
+        return self.param(NodeParameters::BindAddr, addr);
+    }
+
+    pub fn distr(self, subject: DeploySubject, path: &str) -> NodeBuilder {
+        return self.param(subject.distr_param(), path);
+    }
+
+    /* The node downloads the archive from url itself and checks it against sha256 */
+    pub fn distr_url(self, subject: DeploySubject, url: &str, sha256: &str) -> NodeBuilder {
+        return self.param(subject.distr_param(), &ArtifactUrl::with_checksum(url, sha256));
+    }
+
+    pub fn remote_dir(self, subject: DeploySubject, dir: &str) -> NodeBuilder {
+        return self.param(subject.remote_dir_param(), dir);
+    }
+
+    pub fn bind_port(self, subject: DeploySubject, port: u16) -> NodeBuilder {
+        return self.param(subject.bind_port_param(), &port.to_string());
+    }
+
+    /* run() picks the first port of lo-hi nothing listens on */
+    pub fn bind_port_range(self, subject: DeploySubject, lo: u16, hi: u16) -> NodeBuilder {
+        return self.param(subject.bind_port_param(), &format!("{}-{}", lo, hi));
+    }
+
+    /* run() picks any free port of the node's ephemeral range */
+    pub fn bind_port_auto(self, subject: DeploySubject) -> NodeBuilder {
+        return self.param(subject.bind_port_param(), "auto");
+    }
+
+    pub fn tag(mut self, tag: &str) -> NodeBuilder {
+        self.tags.insert(tag.to_string());
+        return self;
+    }
+
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> NodeBuilder {
+        self.retry_policy = Some(policy);
+        return self;
+    }
+
+    /* Node name and the node, or the first misconfiguration found */
+    pub fn restart_policy(mut self, subject: DeploySubject, policy: RestartPolicy) -> NodeBuilder {
+        self.restart_policies.insert(subject, policy);
+        return self;
+    }
+
+    pub fn build(self) -> Result<(String, Node), DeltaError> {
+        let invalid = |what: &str, value: &str| DeltaError::InvalidParameter(what.to_string(), value.to_string());
+
+        if self.name.trim().is_empty() {
+            return Err(invalid("name", &self.name));
+        }
+        parse_host_port(&self.fqdn)?;
+        if let Some(tag) = self.tags.iter().find(|t| !is_valid_tag(t)) {
+            return Err(invalid("tag", tag));
+        }
+
+        let has = |param: NodeParameters| self.params.get(&param.to_string()).is_some_and(|v| !v.is_empty());
+        if has(NodeParameters::KeyPassphrase) && !has(NodeParameters::IdentityFile) {
+            return Err(invalid(&NodeParameters::KeyPassphrase.to_string(), "set without IdentityFile"));
+        }
+
+        let ports = DeploySubject::all().into_iter().map(|s| s.bind_port_param()).chain([NodeParameters::SshPort]);
+        for port in ports.map(|p| p.to_string()) {
+            if let Some(value) = self.params.get(&port) {
+                if !matches!(value.parse::<u16>(), Ok(p) if p > 0) {
+                    return Err(invalid(&port, value));
+                }
+            }
+        }
+
+        return Ok((self.name, Node {
+            fqdn: self.fqdn,
+            str_params: self.params,
+            retry_policy: self.retry_policy,
+            tags: self.tags,
+            restart_policies: self.restart_policies,
+            last_error: None,
+            platform_kind: PlatformKind::Unix,
+            os: Platform::Linux,
+            temp_dir: "".to_string(),
+        }));
+    }
+}
@@ -0,0 +1,77 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use std::collections::HashMap;
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::thread::{self, ThreadId};
+
+/*
+ * Process-wide lock on a node, keyed by its address, held for the whole of
+ * an operation that changes what is on the node. Operations on the same
+ * node serialize even when they come through different pools or under
+ * different names; other nodes proceed in parallel. The lock is re-entrant,
+ * so an operation may call others on the node it holds.
+ */
+pub struct NodeLock {
+    key: String,
+}
+
+struct Holder {
+    thread: ThreadId,
+    depth: usize,
+}
+
+fn held_locks() -> &'static (Mutex<HashMap<String, Holder>>, Condvar) {
+    static LOCKS: OnceLock<(Mutex<HashMap<String, Holder>>, Condvar)> = OnceLock::new();
+    return LOCKS.get_or_init(|| (Mutex::new(HashMap::new()), Condvar::new()));
+}
+
+impl NodeLock {
+    /* Blocks while another thread holds the node */
+    pub fn acquire(key: &str) -> NodeLock {
+        let (locks, released) = held_locks();
+        let me = thread::current().id();
+        let mut held = locks.lock().unwrap_or_else(|e| e.into_inner());
+        while held.get(key).is_some_and(|h| h.thread != me) {
+            held = released.wait(held).unwrap_or_else(|e| e.into_inner());
+        }
+
+        held.entry(key.to_string()).or_insert(Holder { thread: me, depth: 0 }).depth += 1;
+        return NodeLock { key: key.to_string() };
+    }
+}
+
+impl Drop for NodeLock {
+    fn drop(&mut self) {
+        let (locks, released) = held_locks();
+        let mut held = locks.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(holder) = held.get_mut(&self.key) {
+            holder.depth -= 1;
+            if holder.depth == 0 {
+                held.remove(&self.key);
+                released.notify_all();
+            }
+        }
+    }
+}
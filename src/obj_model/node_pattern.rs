@@ -0,0 +1,58 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::delta_error::DeltaError;
+use glob::Pattern;
+use regex::Regex;
+
+const REGEX_PREFIX: &str = "re:";
+
+/*
+ * Pattern matched against node names: a shell glob ("web-*", "db-[0-9]"),
+ * or a regex when prefixed with "re:" ("re:^web-\d+$"). Regexes aren't
+ * anchored implicitly.
+ */
+pub enum NodePattern {
+    Glob(Pattern),
+    Regex(Regex),
+}
+
+impl NodePattern {
+    pub fn parse(pattern: &str) -> Result<NodePattern, DeltaError> {
+        let invalid = || DeltaError::InvalidParameter("node pattern".to_string(), pattern.to_string());
+
+        if let Some(re) = pattern.strip_prefix(REGEX_PREFIX) {
+            return Ok(NodePattern::Regex(Regex::new(re).map_err(|_| invalid())?));
+        }
+
+        return Ok(NodePattern::Glob(Pattern::new(pattern).map_err(|_| invalid())?));
+    }
+
+    pub fn matches(&self, s: &str) -> bool {
+        return match self {
+            NodePattern::Glob(p) => p.matches(s),
+            NodePattern::Regex(r) => r.is_match(s),
+        };
+    }
+}
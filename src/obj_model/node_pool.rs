@@ -22,32 +22,147 @@
  * DEALINGS IN THE SOFTWARE.
  */
 
+use crate::data_model::archive_format::ArchiveFormat;
+use crate::data_model::audit_record::{AuditAction, AuditRecord};
 use crate::data_model::conn_alive_status::*;
+use crate::data_model::conn_method::ConnMethod;
+use crate::data_model::delta_error::DeltaError;
+use crate::data_model::deploy_progress::{DeployPhase, DeployProgress};
 use crate::data_model::deploy_subject::DeploySubject;
+use crate::data_model::endpoint::Endpoint;
+use crate::data_model::exec_output::{ExecOutput, OutputStream};
+use crate::data_model::file_info::FileInfo;
+use crate::data_model::result::rollback_result::RollbackResult;
 use crate::data_model::result::run_result::RunResult;
+use crate::data_model::result::stop_result::StopResult;
+use crate::data_model::result::undeploy_result::UndeployResult;
+use crate::data_model::result::update_result::UpdateResult;
+use crate::data_model::result::upgrade_result::UpgradeResult;
+use crate::data_model::installed_versions::InstalledVersions;
+use crate::data_model::instance_logs::InstanceLogs;
 use crate::data_model::instance::Instance;
-use crate::data_model::conn_status::ConnStatus;
+use crate::data_model::conn_status::{ConnStatus, SubjectStatus};
+use crate::data_model::node_facts::NodeFacts;
 use crate::data_model::node_parameters::NodeParameters;
+use crate::data_model::node_summary::NodeSummary;
+use crate::data_model::operation_record::OperationRecord;
+use crate::data_model::operation_result::OperationResult;
+use crate::data_model::platform::Platform;
+use crate::data_model::platform_kind::PlatformKind;
+use crate::data_model::run_status::RunStatus;
+use crate::data_model::pool_event::PoolEvent;
+use crate::data_model::pool_limits::PoolLimits;
+use crate::data_model::prerequisite_report::{PrerequisiteReport, ToolCheck};
+use crate::data_model::resource_usage::ResourceUsage;
+use crate::data_model::restart_policy::{RestartMode, RestartPolicy};
+use crate::data_model::retry_policy::RetryPolicy;
+use crate::data_model::run_mode::RunMode;
+use crate::data_model::run_options::RunOptions;
+use crate::data_model::secret::{register_secret, scrub, Secret};
+use crate::data_model::stop_method::StopMethod;
+use crate::data_model::supervisor_event::SupervisorEvent;
+use crate::data_model::transfer_method::TransferMethod;
+use crate::data_model::result::activate_result::ActivateResult;
 use crate::data_model::result::add_result::AddResult;
 use crate::data_model::result::connect_result::ConnectResult;
 use crate::data_model::result::deploy_result::DeployResult;
 use crate::data_model::result::disconnect_result::DisconnectResult;
 use crate::data_model::result::remove_result::RemoveResult;
+use crate::data_model::result::rename_result::RenameResult;
+use crate::data_model::result::restart_result::RestartResult;
+#[cfg(feature = "inventory")]
+use crate::obj_model::ansible::read_ansible_inventory;
+use crate::obj_model::archive::*;
+use crate::obj_model::artifact_url::ArtifactUrl;
+use crate::obj_model::audit::AuditSinkRef;
+use crate::obj_model::cancellation::{check_cancelled, is_cancelled, CancelScope, CancellationToken};
+use crate::obj_model::checksum::*;
+#[cfg(feature = "encryption")]
+use crate::obj_model::credential_store::CredentialStore;
+#[cfg(feature = "delta_sync")]
+use crate::obj_model::delta_sync::sync_tree;
+use crate::obj_model::docker_transport::DockerTransport;
+use crate::obj_model::env_expand::expand_env;
+use crate::obj_model::event_bus::EventBus;
+use crate::obj_model::fan_out::fan_out;
+use crate::obj_model::inventory::Inventory;
+#[cfg(feature = "inventory")]
+use crate::obj_model::inventory::read_inventory_spec;
+use crate::obj_model::jump_host::{local_tunnel, JumpHost};
+use crate::obj_model::known_hosts::*;
+#[cfg(feature = "kubernetes")]
+use crate::obj_model::kubernetes_transport::KubernetesTransport;
+use crate::obj_model::limiter::Limiter;
+use crate::obj_model::local_transport::LocalTransport;
+use crate::obj_model::log_stream::{LogStream, LOG_STREAM_BACKLOG};
+use crate::obj_model::net::*;
 use crate::obj_model::node::Node;
-use log::error;
-use log::info;
+use crate::obj_model::node_builder::NodeBuilder;
+use crate::obj_model::node_lock::NodeLock;
+use crate::obj_model::node_pattern::NodePattern;
+use crate::obj_model::operation::{current_operation_id, OperationScope};
+use crate::obj_model::port_forward::PortForward;
+use crate::obj_model::secrets::{SecretsProviderRef, SECRET_PREFIX};
+use crate::obj_model::shell::{is_env_name, is_shell_safe, shell_quote};
+use crate::obj_model::ssh_config::read_ssh_config;
+use crate::obj_model::systemd;
+use crate::obj_model::stream_reader::LineSink;
+use crate::obj_model::tag_expr::{is_valid_tag, TagExpr};
+use crate::obj_model::template::*;
+use crate::obj_model::transport::{ConnectorRef, SshTransport, Transport};
+use crate::obj_model::unix;
+use crate::obj_model::windows::{self, ps_escape, ps_quote};
+use tracing::{error, info, info_span};
 use ssh2::Session;
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::Write;
-use std::io::{BufReader, Read};
-use std::net::TcpStream;
-use std::path::Path;
+use uuid::Uuid;
+use std::env;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_CONNECT_TIMEOUT: u64 = 30;
+const DEFAULT_HANDSHAKE_TIMEOUT: u64 = 30;
+const DEFAULT_KEEPALIVE_INTERVAL: u64 = 30;
+const DEFAULT_HEALTH_TIMEOUT: u64 = 30;
+const HEALTH_POLL_INTERVAL_MS: u64 = 1000;
+pub const DEFAULT_MAX_WORKERS: usize = 8;
+const DEFAULT_REMOTE_TMP_DIR: &str = "/tmp";
+/* Archives the artifact cache on a node holds before the least recently used go */
+const DEFAULT_ARTIFACTS_KEPT: usize = 5;
+/* Seconds a stopped instance gets to exit on StopSignal before SIGKILL */
+const DEFAULT_STOP_GRACE_PERIOD: u64 = 10;
+const DEFAULT_STOP_SIGNAL: &str = "TERM";
+const STOP_SIGNALS: [&str; 6] = ["TERM", "INT", "HUP", "QUIT", "USR1", "USR2"];
+const RESOURCE_SAMPLE_SECS: u64 = 1;
+
+/* Receives remote output line by line: node name, stream, line */
+pub type OutputCallback = Arc<dyn Fn(&str, OutputStream, &str) + Send + Sync>;
+
+/* Receives upload progress: node name, bytes sent, total bytes */
+pub type ProgressCallback = Arc<dyn Fn(&str, u64, u64) + Send + Sync>;
+
+pub type DeployProgressMap = Arc<Mutex<HashMap<String, DeployProgress>>>;
 
 pub struct NodePool {
     pub nodes: HashMap<String, Node>,
     pub instances: HashMap<String, Instance>,
     pub str_params: HashMap<String, String>,
+    pub retry_policy: RetryPolicy,
+    pub output_callback: Option<OutputCallback>,
+    pub progress_callback: Option<ProgressCallback>,
+    pub deploy_progress: DeployProgressMap,
+    pub max_workers: usize,
+    pub secrets_provider: Option<SecretsProviderRef>,
+    pub audit_sink: Option<AuditSinkRef>,
+    pub event_bus: EventBus,
+    pub connector: Option<ConnectorRef>,
+    pub limiter: Limiter,
 }
 
 unsafe impl Send for NodePool {}
@@ -58,20 +173,203 @@ impl NodePool {
             nodes: HashMap::new(),
             instances: HashMap::new(),
             str_params: HashMap::new(),
+            retry_policy: RetryPolicy::new(),
+            output_callback: None,
+            progress_callback: None,
+            deploy_progress: Arc::new(Mutex::new(HashMap::new())),
+            max_workers: DEFAULT_MAX_WORKERS,
+            secrets_provider: None,
+            audit_sink: None,
+            event_bus: EventBus::new(),
+            connector: None,
+            limiter: Limiter::new(),
         };
     }
 
+    /* Saves the inventory as JSON; sessions aren't persisted */
+    pub fn save(&self, path: &Path) -> Result<(), DeltaError> {
+        return self.to_inventory().write(path);
+    }
+
+    /* Sealed inventories must go through load_encrypted() */
+    pub fn load(path: &Path) -> Result<NodePool, DeltaError> {
+        let inventory = Inventory::read(path)?;
+        if inventory.is_sealed() {
+            return Err(DeltaError::Crypto(format!("{}: credentials are encrypted", path.display())));
+        }
+
+        return Ok(NodePool::from_inventory_data(inventory));
+    }
+
+    /* Like save(), with passwords and key passphrases encrypted by the store */
+    #[cfg(feature = "encryption")]
+    pub fn save_encrypted(&self, path: &Path, store: &CredentialStore) -> Result<(), DeltaError> {
+        let mut inventory = self.to_inventory();
+        store.seal(&mut inventory)?;
+        return inventory.write(path);
+    }
+
+    #[cfg(feature = "encryption")]
+    pub fn load_encrypted(path: &Path, store: &CredentialStore) -> Result<NodePool, DeltaError> {
+        let mut inventory = Inventory::read(path)?;
+        store.unseal(&mut inventory)?;
+        return Ok(NodePool::from_inventory_data(inventory));
+    }
+
+    fn from_inventory_data(inventory: Inventory) -> NodePool {
+        let mut pool = NodePool::new();
+        pool.str_params = inventory.str_params;
+        if let Some(policy) = inventory.retry_policy {
+            pool.retry_policy = policy;
+        }
+        if let Some(limits) = inventory.limits {
+            pool.limiter.set_limits(limits);
+        }
+        pool.nodes = inventory.nodes.into_iter().collect();
+        return pool;
+    }
+
+    /* Builds a pool from a native YAML inventory, see InventorySpec */
+    #[cfg(feature = "inventory")]
+    pub fn from_inventory(path: &Path) -> Result<NodePool, DeltaError> {
+        let spec = read_inventory_spec(path)?;
+        let mut pool = NodePool::new();
+        pool.str_params = spec.defaults.to_params();
+
+        for (name, node) in &spec.nodes {
+            let mut params = HashMap::new();
+            for group in &node.groups {
+                params.extend(spec.groups[group].to_params());
+            }
+            params.extend(node.settings().to_params());
+
+            pool.add(name.clone(), node.fqdn.clone(), params);
+            let tags = &mut pool.nodes.get_mut(name).unwrap().tags;
+            tags.extend(node.groups.iter().cloned());
+            tags.extend(node.tags.iter().cloned());
+        }
+
+        return Ok(pool);
+    }
+
+    pub fn to_inventory(&self) -> Inventory {
+        return Inventory {
+            str_params: self.str_params.clone(),
+            retry_policy: Some(self.retry_policy.clone()),
+            limits: Some(self.limiter.limits()),
+            nodes: self.nodes.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        };
+    }
+
+    /* Like try_get_node_param(), but an unresolvable value reads as empty */
     pub fn get_node_param(&self, node: &Node, param: NodeParameters) -> String {
         let sparam = param.to_string();
-        if node.str_params.contains_key(&sparam) {
-            return node.str_params[&sparam].clone();
+        return self.try_get_node_param(node, param).unwrap_or_else(|e| {
+            error!("Failed to resolve {}: {}", sparam, scrub(&e.to_string()));
+            "".to_string()
+        });
+    }
+
+    /*
+     * Node value, else pool value, with ${ENV_VAR} references expanded.
+     * Credentials of the form "secret:<key>" come from the secrets provider.
+     */
+    pub fn try_get_node_param(&self, node: &Node, param: NodeParameters) -> Result<String, DeltaError> {
+        let sparam = param.to_string();
+        let value = match node.str_params.get(&sparam).or_else(|| self.str_params.get(&sparam)) {
+            Some(v) => expand_env(v)?,
+            None => return Ok("".to_string()),
+        };
+
+        let credential = matches!(param, NodeParameters::Username
+                                         | NodeParameters::Password
+                                         | NodeParameters::KeyPassphrase
+                                         | NodeParameters::JumpUsername
+                                         | NodeParameters::JumpPassword);
+        let value = match value.strip_prefix(SECRET_PREFIX).filter(|_| credential) {
+            Some(key) => match &self.secrets_provider {
+                Some(provider) => provider.resolve(key)?.into_inner(),
+                None => return Err(DeltaError::InvalidParameter(sparam, "no secrets provider set".to_string())),
+            },
+            None => value,
+        };
+
+        /* Resolved credentials are masked wherever logs could echo them */
+        if NodeParameters::is_sensitive(&sparam) {
+            register_secret(&value);
+        }
+
+        return Ok(value);
+    }
+
+    /* Unset reads as the default; anything but a port number 1-65535 is an error */
+    pub fn get_port(&self, node: &Node, param: NodeParameters, default: u16) -> Result<u16, DeltaError> {
+        let value = self.try_get_node_param(node, param.clone())?;
+        if value.trim().is_empty() {
+            return Ok(default);
         }
 
-        if self.str_params.contains_key(&sparam) {
-            return self.str_params[&sparam].clone();
+        return match value.trim().parse::<u16>() {
+            Ok(port) if port > 0 => Ok(port),
+            _ => Err(DeltaError::InvalidParameter(param.to_string(), value)),
+        };
+    }
+
+    /* Accepts yes/no, true/false, on/off and 1/0; unset reads as the default */
+    pub fn get_bool(&self, node: &Node, param: NodeParameters, default: bool) -> Result<bool, DeltaError> {
+        let value = self.try_get_node_param(node, param.clone())?;
+        return match value.trim().to_lowercase().as_str() {
+            "" => Ok(default),
+            "yes" | "true" | "on" | "1" => Ok(true),
+            "no" | "false" | "off" | "0" => Ok(false),
+            _ => Err(DeltaError::InvalidParameter(param.to_string(), value)),
+        };
+    }
+
+    /*
+     * A path without the trailing slash; unset reads as the default. Paths
+     * end up quoted in remote shell commands, so quotes and control
+     * characters are rejected.
+     */
+    pub fn get_path(&self, node: &Node, param: NodeParameters, default: &str) -> Result<PathBuf, DeltaError> {
+        let value = self.try_get_node_param(node, param.clone())?;
+        if !is_shell_safe(&value) {
+            return Err(DeltaError::InvalidParameter(param.to_string(), value));
         }
 
-        return "".to_string();
+        let path = value.trim();
+        let trimmed = path.trim_end_matches('/');
+        return Ok(PathBuf::from(match (path.is_empty(), trimmed.is_empty()) {
+            (true, _) => default,
+            (false, true) => "/",
+            (false, false) => trimmed,
+        }));
+    }
+
+    pub fn set_secrets_provider(&mut self, provider: Option<SecretsProviderRef>) {
+        self.secrets_provider = provider;
+    }
+
+    /* Reach nodes through the connector's transports instead of SSH; None restores SSH */
+    pub fn set_connector(&mut self, connector: Option<ConnectorRef>) {
+        self.connector = connector;
+    }
+
+    pub fn set_audit_sink(&mut self, sink: Option<AuditSinkRef>) {
+        self.audit_sink = sink;
+    }
+
+    pub fn subscribe(&self) -> Receiver<PoolEvent> {
+        return self.event_bus.subscribe();
+    }
+
+    pub fn limits(&self) -> PoolLimits {
+        return self.limiter.limits();
+    }
+
+    /* Shared with the pools split off for a batch, so it holds across concurrent batches */
+    pub fn set_limits(&self, limits: PoolLimits) {
+        self.limiter.set_limits(limits);
     }
 
     pub fn add(
@@ -90,6 +388,13 @@ impl NodePool {
             Node {
                 fqdn: fqdn.clone(),
                 str_params: node_params.clone(),
+                retry_policy: None,
+                tags: HashSet::new(),
+                restart_policies: HashMap::new(),
+                last_error: None,
+                platform_kind: PlatformKind::Unix,
+                os: Platform::Linux,
+                temp_dir: "".to_string(),
             },
         );
 
@@ -97,342 +402,3589 @@ impl NodePool {
         return AddResult::Ok;
     }
 
-    pub fn is_connected(&self, name: String) -> ConnStatus {
-        if self.instances.contains_key(&name) {
-            return self.instances[&name].conn_status.clone();
+    /* Adds a node described by a NodeBuilder; invalid settings are an error */
+    pub fn add_node(&mut self, builder: NodeBuilder) -> Result<AddResult, DeltaError> {
+        let (name, node) = builder.build()?;
+        if self.nodes.contains_key(&name) {
+            error!("Node already exists: {}", name);
+            return Ok(AddResult::NodeAlreadyExists);
         }
-        return ConnStatus::new(false);
-    }
 
-    pub fn is_alive(&self, name: String) -> ConnAliveStatus {
-        let mut conn_alive_status = ConnAliveStatus::new();
+        info!("Added node {}", node.fqdn);
+        self.nodes.insert(name, node);
+        return Ok(AddResult::Ok);
+    }
 
-        let mut subj_alive_status = SubjectAliveStatus::new();
-        if self.instances.contains_key(&name) {
-            let inst = &self.instances[&name];
-            let ssh_session = &inst.ssh_session.as_ref().unwrap();
-            let pid = self.execute(ssh_session, "cat /tmp/visao/pid".to_string());
-            if pid.trim().parse::<u64>().is_ok() {
-                let runs = self.execute(ssh_session, format!("kill -0 {} && echo runs", pid.trim()));
-                if runs.contains("runs")
-                {
-                    let bind_addr = self.execute(ssh_session, "cat /tmp/visao/bind_addr".to_string());
-                    let bind_port = self.execute(ssh_session, "cat /tmp/visao/bind_port".to_string());
-
-                    if bind_port.trim().parse::<u16>().is_ok() {
-                        subj_alive_status.alive = true;
-                        subj_alive_status.bind_addr = bind_addr.trim().to_string();
-                        subj_alive_status.bind_port = bind_port.trim().parse::<u16>().unwrap();
-                    }
-                }
-            }
+    /* Adds every concrete Host of an OpenSSH client config, see ssh_config::default_path() */
+    pub fn import_ssh_config(&mut self, path: &Path) -> Result<HashMap<String, AddResult>, DeltaError> {
+        let mut results = HashMap::new();
+        for host in read_ssh_config(path)? {
+            let result = self.add(host.name.clone(), host.fqdn, host.params);
+            results.insert(host.name, result);
         }
-
-        conn_alive_status.subjects.insert(DeploySubject::Sa, subj_alive_status);
-        return conn_alive_status;
+        return Ok(results);
     }
 
-    pub fn connect(&mut self, name: String) -> ConnectResult {
-        if !self.nodes.contains_key(&name) {
-            error!("Node doesn't exist: {}", name);
-            return ConnectResult::NodeNotFound;
+    /* Adds the hosts of an Ansible INI or YAML inventory, tagged with their groups */
+    #[cfg(feature = "inventory")]
+    pub fn import_ansible(&mut self, path: &Path) -> Result<HashMap<String, AddResult>, DeltaError> {
+        let mut results = HashMap::new();
+        for host in read_ansible_inventory(path)? {
+            let result = self.add(host.name.clone(), host.fqdn, host.params);
+            if result == AddResult::Ok {
+                self.nodes.get_mut(&host.name).unwrap().tags = host.tags;
+            }
+            results.insert(host.name, result);
         }
+        return Ok(results);
+    }
 
+    pub fn is_connected(&self, name: String) -> ConnStatus {
         if self.instances.contains_key(&name) {
-            self.instances.remove(&name);
-        }
-
-        let node = &self.nodes[&name];
-        let tcp = TcpStream::connect(node.fqdn.clone()).unwrap();
-        let mut sess = Session::new().unwrap();
-        sess.set_tcp_stream(tcp);
-        sess.handshake().unwrap();
-        let auth_result = sess.userauth_password(
-            &self.get_node_param(node, NodeParameters::Username),
-            &self.get_node_param(node, NodeParameters::Password),
-        );
-        match auth_result {
-            Ok(_r) => {}
-            Err(e) => {
-                error!("Credentials not accepted: {} (error '{}')", name, e);
-                return ConnectResult::NotAuthenticated;
-            }
+            return self.instances[&name].conn_status.clone();
         }
+        return ConnStatus::new(false);
+    }
 
-        if !sess.authenticated() {
-            error!("Failed to authenticate: {}", name);
-            return ConnectResult::NotAuthenticated;
-        }
+    pub fn list(&self) -> Vec<NodeSummary> {
+        let mut list: Vec<NodeSummary> = self.nodes.keys()
+            .filter_map(|name| self.summary(name.clone()))
+            .collect();
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        return list;
+    }
 
-        let plat = self.execute(&sess, "uname -a".to_string());
-        let mut inst = Instance::new_ssh(sess, true);
-        inst.conn_status.platform = plat;
-        self.instances.insert(name.clone(), inst);
+    pub fn summary(&self, name: String) -> Option<NodeSummary> {
+        let node = self.nodes.get(&name)?;
+        let conn_status = self.is_connected(name.clone());
+        let mut tags: Vec<String> = node.tags.iter().cloned().collect();
+        tags.sort();
 
-        info!("Connected node: {}", name);
-        return ConnectResult::Ok;
+        return Some(NodeSummary {
+            name,
+            fqdn: node.fqdn.clone(),
+            connected: conn_status.connected,
+            tags,
+            subjects: conn_status.subjects,
+            last_error: node.last_error.clone(),
+        });
     }
 
-    pub fn disconnect(&mut self, name: String) -> DisconnectResult {
+    /*
+     * Operations recently attempted on the node, oldest first. The history
+     * lives with the connection: a reconnect carries it over, while a node
+     * that never connected, or failed to reconnect, has none.
+     */
+    pub fn history(&self, name: String) -> Option<Vec<OperationRecord>> {
         if !self.nodes.contains_key(&name) {
-            error!("Node doesn't exist: {}", name);
-            return DisconnectResult::NodeNotFound;
+            return None;
         }
+        return Some(self.instances.get(&name).map(|inst| inst.history()).unwrap_or_default());
+    }
+
+    /* Alive status of every subject, each checked against its own pid and bind files */
+    pub fn is_alive(&mut self, name: String) -> ConnAliveStatus {
+        let mut conn_alive_status = ConnAliveStatus::new();
 
         if self.instances.contains_key(&name) {
-            self.instances.remove(&name);
+            let _ = self.ensure_connected(name.clone());
         }
 
-        info!("Disconnected node: {}", name);
-        return DisconnectResult::Ok;
-    }
+        for subject in DeploySubject::all() {
+            let subj_alive_status = self.subject_alive(&name, &subject);
+            conn_alive_status.subjects.insert(subject, subj_alive_status);
+        }
 
-    pub fn remove(&mut self, name: String) -> RemoveResult {
-        if !self.nodes.contains_key(&name) {
-            error!("Node doesn't exist: {}", name);
-            return RemoveResult::NodeNotFound;
+        conn_alive_status.last_checked = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs()).unwrap_or(0);
+        if let Some(inst) = self.instances.get(&name) {
+            inst.cache_alive(conn_alive_status.clone());
         }
+        return conn_alive_status;
+    }
 
-        self.nodes.remove(&name);
+    /*
+     * is_alive() without going to the node when the last result is at most
+     * max_age old; last_checked tells when it was taken. Any operation on
+     * the node since then forces a fresh check.
+     */
+    pub fn is_alive_cached(&mut self, name: String, max_age: Duration) -> ConnAliveStatus {
+        if let Some(status) = self.instances.get(&name).and_then(|inst| inst.cached_alive(max_age)) {
+            return status;
+        }
+        return self.is_alive(name);
+    }
 
+    /* Same as is_alive(), for one subject only */
+    pub fn is_subject_alive(&mut self, name: String, subject: DeploySubject) -> SubjectAliveStatus {
         if self.instances.contains_key(&name) {
-            self.instances.remove(&name);
+            let _ = self.ensure_connected(name.clone());
         }
+        return self.subject_alive(&name, &subject);
+    }
 
-        info!("Removed node: {}", name);
-        return RemoveResult::Ok;
+    fn subject_alive(&self, name: &str, subject: &DeploySubject) -> SubjectAliveStatus {
+        let sess = match self.session(name) {
+            Ok(sess) => sess,
+            Err(_e) => return SubjectAliveStatus::new(),
+        };
+        let node = &self.nodes[name];
+        return self.check_alive(sess, node, subject, self.get_command_timeout(node)).unwrap_or_else(|e| {
+            error!("Failed to check instance: {} ({})", name, scrub(&e.to_string()));
+            SubjectAliveStatus::new()
+        });
     }
 
-    pub fn deploy(&mut self, name: String, subject: DeploySubject) -> DeployResult {
-        if subject == DeploySubject::Delta {
-            return DeployResult::InvalidArgument;
+    /* The instance counts as alive when the alive command succeeds */
+    fn check_alive(&self, sess: &dyn Transport, node: &Node, subject: &DeploySubject,
+                   timeout: Option<Duration>) -> Result<SubjectAliveStatus, DeltaError> {
+        let mut subj_alive_status = SubjectAliveStatus::new();
+        /* Checked here, since get_remote_dir() would quietly fall back to the default */
+        self.get_path(node, subject.remote_dir_param(), subject.default_remote_dir())?;
+        let remote_dir = self.get_remote_dir(node, subject);
+
+        let windows = self.is_windows(node);
+        let mode = self.get_run_mode(node);
+        let default = match (windows, mode.is_systemd()) {
+            (true, _) => windows::DEFAULT_ALIVE_TEMPLATE.to_string(),
+            (false, true) => systemd::alive_command(&mode, subject),
+            (false, false) => DEFAULT_ALIVE_TEMPLATE.to_string(),
+        };
+        let alive = self.command_context(node, subject).render(
+            &self.command_template(node, subject.alive_command_param(), &default));
+        let probe = match windows {
+            true => windows::powershell(&windows::alive_probe_script(&alive, &remote_dir)),
+            false => unix::alive_probe_script(&alive, &remote_dir),
+        };
+        let out = self.execute(sess, probe, timeout)?;
+        if !out.success() {
+            return Ok(subj_alive_status);
         }
 
-        // Deploy Sa
-
-        if !self.nodes.contains_key(&name) {
-            error!("Node doesn't exist: {}", name);
-            return DeployResult::NodeNotFound;
+        let mut bind_addr = "";
+        let mut bind_port = "";
+        for line in out.stdout.lines() {
+            if let Some(addr) = line.strip_prefix("bind_addr=") {
+                bind_addr = addr;
+            } else if let Some(port) = line.strip_prefix("bind_port=") {
+                bind_port = port;
+            }
         }
 
-        if !self.instances.contains_key(&name) {
-            error!("Node not connected: {}", name);
-            return DeployResult::NodeNotConnected;
+        if let Ok(port) = bind_port.trim().parse::<u16>() {
+            subj_alive_status.alive = true;
+            subj_alive_status.bind_addr = bind_addr.trim().to_string();
+            subj_alive_status.bind_port = port;
         }
 
-        let node = &self.nodes[&name];
-        let inst = &self.instances[&name];
+        return Ok(subj_alive_status);
+    }
 
-        let mut conn_status = inst.conn_status.clone();
-        let mut subject_st = conn_status.get_subject(subject.clone());
+    pub fn get_retry_policy(&self, node: &Node) -> RetryPolicy {
+        return match &node.retry_policy {
+            Some(p) => p.clone(),
+            None => self.retry_policy.clone(),
+        };
+    }
 
-        subject_st.deployed = false;
-        subject_st.deploy_archive_copied = false;
-        subject_st.deploy_archive_extracted = false;
-        subject_st.deploy_archive_tested = false;
+    pub fn set_node_retry_policy(&mut self, name: String, policy: Option<RetryPolicy>) -> bool {
+        return match self.nodes.get_mut(&name) {
+            Some(node) => {
+                node.retry_policy = policy;
+                true
+            }
+            None => false,
+        };
+    }
 
-        if !self.upload_file(
-            &inst.ssh_session.as_ref().unwrap(),
-            self.get_node_param(node, NodeParameters::Distr),
-            "/tmp/visao-archive.tar.xz".to_string(),
-        ) {
-            conn_status.set_subject(subject, subject_st);
-            self.set_state(name, conn_status);
-            return DeployResult::DeployCopyFailed;
+    /* None goes back to never restarting */
+    pub fn set_restart_policy(&mut self, name: String, subject: DeploySubject,
+                              policy: Option<RestartPolicy>) -> bool {
+        return match self.nodes.get_mut(&name) {
+            Some(node) => {
+                match policy {
+                    Some(p) => node.restart_policies.insert(subject, p),
+                    None => node.restart_policies.remove(&subject),
+                };
+                true
+            }
+            None => false,
+        };
+    }
+
+    pub fn tag(&mut self, name: String, tag: String) -> bool {
+        if !is_valid_tag(&tag) {
+            error!("Invalid tag: {}", tag);
+            return false;
         }
 
-        subject_st.deploy_archive_copied = true;
+        return match self.nodes.get_mut(&name) {
+            Some(node) => {
+                node.tags.insert(tag);
+                true
+            }
+            None => false,
+        };
+    }
 
-        if self.execute(
-            &inst.ssh_session.as_ref().unwrap(),
-            "tar xvf /tmp/visao-archive.tar.xz -C /tmp/visao > /dev/null 2> /dev/null && echo ok".to_string(),
-        ) == ""
-        {
-            conn_status.set_subject(subject, subject_st);
-            self.set_state(name, conn_status);
-            return DeployResult::DeployExtractionFailed;
-        }
+    pub fn untag(&mut self, name: String, tag: String) -> bool {
+        return match self.nodes.get_mut(&name) {
+            Some(node) => node.tags.remove(&tag),
+            None => false,
+        };
+    }
 
-        subject_st.deploy_archive_extracted = true;
+    /* Names of nodes whose tags satisfy expr, e.g. "prod && !canary" */
+    pub fn resolve_tags(&self, expr: &str) -> Result<Vec<String>, DeltaError> {
+        let expr = TagExpr::parse(expr)?;
+        let mut names: Vec<String> = self.nodes.iter()
+            .filter(|(_, node)| expr.matches(&node.tags))
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        return Ok(names);
+    }
 
-        if self.execute(
-            &inst.ssh_session.as_ref().unwrap(),
-            "/tmp/visao/bin/visao --version".to_string(),
-        ) == ""
-        {
-            conn_status.set_subject(subject, subject_st);
-            self.set_state(name, conn_status);
-            return DeployResult::DeployTestFailed;
-        }
+    /* Names matching a glob or "re:" regex; with_fqdn also tries the node's address */
+    pub fn select(&self, pattern: &str, with_fqdn: bool) -> Result<Vec<String>, DeltaError> {
+        let pattern = NodePattern::parse(pattern)?;
+        let mut names: Vec<String> = self.nodes.iter()
+            .filter(|(name, node)| pattern.matches(name) || (with_fqdn && pattern.matches(&node.fqdn)))
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        return Ok(names);
+    }
 
-        subject_st.deploy_archive_tested = true;
+    pub fn connect(&mut self, name: String) -> ConnectResult {
+        return self.connect_tracked(name).result;
+    }
 
-        subject_st.deployed = true;
-        conn_status.set_subject(subject, subject_st);
-        self.set_state(name, conn_status);
-        return DeployResult::Ok;
+    /* Same as connect(), also returning the id the attempt was logged and audited under */
+    pub fn connect_tracked(&mut self, name: String) -> OperationResult<ConnectResult> {
+        let op = OperationScope::enter();
+        let span = info_span!("connect", node = %name, op_id = %op.id());
+        let _entered = span.enter();
+        let result = self.connect_node(name);
+        return op.finish(result);
     }
 
-    pub fn run(&mut self, name: String, subject: DeploySubject) -> RunResult {
+    fn connect_node(&mut self, name: String) -> ConnectResult {
         if !self.nodes.contains_key(&name) {
             error!("Node doesn't exist: {}", name);
-            return RunResult::NodeNotFound;
+            return ConnectResult::NodeNotFound;
         }
 
-        if !self.instances.contains_key(&name) {
-            error!("Node not connected: {}", name);
-            return RunResult::NodeNotConnected;
+        let started = Instant::now();
+        let policy = self.get_retry_policy(&self.nodes[&name]);
+        let mut attempt = 1;
+        loop {
+            let result = self.connect_once(&name);
+            if !result.is_transient() || attempt >= policy.max_attempts {
+                if result != ConnectResult::Ok {
+                    self.record_error(&name, format!("connect: {:?}", result));
+                }
+                self.audit(&name, AuditAction::Connect, format!("attempts: {}", attempt),
+                           format!("{:?}", result), result == ConnectResult::Ok, started);
+                if result == ConnectResult::Ok {
+                    self.event_bus.publish(PoolEvent::NodeConnected { node: name });
+                }
+                return result;
+            }
+
+            let delay = policy.delay(attempt);
+            info!("Retrying connection to {} in {} ms (attempt {}/{})",
+                  name, delay.as_millis(), attempt + 1, policy.max_attempts);
+            thread::sleep(delay);
+            attempt += 1;
         }
+    }
 
-        let node = &self.nodes[&name];
-        let inst = &self.instances[&name];
+    fn connect_once(&mut self, name: &str) -> ConnectResult {
+        /* The history outlives the session, a failed attempt drops both */
+        let history = self.instances.remove(name).map(|inst| inst.history).unwrap_or_default();
 
-        let mut conn_status = inst.conn_status.clone();
-        let mut subject_st = conn_status.get_subject(subject.clone());
+        let (conn_method, transport, address) = match self.open_transport(name) {
+            Ok(t) => t,
+            Err(result) => return result,
+        };
 
-        subject_st.running = false;
+        let handshake_timeout = self.get_timeout(&self.nodes[name], NodeParameters::HandshakeTimeout,
+                                                 DEFAULT_HANDSHAKE_TIMEOUT);
+        let unix_facts = match self.execute(transport.as_ref(), unix::facts_script(), Some(handshake_timeout)) {
+            Ok(out) => out,
+            Err(e) => {
+                error!("Failed to detect platform: {} (error '{}')", name, e);
+                return ConnectResult::ConnectionFailed;
+            }
+        };
+        let unix_kind = || {
+            let os = unix_facts.stdout.lines().find_map(|l| l.trim().strip_prefix("os=")).unwrap_or("");
+            return (PlatformKind::Unix, NodeFacts::parse(Platform::from_uname(os), &unix_facts.stdout), "".to_string());
+        };
+        let (kind, facts, temp_dir) = match unix_facts.success() {
+            true => unix_kind(),
+            false => match self.detect_windows(transport.as_ref(), handshake_timeout) {
+                Some((version, temp_dir)) => {
+                    (PlatformKind::Windows, self.windows_facts(transport.as_ref(), &version, handshake_timeout), temp_dir)
+                }
+                None => unix_kind(),
+            },
+        };
+        let os = facts.os.clone();
+        let mut inst = Instance::new(conn_method, transport, true);
+        inst.history = history;
+        inst.conn_status.facts = facts;
+        inst.conn_status.platform_kind = kind.clone();
+        inst.conn_status.os = os.clone();
+        inst.conn_status.address = address;
+        self.instances.insert(name.to_string(), inst);
+        if let Some(node) = self.nodes.get_mut(name) {
+            node.platform_kind = kind;
+            node.os = os.clone();
+            node.temp_dir = temp_dir;
+        }
 
-        /* Infer bind addr/bind port */
+        info!("Connected node: {} ({})", name, os);
+        return ConnectResult::Ok;
+    }
 
-        /* Kill existing instance, if exists */
-        let _exec_result = self.execute(
-            &inst.ssh_session.as_ref().unwrap(),
-            "/bin/bash -c 'test -f /tmp/visao/pid && test $(cat /tmp/visao/pid) -gt 0 && kill $(cat /tmp/visao/pid)'".to_string());
+    /* Windows has no uname; cmd is there whichever shell OpenSSH starts */
+    fn detect_windows(&self, sess: &dyn Transport, timeout: Duration) -> Option<(String, String)> {
+        let out = self.execute(sess, windows::DETECT_COMMAND.to_string(), Some(timeout)).ok()?;
+        if !out.success() {
+            return None;
+        }
+        return windows::parse_detect(&out.stdout);
+    }
 
-        /* Run new instance */
-        let conn_params = self.infer_conn_params(node);
-        let mut commands = Vec::<String>::new();
-        commands.push("/tmp/visao/bin/visao --server 'tcp://".to_owned() + &conn_params.0 + ":" + &conn_params.1 + "' < /dev/null > /dev/null 2> /dev/null &");
-        commands.push("echo $! > /tmp/visao/pid".to_string());
-        commands.push("echo ".to_owned() + &conn_params.0 + " > /tmp/visao/bind_addr");
-        commands.push("echo ".to_owned() + &conn_params.1 + " > /tmp/visao/bind_port");
-        commands.push("sleep 4".to_string());
-        commands.push("kill -0 \"$(cat /tmp/visao/pid)\" && echo pid \"$(cat /tmp/visao/pid)\"".to_string());
-
-        let exec_result = self.execute_vec(
-            &inst.ssh_session.as_ref().unwrap(),
-            commands);
-
-        /* Check result */
-        if !exec_result.contains("pid") {
-            conn_status.set_subject(subject, subject_st);
-            self.set_state(name, conn_status);
-            return RunResult::RunFailed;
+    /* Where PowerShell can't tell, the kernel still comes from the ver banner */
+    fn windows_facts(&self, sess: &dyn Transport, version: &str, timeout: Duration) -> NodeFacts {
+        let stdout = match self.execute(sess, windows::powershell(windows::FACTS_SCRIPT), Some(timeout)) {
+            Ok(out) if out.success() => out.stdout,
+            _ => "".to_string(),
+        };
+        let mut facts = NodeFacts::parse(Platform::Windows, &stdout);
+        if facts.kernel.is_empty() {
+            facts.kernel = version.to_string();
         }
+        return facts;
+    }
 
-        subject_st.running = true;
-        conn_status.set_subject(subject, subject_st);
-        self.set_state(name, conn_status);
-        return RunResult::Ok;
+    /* Transport to the node from the pool's connector, else the one the node's Transport param picks */
+    fn open_transport(&self, name: &str) -> Result<(ConnMethod, Box<dyn Transport>, String), ConnectResult> {
+        let node = &self.nodes[name];
+        if let Some(connector) = &self.connector {
+            let transport = connector.connect(name, node)?;
+            return Ok((ConnMethod::Custom, transport, node.fqdn.clone()));
+        }
+
+        return match self.get_conn_method(node) {
+            ConnMethod::Local => Ok((ConnMethod::Local, Box::new(LocalTransport::new()), "localhost".to_string())),
+            ConnMethod::Docker => {
+                /* The container defaults to the node's address, so a node can simply be named after it */
+                let mut container = self.get_node_param(node, NodeParameters::DockerContainer);
+                if container.is_empty() {
+                    container = node.fqdn.clone();
+                }
+                let user = self.get_node_param(node, NodeParameters::DockerUser);
+                Ok((ConnMethod::Docker, Box::new(DockerTransport::open(&container, &user)?), container))
+            }
+            ConnMethod::Kubernetes => self.open_kubernetes(node),
+            _ => {
+                let (sess, address) = self.open_session(name)?;
+                Ok((ConnMethod::Ssh, Box::new(SshTransport::new(sess)), address))
+            }
+        };
     }
 
-    fn upload_file(&self, sess: &Session, local_path: String, remote_path: String) -> bool {
-        let file = File::open(local_path);
-        let file = match file {
-            Ok(f) => f,
+    /* Authenticated session to the node and the address it went to, not tied to any instance */
+    fn open_session(&self, name: &str) -> Result<(Session, String), ConnectResult> {
+        let node = &self.nodes[name];
+        let (username, password, identity, passphrase) = match self.get_credentials(node) {
+            Ok(c) => c,
             Err(e) => {
-                error!("Failed to open local file: {}", e);
-                return false;
+                error!("Failed to resolve credentials: {} ({})", name, scrub(&e.to_string()));
+                return Err(ConnectResult::InvalidParameter);
             }
         };
 
-        let metadata = file.metadata();
-        let metadata = match metadata {
-            Ok(m) => m,
+        let (host, port) = match self.get_ssh_address(node) {
+            Ok(a) => a,
             Err(e) => {
-                error!("Failed to get file metadata: {}", e);
-                return false;
+                error!("Invalid node address: {} ({})", name, e);
+                return Err(ConnectResult::InvalidParameter);
             }
         };
-        let file_size = metadata.len();
-
-        let remote_file = sess.scp_send(Path::new(&remote_path), 0o644, file_size, None);
-
-        match remote_file {
-            Ok(ref _n) => {}
-            Err(_e) => {
-                return false;
+        let jump_fqdn = self.get_node_param(node, NodeParameters::JumpHost);
+        let connect_timeout = self.get_timeout(node, NodeParameters::ConnectTimeout,
+                                               DEFAULT_CONNECT_TIMEOUT);
+        let handshake_timeout = self.get_timeout(node, NodeParameters::HandshakeTimeout,
+                                                 DEFAULT_HANDSHAKE_TIMEOUT);
+        let mut address = format!("{} via {}", format_host_port(&host, port), jump_fqdn);
+        self.limiter.pace_connect(&host);
+        let mut sess = match Session::new() {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Failed to create session: {} (error '{}')", name, e);
+                return Err(ConnectResult::ConnectionFailed);
+            }
+        };
+        if jump_fqdn.is_empty() {
+            match connect_tcp(&host, port, connect_timeout) {
+                Ok(t) => {
+                    address = t.peer_addr().map(|a| a.to_string()).unwrap_or_else(|_| format_host_port(&host, port));
+                    sess.set_tcp_stream(t);
+                }
+                Err(e) if is_timeout(&e) => {
+                    error!("Connection timed out: {}", name);
+                    return Err(ConnectResult::Timeout);
+                }
+                Err(e) => {
+                    error!("Failed to connect: {} (error '{}')", name, e);
+                    return Err(ConnectResult::ConnectionFailed);
+                }
+            }
+        } else {
+            let jump_host = match self.get_jump_host(node, jump_fqdn) {
+                Ok(j) => j,
+                Err(e) => {
+                    error!("Failed to resolve jump host credentials: {} ({})", name, scrub(&e.to_string()));
+                    return Err(ConnectResult::InvalidParameter);
+                }
+            };
+            match jump_host.open_tunnel(&host, port) {
+                Some(t) => sess.set_tcp_stream(t),
+                None => {
+                    error!("Failed to reach node through jump host: {}", name);
+                    return Err(ConnectResult::JumpHostFailed);
+                }
             }
         }
-
-        let mut remote_file = remote_file.unwrap();
-        let mut reader = BufReader::new(file);
-        let mut buffer = vec![0; 4096];
-        loop {
-            let n = reader.read(&mut buffer);
-            match n {
-                Ok(_n) => {}
-                Err(_e) => {
-                    break;
+        /* zlib on the transport; only helps for artifacts that aren't compressed already */
+        sess.set_compress(self.get_node_param(node, NodeParameters::Compression) == "yes");
+        sess.set_timeout(handshake_timeout.as_millis() as u32);
+        match sess.handshake() {
+            Ok(_r) => {}
+            Err(e) if is_ssh_timeout(&e) => {
+                error!("Handshake timed out: {}", name);
+                return Err(ConnectResult::Timeout);
+            }
+            Err(e) => {
+                error!("Handshake failed: {} (error '{}')", name, e);
+                return Err(ConnectResult::ConnectionFailed);
+            }
+        }
+        sess.set_timeout(0);
+
+        let known_hosts = KnownHosts::from_param(
+            self.get_node_param(node, NodeParameters::KnownHostsFile));
+        match known_hosts.verify(&sess, &host, port) {
+            HostKeyCheck::Match => {}
+            HostKeyCheck::NotFound => {
+                if self.get_node_param(node, NodeParameters::StrictHostKeyChecking) == "yes" {
+                    error!("Host key unknown: {} ({})", name, KnownHosts::fingerprint(&sess));
+                    return Err(ConnectResult::HostKeyUnknown);
                 }
+                info!("Host key not in known hosts, accepting: {} ({})",
+                      name, KnownHosts::fingerprint(&sess));
             }
-
-            let n = n.unwrap();
-            if n == 0 {
-                break;
+            HostKeyCheck::Mismatch => {
+                error!("Host key verification failed: {} ({})", name, KnownHosts::fingerprint(&sess));
+                return Err(ConnectResult::HostKeyMismatch);
+            }
+            HostKeyCheck::Failure => {
+                error!("Host key could not be checked: {}", name);
+                return Err(ConnectResult::HostKeyCheckFailed);
+            }
+        }
+        let auth_result = if identity.is_empty() {
+            sess.userauth_password(&username, password.expose())
+        } else {
+            sess.userauth_pubkey_file(&username, None, Path::new(&identity),
+                                      Some(passphrase.expose().as_str()).filter(|p| !p.is_empty()))
+        };
+        match auth_result {
+            Ok(_r) => {}
+            Err(e) => {
+                error!("Credentials not accepted: {} (error '{}')", name, e);
+                return Err(ConnectResult::NotAuthenticated);
             }
+        }
 
-            let _ = remote_file.write_all(&buffer[..n]);
+        if !sess.authenticated() {
+            error!("Failed to authenticate: {}", name);
+            return Err(ConnectResult::NotAuthenticated);
         }
 
-        remote_file.send_eof().unwrap();
-        remote_file.wait_eof().unwrap();
-        remote_file.close().unwrap();
-        remote_file.wait_close().unwrap();
-        return true;
+        let keepalive = self.get_timeout(node, NodeParameters::KeepaliveInterval,
+                                         DEFAULT_KEEPALIVE_INTERVAL);
+        sess.set_keepalive(true, keepalive.as_secs() as u32);
+        return Ok((sess, address));
     }
 
-    fn execute(&self, sess: &Session, cmd: String) -> String {
-        let mut channel = sess.channel_session().unwrap();
-        channel.exec(&cmd).unwrap();
-        let mut s = String::new();
-        channel.read_to_string(&mut s).unwrap();
-        let _ = channel.wait_close();
+    pub fn probe(&mut self, name: String) -> ConnStatus {
+        let inst = match self.instances.get_mut(&name) {
+            Some(i) => i,
+            None => return ConnStatus::new(false),
+        };
+
+        let alive = match inst.transport.as_ref() {
+            Some(transport) => transport.is_alive(),
+            None => false,
+        };
+
+        if !alive && inst.conn_status.connected {
+            error!("Session is dead: {}", name);
+        }
+
+        inst.conn_status.connected = alive;
+        return inst.conn_status.clone();
+    }
+
+    /* Reconnects a node whose session has dropped, keeping its subject state */
+    pub fn ensure_connected(&mut self, name: String) -> ConnectResult {
+        if !self.nodes.contains_key(&name) {
+            error!("Node doesn't exist: {}", name);
+            return ConnectResult::NodeNotFound;
+        }
+
+        let mut subjects = HashMap::new();
+        if self.instances.contains_key(&name) {
+            if self.probe(name.clone()).connected {
+                return ConnectResult::Ok;
+            }
+
+            subjects = self.instances[&name].conn_status.subjects.clone();
+            info!("Reconnecting node: {}", name);
+            self.event_bus.publish(PoolEvent::ConnectionLost { node: name.clone() });
+        }
+
+        let result = self.connect(name.clone());
+        if result == ConnectResult::Ok {
+            if let Some(inst) = self.instances.get_mut(&name) {
+                inst.conn_status.subjects = subjects;
+            }
+        }
+
+        return result;
+    }
+
+    pub fn set_output_callback(&mut self, callback: Option<OutputCallback>) {
+        self.output_callback = callback;
+    }
+
+    pub fn set_progress_callback(&mut self, callback: Option<ProgressCallback>) {
+        self.progress_callback = callback;
+    }
+
+    /* Progress of the current or last deploy; the map is shared, so it can be read mid-deploy */
+    pub fn get_deploy_progress(&self, name: String) -> Option<DeployProgress> {
+        let progress = self.deploy_progress.lock().unwrap_or_else(|e| e.into_inner());
+        return progress.get(&name).cloned();
+    }
+
+    /* Runs an arbitrary command on a connected node, reporting output line by line */
+    pub fn execute_streaming(&mut self, name: String, cmd: String,
+                             callback: &LineSink) -> Result<ExecOutput, DeltaError> {
+        return self.execute_node(name, cmd, Some(callback));
+    }
+
+    /* Runs an arbitrary command on a connected node */
+    pub fn execute_command(&mut self, name: String, cmd: String) -> Result<ExecOutput, DeltaError> {
+        return self.execute_node(name, cmd, None);
+    }
+
+    /* execute_command() on up to max_workers nodes at a time */
+    pub fn execute_many(&mut self, names: Vec<String>, cmd: String) -> HashMap<String, Result<ExecOutput, DeltaError>> {
+        return self.fan_out_nodes(names, |name| Err(DeltaError::NodeNotFound(name.to_string())),
+                                  |pool, name| pool.execute_command(name, cmd.clone()));
+    }
+
+    /*
+     * execute_command() on every connected node whose name matches selector,
+     * a glob or "re:" regex as select() takes; nodes not connected are left
+     * out rather than connected to.
+     */
+    pub fn execute_on(&mut self, selector: &str, cmd: String)
+                      -> Result<HashMap<String, Result<ExecOutput, DeltaError>>, DeltaError> {
+        let names = self.select(selector, false)?.into_iter()
+            .filter(|name| self.instances.contains_key(name))
+            .collect();
+        return Ok(self.execute_many(names, cmd));
+    }
+
+    fn execute_node(&mut self, name: String, cmd: String,
+                    callback: Option<&LineSink>) -> Result<ExecOutput, DeltaError> {
+        let op = OperationScope::enter();
+        let span = info_span!("execute", node = %name, op_id = %op.id());
+        let _entered = span.enter();
+        if !self.nodes.contains_key(&name) {
+            return Err(DeltaError::NodeNotFound(name));
+        }
+
+        if self.instances.contains_key(&name) {
+            let _ = self.ensure_connected(name.clone());
+        }
+
+        let sess = self.session(&name)?;
+        let timeout = self.get_command_timeout(&self.nodes[&name]);
+        let started = Instant::now();
+        let result = sess.execute(&cmd, timeout, callback);
+
+        match &result {
+            Ok(out) => self.audit(&name, AuditAction::Execute, cmd.clone(),
+                                  format!("exit code {}", out.exit_code), out.success(), started),
+            Err(e) => self.audit(&name, AuditAction::Execute, cmd.clone(), e.to_string(), false, started),
+        }
+        return result;
+    }
+
+    /*
+     * Copies a file from one node to another through the controller. It is
+     * spooled to a local temporary file in between, so neither transport
+     * waits on the other; the destination is replaced, its directory has to
+     * exist. Returns the bytes copied.
+     */
+    pub fn copy_between(&mut self, src_node: String, src_path: String,
+                        dst_node: String, dst_path: String) -> Result<u64, DeltaError> {
+        let op = OperationScope::enter();
+        let span = info_span!("copy", src = %src_node, dst = %dst_node, op_id = %op.id());
+        let _entered = span.enter();
+        let spool = NodePool::spool_path();
+        let copied = self.spool_download(src_node.clone(), src_path.clone(), &spool)
+            .and_then(|_| self.spool_upload(dst_node.clone(), &spool, &format!("{}:{}", src_node, src_path),
+                                            dst_path.clone()));
+        let _ = fs::remove_file(&spool);
+        if let Ok(n) = &copied {
+            info!("Copied {} bytes from {}:{} to {}:{}", n, src_node, scrub(&src_path), dst_node, scrub(&dst_path));
+        }
+        return copied;
+    }
+
+    /*
+     * Copies the local_dir tree below remote_dir on the node, creating it as
+     * needed; files and directories keep their permission bits. Meant for
+     * bundles such as configuration that aren't worth packing into an
+     * archive first. Returns the bytes uploaded.
+     */
+    pub fn upload_dir(&mut self, name: String, local_dir: &Path, remote_dir: String) -> Result<u64, DeltaError> {
+        let _lock = self.lock_node(&name);
+        let op = OperationScope::enter();
+        let span = info_span!("upload_dir", node = %name, op_id = %op.id());
+        let _entered = span.enter();
+        if !self.nodes.contains_key(&name) {
+            return Err(DeltaError::NodeNotFound(name));
+        }
+        if !local_dir.is_dir() {
+            return Err(DeltaError::InvalidParameter("local_dir".to_string(), local_dir.display().to_string()));
+        }
+        let remote_dir = remote_dir.trim_end_matches('/').to_string();
+        if remote_dir.is_empty() || !is_shell_safe(&remote_dir) {
+            return Err(DeltaError::InvalidParameter("remote_dir".to_string(), remote_dir));
+        }
+
+        if self.instances.contains_key(&name) {
+            let _ = self.ensure_connected(name.clone());
+        }
+
+        let sess = self.session(&name)?;
+        let started = Instant::now();
+        let uploaded = self.upload_tree(&name, sess, &self.nodes[&name], local_dir, &remote_dir);
+        self.audit_upload(&name, &local_dir.display().to_string(), &remote_dir, &uploaded, started);
+        if let Ok(n) = &uploaded {
+            info!("Uploaded {} bytes below {}: {}", n, scrub(&remote_dir), name);
+        }
+        return uploaded;
+    }
+
+    /*
+     * Copies remote_path on the node into local_path, over SCP or SFTP as
+     * TransferMethod says. The data goes to a hidden file next to local_path
+     * that is renamed into place at the end, so a failed download leaves no
+     * half-written file. Returns the bytes copied.
+     */
+    pub fn download_file(&mut self, name: String, remote_path: String, local_path: &Path) -> Result<u64, DeltaError> {
+        let _lock = self.lock_node(&name);
+        let op = OperationScope::enter();
+        let span = info_span!("download", node = %name, op_id = %op.id());
+        let _entered = span.enter();
+        if !self.nodes.contains_key(&name) {
+            return Err(DeltaError::NodeNotFound(name));
+        }
+        if !is_shell_safe(&remote_path) {
+            return Err(DeltaError::InvalidParameter("remote_path".to_string(), remote_path));
+        }
+        let Some(file_name) = local_path.file_name() else {
+            return Err(DeltaError::InvalidParameter("local_path".to_string(), local_path.display().to_string()));
+        };
+        let partial = local_path.with_file_name(format!(".{}.part", file_name.to_string_lossy()));
+
+        if self.instances.contains_key(&name) {
+            let _ = self.ensure_connected(name.clone());
+        }
+
+        let sess = self.session(&name)?;
+        let method = TransferMethod::from_param(&self.get_node_param(&self.nodes[&name], NodeParameters::TransferMethod));
+        let started = Instant::now();
+        let downloaded = NodePool::download_to(sess, &remote_path, &partial, &method)
+            .and_then(|n| fs::rename(&partial, local_path).map(|_| n).map_err(DeltaError::from));
+        if downloaded.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+
+        let (outcome, success) = match &downloaded {
+            Ok(n) => (format!("{} bytes", n), true),
+            Err(e) => (e.to_string(), false),
+        };
+        self.audit(&name, AuditAction::Download, format!("{} -> {}", remote_path, local_path.display()),
+                   outcome, success, started);
+        return downloaded;
+    }
+
+    /* Local file copy_between() spools through */
+    pub(crate) fn spool_path() -> PathBuf {
+        return env::temp_dir().join(format!("delta-copy-{}", Uuid::new_v4()));
+    }
+
+    /* First half of copy_between(): remote_path of the node into local */
+    pub(crate) fn spool_download(&mut self, name: String, remote_path: String, local: &Path) -> Result<u64, DeltaError> {
+        let _lock = self.lock_node(&name);
+        if !self.nodes.contains_key(&name) {
+            return Err(DeltaError::NodeNotFound(name));
+        }
+        if !is_shell_safe(&remote_path) {
+            return Err(DeltaError::InvalidParameter("src_path".to_string(), remote_path));
+        }
+
+        if self.instances.contains_key(&name) {
+            let _ = self.ensure_connected(name.clone());
+        }
+
+        let sess = self.session(&name)?;
+        let method = TransferMethod::from_param(&self.get_node_param(&self.nodes[&name], NodeParameters::TransferMethod));
+        return NodePool::download_to(sess, &remote_path, local, &method);
+    }
+
+    fn download_to(sess: &dyn Transport, remote_path: &str, local: &Path,
+                   method: &TransferMethod) -> Result<u64, DeltaError> {
+        let mut writer = BufWriter::new(File::create(local)?);
+        let copied = sess.download(remote_path, &mut writer, method)?;
+        writer.flush()?;
+        return Ok(copied);
+    }
+
+    /* Second half of copy_between(): local into remote_path of the node, audited as coming from source */
+    pub(crate) fn spool_upload(&mut self, name: String, local: &Path, source: &str,
+                               remote_path: String) -> Result<u64, DeltaError> {
+        let _lock = self.lock_node(&name);
+        if !self.nodes.contains_key(&name) {
+            return Err(DeltaError::NodeNotFound(name));
+        }
+        if !is_shell_safe(&remote_path) {
+            return Err(DeltaError::InvalidParameter("dst_path".to_string(), remote_path));
+        }
+
+        if self.instances.contains_key(&name) {
+            let _ = self.ensure_connected(name.clone());
+        }
+
+        let sess = self.session(&name)?;
+        let file = File::open(local)?;
+        let size = file.metadata()?.len();
+        let method = TransferMethod::from_param(&self.get_node_param(&self.nodes[&name], NodeParameters::TransferMethod));
+        let started = Instant::now();
+        let _permit = self.limiter.upload();
+        let uploaded = sess.upload(&mut BufReader::new(file), &remote_path, 0o644, size, 0, &method, &mut |_| {});
+        self.audit_upload(&name, source, &remote_path, &uploaded, started);
+        return uploaded.map(|_| size);
+    }
+
+    /*
+     * Changes the address and/or merges params into the node's own ones; an
+     * empty value removes the param. Changing anything the SSH session was
+     * established with drops the live session, the next call reconnects.
+     */
+    pub fn update(&mut self, name: String, fqdn: Option<String>,
+                  params: HashMap<String, String>) -> UpdateResult {
+        if fqdn.as_deref() == Some("") {
+            return UpdateResult::InvalidArgument;
+        }
+
+        let node = match self.nodes.get_mut(&name) {
+            Some(n) => n,
+            None => {
+                error!("Node doesn't exist: {}", name);
+                return UpdateResult::NodeNotFound;
+            }
+        };
+
+        let mut reconnect = false;
+        if let Some(fqdn) = fqdn {
+            reconnect |= node.fqdn != fqdn;
+            node.fqdn = fqdn;
+        }
+
+        for (key, value) in params {
+            let changed = if value.is_empty() {
+                node.str_params.remove(&key).is_some()
+            } else {
+                node.str_params.insert(key.clone(), value.clone()) != Some(value)
+            };
+            reconnect |= changed && NodePool::is_connection_param(&key);
+        }
+
+        if reconnect && self.instances.remove(&name).is_some() {
+            info!("Connection parameters changed, dropped session: {}", name);
+        }
+
+        info!("Updated node: {}", name);
+        return UpdateResult::Ok;
+    }
+
+    fn is_connection_param(key: &str) -> bool {
+        return [
+            NodeParameters::Username,
+            NodeParameters::Password,
+            NodeParameters::IdentityFile,
+            NodeParameters::KeyPassphrase,
+            NodeParameters::KnownHostsFile,
+            NodeParameters::StrictHostKeyChecking,
+            NodeParameters::SshPort,
+            NodeParameters::JumpHost,
+            NodeParameters::JumpUsername,
+            NodeParameters::JumpPassword,
+            NodeParameters::ConnectTimeout,
+            NodeParameters::HandshakeTimeout,
+            NodeParameters::KeepaliveInterval,
+            NodeParameters::Compression,
+        ].iter().any(|p| p.to_string() == key);
+    }
+
+    /* Moves the node together with its live session and deploy progress */
+    pub fn rename(&mut self, old: String, new: String) -> RenameResult {
+        let _lock = self.lock_node(&old);
+        if self.nodes.contains_key(&new) {
+            error!("Node already exists: {}", new);
+            return RenameResult::NameAlreadyExists;
+        }
+
+        let node = match self.nodes.remove(&old) {
+            Some(n) => n,
+            None => {
+                error!("Node doesn't exist: {}", old);
+                return RenameResult::NodeNotFound;
+            }
+        };
+
+        self.nodes.insert(new.clone(), node);
+        if let Some(inst) = self.instances.remove(&old) {
+            self.instances.insert(new.clone(), inst);
+        }
+
+        let mut progress = self.deploy_progress.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(p) = progress.remove(&old) {
+            progress.insert(new.clone(), p);
+        }
+
+        info!("Renamed node {} to {}", old, new);
+        return RenameResult::Ok;
+    }
+
+    pub fn disconnect(&mut self, name: String) -> DisconnectResult {
+        if !self.nodes.contains_key(&name) {
+            error!("Node doesn't exist: {}", name);
+            return DisconnectResult::NodeNotFound;
+        }
+
+        if self.instances.remove(&name).is_some() {
+            self.event_bus.publish(PoolEvent::NodeDisconnected { node: name.clone() });
+        }
+
+        info!("Disconnected node: {}", name);
+        return DisconnectResult::Ok;
+    }
+
+    pub fn remove(&mut self, name: String) -> RemoveResult {
+        if !self.nodes.contains_key(&name) {
+            error!("Node doesn't exist: {}", name);
+            return RemoveResult::NodeNotFound;
+        }
+
+        self.nodes.remove(&name);
+
+        if self.instances.contains_key(&name) {
+            self.instances.remove(&name);
+        }
+
+        info!("Removed node: {}", name);
+        return RemoveResult::Ok;
+    }
+
+    pub fn deploy(&mut self, name: String, subject: DeploySubject) -> DeployResult {
+        return self.deploy_tracked(name, subject).result;
+    }
+
+    /* Same as deploy(), stopping with DeployResult::Cancelled once token is cancelled */
+    pub fn deploy_cancellable(&mut self, name: String, subject: DeploySubject,
+                              token: &CancellationToken) -> DeployResult {
+        let _scope = CancelScope::enter(token);
+        return self.deploy(name, subject);
+    }
+
+    /* Same as deploy(), also returning the id the deployment was logged and audited under */
+    pub fn deploy_tracked(&mut self, name: String, subject: DeploySubject) -> OperationResult<DeployResult> {
+        let _lock = self.lock_node(&name);
+        let op = OperationScope::enter();
+        let span = info_span!("deploy", node = %name, subject = %subject, op_id = %op.id());
+        let _entered = span.enter();
+        let started = Instant::now();
+        self.event_bus.publish(PoolEvent::DeployStarted { node: name.clone(), subject: subject.clone() });
+        let mut result = self.deploy_node(name.clone(), subject.clone());
+        if !result.is_ok() && is_cancelled() {
+            result = DeployResult::Cancelled;
+        }
+        self.audit(&name, AuditAction::Deploy, subject.to_string(),
+                   format!("{:?}", result), result.is_ok(), started);
+        self.event_bus.publish(PoolEvent::DeployFinished { node: name, subject, result: result.clone() });
+        return op.finish(result);
+    }
+
+    fn deploy_node(&mut self, name: String, subject: DeploySubject) -> DeployResult {
+        if !self.nodes.contains_key(&name) {
+            error!("Node doesn't exist: {}", name);
+            return DeployResult::NodeNotFound;
+        }
+
+        if let Err(e) = self.check_deploy_params(&self.nodes[&name], &subject) {
+            error!("Invalid deploy parameters: {} ({})", name, scrub(&e.to_string()));
+            self.record_error(&name, format!("deploy {}: {}", subject, e));
+            return DeployResult::InvalidArgument;
+        }
+
+        if self.instances.contains_key(&name) {
+            let _ = self.ensure_connected(name.clone());
+        }
+
+        let sess = match self.session(&name) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("{}", e);
+                self.record_error(&name, format!("deploy {}: {}", subject, e));
+                return DeployResult::NodeNotConnected;
+            }
+        };
+
+        let node = &self.nodes[&name];
+        let inst = &self.instances[&name];
+
+        let mut conn_status = inst.conn_status.clone();
+        let mut subject_st = conn_status.get_subject(subject.clone());
+        let was_deployed = subject_st.deployed;
+
+        subject_st.deployed = false;
+        subject_st.deploy_archive_copied = false;
+        subject_st.deploy_archive_extracted = false;
+        subject_st.deploy_archive_tested = false;
+        let prev_checksum = std::mem::take(&mut subject_st.checksum);
+        let versioned = self.is_versioned(node);
+
+        if was_deployed && !versioned {
+            /* Keep the current tree around so a broken deploy can be rolled back */
+            subject_st.prev_checksum = if self.backup_tree(&name, sess, node, &subject) {
+                prev_checksum.clone()
+            } else {
+                "".to_string()
+            };
+        }
+
+        self.update_progress(&name, |p| *p = DeployProgress::new(subject.clone()));
+
+        let result = self.deploy_steps(&name, sess, node, &subject, &mut subject_st, was_deployed);
+        if result.is_ok() {
+            if versioned {
+                subject_st.prev_checksum = prev_checksum;
+            }
+            subject_st.deployed = true;
+            self.update_progress(&name, |p| p.phase = DeployPhase::Finished);
+        } else {
+            self.update_progress(&name, |p| p.phase = DeployPhase::Failed);
+            self.record_error(&name, format!("deploy {}: {:?}", subject, result));
+        }
+
+        conn_status.set_subject(subject, subject_st);
+        self.set_state(name, conn_status);
+        return result;
+    }
+
+    /* Deploys to up to max_workers nodes at a time */
+    pub fn deploy_many(&mut self, names: Vec<String>, subject: DeploySubject) -> HashMap<String, DeployResult> {
+        return self.fan_out_nodes(names, |_| DeployResult::NodeNotFound,
+                                  |pool, name| pool.deploy(name, subject.clone()));
+    }
+
+    pub fn connect_all(&mut self) -> HashMap<String, ConnectResult> {
+        let names = self.nodes.keys().cloned().collect();
+        return self.fan_out_nodes(names, |_| ConnectResult::NodeNotFound,
+                                  |pool, name| pool.connect(name));
+    }
+
+    pub fn disconnect_all(&mut self) -> HashMap<String, DisconnectResult> {
+        let names = self.nodes.keys().cloned().collect();
+        return self.fan_out_nodes(names, |_| DisconnectResult::NodeNotFound,
+                                  |pool, name| pool.disconnect(name));
+    }
+
+    fn fan_out_nodes<R, M, F>(&mut self, names: Vec<String>, missing: M, f: F) -> HashMap<String, R>
+    where
+        R: Send,
+        M: Fn(&str) -> R,
+        F: Fn(&mut NodePool, String) -> R + Sync,
+    {
+        let mut results = HashMap::new();
+        let mut pools = vec![];
+        for name in names {
+            match self.split_node(&name) {
+                Some(pool) => pools.push((name, pool)),
+                None => {
+                    error!("Node doesn't exist: {}", name);
+                    results.insert(name.clone(), missing(&name));
+                }
+            }
+        }
+
+        let done = fan_out(pools, self.max_workers, |(name, mut pool)| {
+            let _permit = pool.limiter.session();
+            let result = f(&mut pool, name.clone());
+            return (name, result, pool);
+        });
+
+        for (name, result, pool) in done {
+            self.merge_node(pool);
+            results.insert(name, result);
+        }
+
+        return results;
+    }
+
+    /* Moves a node into its own pool sharing this pool's settings, so it can be worked on in parallel */
+    fn split_node(&mut self, name: &str) -> Option<NodePool> {
+        let node = self.nodes.remove(name)?;
+        let mut pool = NodePool::new();
+        pool.str_params = self.str_params.clone();
+        pool.retry_policy = self.retry_policy.clone();
+        pool.output_callback = self.output_callback.clone();
+        pool.progress_callback = self.progress_callback.clone();
+        pool.deploy_progress = self.deploy_progress.clone();
+        pool.secrets_provider = self.secrets_provider.clone();
+        pool.audit_sink = self.audit_sink.clone();
+        pool.event_bus = self.event_bus.clone();
+        pool.connector = self.connector.clone();
+        pool.limiter = self.limiter.clone();
+        if let Some(inst) = self.instances.remove(name) {
+            pool.instances.insert(name.to_string(), inst);
+        }
+        pool.nodes.insert(name.to_string(), node);
+        return Some(pool);
+    }
+
+    fn merge_node(&mut self, mut pool: NodePool) {
+        self.nodes.extend(pool.nodes.drain());
+        self.instances.extend(pool.instances.drain());
+    }
+
+    pub fn rollback(&mut self, name: String, subject: DeploySubject) -> RollbackResult {
+        let _lock = self.lock_node(&name);
+        let op = OperationScope::enter();
+        let span = info_span!("rollback", node = %name, subject = %subject, op_id = %op.id());
+        let _entered = span.enter();
+        let started = Instant::now();
+        let result = self.rollback_node(name.clone(), subject.clone());
+        self.audit(&name, AuditAction::Rollback, subject.to_string(), format!("{:?}", result),
+                   result == RollbackResult::Ok, started);
+        return result;
+    }
+
+    fn rollback_node(&mut self, name: String, subject: DeploySubject) -> RollbackResult {
+        if !self.nodes.contains_key(&name) {
+            error!("Node doesn't exist: {}", name);
+            return RollbackResult::NodeNotFound;
+        }
+
+        if self.instances.contains_key(&name) {
+            let _ = self.ensure_connected(name.clone());
+        }
+
+        let sess = match self.session(&name) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("{}", e);
+                return RollbackResult::NodeNotConnected;
+            }
+        };
+
+        let node = &self.nodes[&name];
+        let timeout = self.get_command_timeout(node);
+        let remote_dir = self.get_remote_dir(node, &subject);
+
+        let versioned = self.is_versioned(node);
+
+        let dir = shell_quote(&remote_dir);
+        let prev_check = if versioned {
+            format!("test -L {}/previous", dir)
+        } else {
+            format!("test -d {}.prev", dir)
+        };
+        let has_prev = self.execute(sess, prev_check, timeout)
+            .map(|out| out.success())
+            .unwrap_or(false);
+        if !has_prev {
+            error!("No previous version to roll back to: {}", name);
+            return RollbackResult::NoPreviousVersion;
+        }
+
+        let _ = self.stop_instance(sess, node, &subject, timeout);
+
+        /* Swap trees, so rolling back twice returns to the new version */
+        let swap = if versioned {
+            format!("cur=$(readlink {0}/current) && ln -sfn \"$(readlink {0}/previous)\" {0}/current && \
+                     ln -sfn \"$cur\" {0}/previous", dir)
+        } else {
+            format!("rm -rf {0}.swap && mv {0} {0}.swap && mv {0}.prev {0} && \
+                     mv {0}.swap {0}.prev && rm -f {0}.prev/pid {0}.prev/bind_addr {0}.prev/bind_port",
+                    dir)
+        };
+        let swapped = match self.execute(sess, swap, timeout) {
+            Ok(out) => NodePool::check_output(&name, "swap trees", &out),
+            Err(e) => {
+                error!("Failed to swap trees: {} ({})", name, scrub(&e.to_string()));
+                false
+            }
+        };
+
+        if !swapped {
+            return RollbackResult::RollbackFailed;
+        }
+
+        let mut conn_status = self.instances[&name].conn_status.clone();
+        let mut subject_st = conn_status.get_subject(subject.clone());
+        std::mem::swap(&mut subject_st.checksum, &mut subject_st.prev_checksum);
+        if versioned {
+            subject_st.version = self.read_version_link(sess, &remote_dir, "current", timeout);
+        }
+        subject_st.deploy_archive_copied = true;
+        subject_st.deploy_archive_extracted = true;
+        subject_st.deploy_archive_tested = true;
+        subject_st.deployed = true;
+        subject_st.running = false;
+        conn_status.set_subject(subject.clone(), subject_st);
+        self.set_state(name.clone(), conn_status);
+
+        info!("Rolled back to previous version: {}", name);
+
+        let options = self.last_run_options(&name, &subject);
+        if self.run_with_options(name, subject, options) != RunResult::Ok {
+            return RollbackResult::RunFailed;
+        }
+
+        return RollbackResult::Ok;
+    }
+
+    pub fn upgrade(&mut self, name: String, subject: DeploySubject, new_distr: String) -> UpgradeResult {
+        let _lock = self.lock_node(&name);
+        if new_distr.is_empty() {
+            return UpgradeResult::InvalidArgument;
+        }
+
+        let param = subject.distr_param().to_string();
+        let old_distr = match self.nodes.get_mut(&name) {
+            Some(node) => node.str_params.insert(param.clone(), new_distr.clone()),
+            None => {
+                error!("Node doesn't exist: {}", name);
+                return UpgradeResult::NodeNotFound;
+            }
+        };
+
+        let result = self.upgrade_steps(&name, &subject);
+        if result != UpgradeResult::Ok {
+            /* Keep pointing at the archive that is actually deployed */
+            let node = self.nodes.get_mut(&name).unwrap();
+            match old_distr {
+                Some(d) => node.str_params.insert(param, d),
+                None => node.str_params.remove(&param),
+            };
+        } else {
+            info!("Upgraded to {}: {}", new_distr, name);
+        }
+
+        return result;
+    }
+
+    fn upgrade_steps(&mut self, name: &str, subject: &DeploySubject) -> UpgradeResult {
+        let deployed = self.deploy(name.to_string(), subject.clone());
+        if !deployed.is_ok() {
+            error!("Upgrade deploy failed: {} ({:?})", name, deployed);
+            return UpgradeResult::DeployFailed;
+        }
+
+        let options = self.last_run_options(name, subject);
+        if self.run_with_options(name.to_string(), subject.clone(), options) == RunResult::Ok
+            && self.wait_alive(name, subject) {
+            return UpgradeResult::Ok;
+        }
+
+        error!("New version never became alive, rolling back: {}", name);
+        return match self.rollback(name.to_string(), subject.clone()) {
+            RollbackResult::Ok => UpgradeResult::RolledBack,
+            r => {
+                error!("Rollback failed: {} ({:?})", name, r);
+                UpgradeResult::RollbackFailed
+            }
+        };
+    }
+
+    fn wait_alive(&self, name: &str, subject: &DeploySubject) -> bool {
+        let node = &self.nodes[name];
+        let deadline = Instant::now() + self.get_timeout(node, NodeParameters::HealthTimeout,
+                                                         DEFAULT_HEALTH_TIMEOUT);
+        let timeout = self.get_command_timeout(node);
+
+        loop {
+            let alive = match self.session(name) {
+                Ok(sess) => self.check_alive(sess, node, subject, timeout)
+                    .map(|st| st.alive)
+                    .unwrap_or(false),
+                Err(_e) => false,
+            };
+
+            if alive {
+                return true;
+            }
+
+            if Instant::now() >= deadline {
+                return false;
+            }
+
+            thread::sleep(Duration::from_millis(HEALTH_POLL_INTERVAL_MS));
+        }
+    }
+
+    pub fn undeploy(&mut self, name: String, subject: DeploySubject) -> UndeployResult {
+        let _lock = self.lock_node(&name);
+        let op = OperationScope::enter();
+        let span = info_span!("undeploy", node = %name, subject = %subject, op_id = %op.id());
+        let _entered = span.enter();
+        let started = Instant::now();
+        let result = self.undeploy_node(name.clone(), subject.clone());
+        self.audit(&name, AuditAction::Undeploy, subject.to_string(), format!("{:?}", result),
+                   result == UndeployResult::Ok, started);
+        return result;
+    }
+
+    fn undeploy_node(&mut self, name: String, subject: DeploySubject) -> UndeployResult {
+        if !self.nodes.contains_key(&name) {
+            error!("Node doesn't exist: {}", name);
+            return UndeployResult::NodeNotFound;
+        }
+
+        if self.instances.contains_key(&name) {
+            let _ = self.ensure_connected(name.clone());
+        }
+
+        let sess = match self.session(&name) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("{}", e);
+                return UndeployResult::NodeNotConnected;
+            }
+        };
+
+        let node = &self.nodes[&name];
+        let timeout = self.get_command_timeout(node);
+        let remote_dir = self.get_remote_dir(node, &subject);
+
+        let _ = self.stop_instance(sess, node, &subject, timeout);
+
+        let mode = self.get_run_mode(node);
+        if mode.is_systemd() {
+            match self.execute(sess, systemd::remove_script(&mode, &subject), timeout) {
+                Ok(out) if !out.success() => error!("Failed to remove unit: {} ({})", name, scrub(out.stderr.trim())),
+                Err(e) => error!("Failed to remove unit: {} ({})", name, scrub(&e.to_string())),
+                _ => {}
+            }
+        }
+
+        /* Archives of any format, the tree and its rollback copies */
+        let remove = match self.is_windows(node) {
+            true => windows::powershell(&windows::remove_script(&[
+                remote_dir.clone(), format!("{}.prev", remote_dir), format!("{}.swap", remote_dir),
+                format!("{}/{}-archive*", self.get_remote_tmp_dir(node), subject.binary()),
+            ])),
+            false => format!("rm -rf {0} {0}.prev {0}.swap {1}/{2}-archive*", shell_quote(&remote_dir),
+                             shell_quote(&self.get_remote_tmp_dir(node)), subject.binary()),
+        };
+        let removed = match self.execute(sess, remove, timeout) {
+            Ok(out) => NodePool::check_output(&name, "remove deployment", &out),
+            Err(e) => {
+                error!("Failed to remove deployment: {} ({})", name, scrub(&e.to_string()));
+                false
+            }
+        };
+
+        if !removed {
+            return UndeployResult::UndeployFailed;
+        }
+
+        let mut conn_status = self.instances[&name].conn_status.clone();
+        conn_status.set_subject(subject, SubjectStatus::new());
+        self.set_state(name.clone(), conn_status);
+
+        info!("Undeployed: {}", name);
+        return UndeployResult::Ok;
+    }
+
+    pub fn list_versions(&mut self, name: String, subject: DeploySubject) -> Result<InstalledVersions, DeltaError> {
+        if !self.nodes.contains_key(&name) {
+            return Err(DeltaError::NodeNotFound(name));
+        }
+
+        if self.instances.contains_key(&name) {
+            let _ = self.ensure_connected(name.clone());
+        }
+
+        let sess = self.session(&name)?;
+        let node = &self.nodes[&name];
+        let timeout = self.get_command_timeout(node);
+        let remote_dir = self.get_remote_dir(node, &subject);
+
+        let mut versions = InstalledVersions::new();
+        if !self.is_versioned(node) {
+            return Ok(versions);
+        }
+
+        let out = self.execute(sess, format!("ls -1 {}/versions 2> /dev/null", shell_quote(&remote_dir)), timeout)?;
+        versions.versions = out.stdout.lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect();
+        versions.versions.sort();
+        versions.current = self.read_version_link(sess, &remote_dir, "current", timeout);
+        versions.previous = self.read_version_link(sess, &remote_dir, "previous", timeout);
+        return Ok(versions);
+    }
+
+    pub fn activate(&mut self, name: String, subject: DeploySubject, version: String) -> ActivateResult {
+        let _lock = self.lock_node(&name);
+        let op = OperationScope::enter();
+        let span = info_span!("activate", node = %name, subject = %subject, op_id = %op.id());
+        let _entered = span.enter();
+        let started = Instant::now();
+        let result = self.activate_node(name.clone(), subject.clone(), version.clone());
+        self.audit(&name, AuditAction::Activate, format!("{} {}", subject, version), format!("{:?}", result),
+                   result == ActivateResult::Ok, started);
+        return result;
+    }
+
+    fn activate_node(&mut self, name: String, subject: DeploySubject, version: String) -> ActivateResult {
+        if !NodePool::is_valid_version(&version) {
+            return ActivateResult::InvalidArgument;
+        }
+
+        if !self.nodes.contains_key(&name) {
+            error!("Node doesn't exist: {}", name);
+            return ActivateResult::NodeNotFound;
+        }
+
+        if !self.is_versioned(&self.nodes[&name]) {
+            error!("Versioned deploys are disabled: {}", name);
+            return ActivateResult::InvalidArgument;
+        }
+
+        if self.instances.contains_key(&name) {
+            let _ = self.ensure_connected(name.clone());
+        }
+
+        let sess = match self.session(&name) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("{}", e);
+                return ActivateResult::NodeNotConnected;
+            }
+        };
+
+        let node = &self.nodes[&name];
+        let timeout = self.get_command_timeout(node);
+        let remote_dir = self.get_remote_dir(node, &subject);
+
+        let exists = self.execute(sess, format!("test -d {}", shell_quote(&format!("{}/versions/{}", remote_dir, version))), timeout)
+            .map(|out| out.success())
+            .unwrap_or(false);
+        if !exists {
+            error!("Version {} is not installed: {}", version, name);
+            return ActivateResult::VersionNotFound;
+        }
+
+        let _ = self.stop_instance(sess, node, &subject, timeout);
+
+        if !self.switch_version(&name, sess, &remote_dir, &version, timeout) {
+            return ActivateResult::ActivateFailed;
+        }
+
+        let mut conn_status = self.instances[&name].conn_status.clone();
+        let mut subject_st = conn_status.get_subject(subject.clone());
+        let was_running = subject_st.running;
+        subject_st.prev_checksum = std::mem::take(&mut subject_st.checksum);
+        subject_st.version = version.clone();
+        subject_st.deploy_archive_copied = true;
+        subject_st.deploy_archive_extracted = true;
+        subject_st.deploy_archive_tested = true;
+        subject_st.deployed = true;
+        subject_st.running = false;
+        conn_status.set_subject(subject.clone(), subject_st);
+        self.set_state(name.clone(), conn_status);
+
+        info!("Activated version {}: {}", version, name);
+
+        let options = self.last_run_options(&name, &subject);
+        if was_running && self.run_with_options(name, subject, options) != RunResult::Ok {
+            return ActivateResult::RunFailed;
+        }
+
+        return ActivateResult::Ok;
+    }
+
+    fn switch_version(&self, name: &str, sess: &dyn Transport, remote_dir: &str, version: &str,
+                      timeout: Option<Duration>) -> bool {
+        /* Links are relative, so the whole tree can be moved around */
+        return match self.execute(
+            sess,
+            format!("cd {0} && if [ -L current ]; then ln -sfn \"$(readlink current)\" previous; fi && \
+                     ln -sfn {1} current", shell_quote(remote_dir), shell_quote(&format!("versions/{}", version))),
+            timeout,
+        ) {
+            Ok(out) => NodePool::check_output(name, "switch version", &out),
+            Err(e) => {
+                error!("Failed to switch version: {} ({})", name, scrub(&e.to_string()));
+                false
+            }
+        };
+    }
+
+    fn read_version_link(&self, sess: &dyn Transport, remote_dir: &str, link: &str,
+                         timeout: Option<Duration>) -> String {
+        return match self.execute(sess, format!("readlink {}", shell_quote(&format!("{}/{}", remote_dir, link))), timeout) {
+            Ok(out) if out.success() => out.stdout.trim()
+                .rsplit('/')
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            _ => "".to_string(),
+        };
+    }
+
+    fn is_valid_version(version: &str) -> bool {
+        return !version.is_empty() && version != "." && version != ".."
+            && version.chars().all(|c| c.is_ascii_alphanumeric() || "._-".contains(c));
+    }
+
+    fn backup_tree(&self, name: &str, sess: &dyn Transport, node: &Node, subject: &DeploySubject) -> bool {
+        let remote_dir = self.get_remote_dir(node, subject);
+        return match self.execute(
+            sess,
+            format!("rm -rf {0}.prev && cp -a {0} {0}.prev && \
+                     rm -f {0}.prev/pid {0}.prev/bind_addr {0}.prev/bind_port",
+                    shell_quote(&remote_dir)),
+            self.get_command_timeout(node),
+        ) {
+            Ok(out) => NodePool::check_output(name, "back up previous version", &out),
+            Err(e) => {
+                error!("Failed to back up previous version: {} ({})", name, scrub(&e.to_string()));
+                false
+            }
+        };
+    }
+
+    fn deploy_steps(&self, name: &str, sess: &dyn Transport, node: &Node, subject: &DeploySubject,
+                    subject_st: &mut SubjectStatus, was_deployed: bool) -> DeployResult {
+        let timeout = self.get_command_timeout(node);
+        let distr = self.get_node_param(node, subject.distr_param());
+        let remote_dir = self.get_remote_dir(node, subject);
+
+        let fetch = match ArtifactUrl::is_url(&distr) {
+            true => match ArtifactUrl::parse(&distr) {
+                Ok(url) => Some(url),
+                Err(e) => {
+                    error!("Invalid distr URL: {} ({})", name, e);
+                    return DeployResult::InvalidArgument;
+                }
+            },
+            false => None,
+        };
+
+        let format = match &fetch {
+            Some(url) => match url.format() {
+                Some(f) => f,
+                None => {
+                    error!("Failed to tell archive format from URL: {}", url.url);
+                    return DeployResult::InvalidArgument;
+                }
+            },
+            None => match detect_format(Path::new(&distr)) {
+                Ok(f) => f,
+                Err(e) => {
+                    error!("Failed to detect archive format of {}: {}", distr, e);
+                    return DeployResult::InvalidArgument;
+                }
+            },
+        };
+
+        let mut local_checksum = "".to_string();
+        if let Some(url) = &fetch {
+            local_checksum = url.sha256.clone();
+        } else if format != ArchiveFormat::Directory {
+            local_checksum = match file_sha256(Path::new(&distr)) {
+                Ok(c) => c,
+                Err(e) => {
+                    error!("Failed to checksum archive {}: {}", distr, e);
+                    return DeployResult::DeployCopyFailed;
+                }
+            };
+        }
+
+        let versioned = self.is_versioned(node);
+        if versioned && self.is_windows(node) {
+            error!("Versioned deploys rely on symlinks, not available on Windows: {}", name);
+            return DeployResult::InvalidArgument;
+        }
+        let version = NodePool::version_stamp(&local_checksum);
+        let install_dir = if versioned {
+            format!("{}/versions/{}", remote_dir, version)
+        } else {
+            remote_dir.clone()
+        };
+
+        if !self.run_hook(name, sess, node, subject, NodeParameters::PreDeployCmd, &install_dir) {
+            return DeployResult::HookFailed;
+        }
+
+        let remote_archive = format!("{}/{}{}", self.get_artifact_cache_dir(node), local_checksum,
+                                     format.extension());
+        let mut reused = false;
+        let sync = fetch.is_none() && was_deployed && !versioned && format == ArchiveFormat::TarXz && !self.is_windows(node)
+            && self.get_bool(node, NodeParameters::SyncMode, false).unwrap_or(false);
+        if sync && self.sync_deploy(name, sess, &distr, &install_dir, timeout) {
+            subject_st.deploy_archive_copied = true;
+            subject_st.deploy_archive_extracted = true;
+            subject_st.checksum = local_checksum;
+        } else {
+            let result = match &fetch {
+                Some(url) => self.fetch_archive(name, sess, node, url, &format, &remote_archive, timeout),
+                None => self.copy_archive(name, sess, node, &distr, &format, &remote_archive,
+                                          &install_dir, &local_checksum, timeout),
+            };
+            reused = result == DeployResult::AlreadyDeployed;
+            if !result.is_ok() {
+                return result;
+            }
+
+            subject_st.deploy_archive_copied = true;
+            subject_st.checksum = local_checksum;
+            self.update_progress(name, |p| p.phase = DeployPhase::Extracting);
+
+            let extract = match self.is_windows(node) {
+                true => windows::extract_script(&format, &remote_archive, &install_dir).map(|s| windows::powershell(&s)),
+                false => format.extract_command(&remote_archive, &install_dir),
+            };
+            if let Some(cmd) = extract {
+                let extracted = match self.execute_reported(name, sess, cmd, timeout) {
+                    Ok(out) => NodePool::check_output(name, "extract archive", &out),
+                    Err(e) => {
+                        error!("Failed to extract archive: {} ({})", name, scrub(&e.to_string()));
+                        false
+                    }
+                };
+
+                if !extracted {
+                    return DeployResult::DeployExtractionFailed;
+                }
+            }
+
+            subject_st.deploy_archive_extracted = true;
+        }
+
+        self.update_progress(name, |p| p.phase = DeployPhase::Testing);
+
+        let tested = match self.execute_reported(
+            name,
+            sess,
+            self.get_test_command(node, subject, &install_dir),
+            timeout,
+        ) {
+            Ok(out) => NodePool::check_output(name, "test deployment", &out),
+            Err(e) => {
+                error!("Failed to test deployment: {} ({})", name, scrub(&e.to_string()));
+                false
+            }
+        };
+
+        if !tested {
+            return DeployResult::DeployTestFailed;
+        }
+
+        subject_st.deploy_archive_tested = true;
+
+        if versioned {
+            if !self.switch_version(name, sess, &remote_dir, &version, timeout) {
+                return DeployResult::DeployExtractionFailed;
+            }
+            subject_st.version = version;
+        }
+
+        if !self.run_hook(name, sess, node, subject, NodeParameters::PostDeployCmd, &install_dir) {
+            return DeployResult::HookFailed;
+        }
+
+        return if reused { DeployResult::AlreadyDeployed } else { DeployResult::Ok };
+    }
+
+    /* Hooks see where the tree goes through DEPLOY_DIR and DEPLOY_SUBJECT */
+    fn run_hook(&self, name: &str, sess: &dyn Transport, node: &Node, subject: &DeploySubject,
+                param: NodeParameters, install_dir: &str) -> bool {
+        let hook = param.to_string();
+        let cmd = self.get_node_param(node, param);
+        if cmd.trim().is_empty() {
+            return true;
+        }
+
+        let cmd = self.escaped(node, CommandContext::new(subject, &self.get_remote_dir(node, subject), install_dir))
+            .render(&cmd);
+        let cmd = match self.is_windows(node) {
+            true => windows::powershell(&format!("$env:DEPLOY_DIR = {}; $env:DEPLOY_SUBJECT = '{}'; {}",
+                                                 ps_quote(install_dir), subject, cmd)),
+            false => format!("export DEPLOY_DIR={} DEPLOY_SUBJECT={}; {}", shell_quote(install_dir), subject, cmd),
+        };
+        return match self.execute_reported(name, sess, cmd, self.get_command_timeout(node)) {
+            Ok(out) => NodePool::check_output(name, &hook, &out),
+            Err(e) => {
+                error!("Failed to run {}: {} ({})", hook, name, scrub(&e.to_string()));
+                false
+            }
+        };
+    }
+
+    /* Sortable by deploy time, with the archive checksum to tell builds apart */
+    fn version_stamp(checksum: &str) -> String {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        if checksum.is_empty() {
+            return secs.to_string();
+        }
+        return format!("{}-{}", secs, &checksum[..checksum.len().min(12)]);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn copy_archive(&self, name: &str, sess: &dyn Transport, node: &Node, distr: &str,
+                    format: &ArchiveFormat, remote_archive: &str, install_dir: &str,
+                    local_checksum: &str, timeout: Option<Duration>) -> DeployResult {
+        let started = Instant::now();
+        let unpacked = extracted_size(Path::new(distr), format).unwrap_or(0);
+        if *format == ArchiveFormat::Directory {
+            if let Err(result) = self.check_disk_space(name, sess, node, &[(install_dir, unpacked)], timeout) {
+                return result;
+            }
+            let uploaded = self.upload_tree(name, sess, node, Path::new(distr), install_dir);
+            self.audit_upload(name, distr, install_dir, &uploaded, started);
+            if let Err(e) = uploaded {
+                error!("Failed to copy directory: {} ({})", name, scrub(&e.to_string()));
+                return DeployResult::DeployCopyFailed;
+            }
+            return DeployResult::Ok;
+        }
+
+        if self.has_artifact(name, sess, node, format, local_checksum, timeout) {
+            info!("Archive already in the node's artifact cache, skipping upload: {}", name);
+            return DeployResult::AlreadyDeployed;
+        }
+
+        let packed = fs::metadata(distr).map(|m| m.len()).unwrap_or(0);
+        if let Err(result) = self.check_disk_space(name, sess, node,
+                                                   &[(remote_archive, packed), (install_dir, unpacked)], timeout) {
+            return result;
+        }
+
+        let resume = self.get_bool(node, NodeParameters::ResumableUpload, false).unwrap_or(false);
+        let uploaded = self.upload_file(
+            name,
+            sess,
+            TransferMethod::from_param(&self.get_node_param(node, NodeParameters::TransferMethod)),
+            resume,
+            distr.to_string(),
+            remote_archive.to_string(),
+        );
+        self.audit_upload(name, distr, remote_archive, &uploaded, started);
+        if let Err(e) = uploaded {
+            error!("Failed to copy archive: {} ({})", name, scrub(&e.to_string()));
+            return DeployResult::DeployCopyFailed;
+        }
+
+        /* A truncated upload shows in the size, without hashing the whole archive */
+        let local_size = fs::metadata(distr).map(|m| m.len()).ok();
+        let size_ok = match self.stat_remote(sess, node, remote_archive, timeout) {
+            Ok(info) => info.map(|i| i.size) == local_size || local_size.is_none(),
+            Err(e) => {
+                error!("Failed to stat remote archive: {} ({})", name, scrub(&e.to_string()));
+                true
+            }
+        };
+        if !size_ok {
+            error!("Archive size mismatch: {} (local {})", name, local_size.unwrap_or_default());
+        }
+
+        if !size_ok || !self.verify_archive(name, sess, node, remote_archive, local_checksum, timeout) {
+            if resume {
+                /* Don't resume from a corrupt partial file next time */
+                self.remove_remote(sess, node, remote_archive, timeout);
+            }
+            return DeployResult::ChecksumMismatch;
+        }
+
+        self.store_artifact(name, sess, node, format, local_checksum, distr);
+        return DeployResult::Ok;
+    }
+
+    /* Remote-pull counterpart of copy_archive(): the node downloads the archive itself */
+    #[allow(clippy::too_many_arguments)]
+    fn fetch_archive(&self, name: &str, sess: &dyn Transport, node: &Node, url: &ArtifactUrl,
+                     format: &ArchiveFormat, remote_archive: &str, timeout: Option<Duration>) -> DeployResult {
+        if self.has_artifact(name, sess, node, format, &url.sha256, timeout) {
+            info!("Archive already in the node's artifact cache, skipping download: {}", name);
+            return DeployResult::AlreadyDeployed;
+        }
+
+        self.update_progress(name, |p| p.phase = DeployPhase::Fetching);
+        let fetch = match self.is_windows(node) {
+            true => windows::powershell(&windows::fetch_script(url, remote_archive)),
+            false => unix::fetch_script(url, remote_archive),
+        };
+        let fetched = match self.execute_reported(name, sess, fetch, timeout) {
+            Ok(out) => NodePool::check_output(name, "fetch archive", &out),
+            Err(e) => {
+                error!("Failed to fetch archive: {} ({})", name, scrub(&e.to_string()));
+                false
+            }
+        };
+        if !fetched {
+            self.remove_remote(sess, node, remote_archive, timeout);
+            return DeployResult::DeployCopyFailed;
+        }
+
+        if !self.verify_archive(name, sess, node, remote_archive, &url.sha256, timeout) {
+            self.remove_remote(sess, node, remote_archive, timeout);
+            return DeployResult::ChecksumMismatch;
+        }
+
+        self.store_artifact(name, sess, node, format, &url.sha256, &url.url);
+        return DeployResult::Ok;
+    }
+
+    /* Size, mode and mtime of path on the node; None when nothing is there */
+    pub fn remote_stat(&mut self, name: String, path: String) -> Result<Option<FileInfo>, DeltaError> {
+        if !self.nodes.contains_key(&name) {
+            return Err(DeltaError::NodeNotFound(name));
+        }
+        if !is_shell_safe(&path) {
+            return Err(DeltaError::InvalidParameter("path".to_string(), path));
+        }
+
+        if self.instances.contains_key(&name) {
+            let _ = self.ensure_connected(name.clone());
+        }
+
+        let sess = self.session(&name)?;
+        let node = &self.nodes[&name];
+        return self.stat_remote(sess, node, &path, self.get_command_timeout(node));
+    }
+
+    pub fn remote_exists(&mut self, name: String, path: String) -> Result<bool, DeltaError> {
+        return Ok(self.remote_stat(name, path)?.is_some());
+    }
+
+    /* Whether the tools deploys run are on the node, in versions new enough */
+    pub fn check_prerequisites(&mut self, name: String) -> Result<PrerequisiteReport, DeltaError> {
+        if !self.nodes.contains_key(&name) {
+            return Err(DeltaError::NodeNotFound(name));
+        }
+
+        if self.instances.contains_key(&name) {
+            let _ = self.ensure_connected(name.clone());
+        }
+
+        let sess = self.session(&name)?;
+        let node = &self.nodes[&name];
+        let (tools, script) = match self.is_windows(node) {
+            true => (windows::PREREQUISITES, windows::powershell(&windows::prerequisites_script())),
+            false => {
+                let tools = unix::prerequisites(&node.os);
+                (tools, unix::prerequisites_script(tools))
+            }
+        };
+        let out = self.execute(sess, script, self.get_command_timeout(node))?;
+        if !out.success() {
+            return Err(DeltaError::CommandFailed(format!("failed to check prerequisites: {}", out.stderr.trim())));
+        }
+
+        let found: HashMap<&str, (bool, &str)> = out.stdout.lines()
+            .filter_map(|line| line.trim().split_once(' '))
+            .map(|(tool, rest)| (tool, (rest.starts_with("found"), rest.trim_start_matches("found").trim())))
+            .collect();
+        let checks: Vec<ToolCheck> = tools.iter().map(|(tool, minimum)| {
+            let (present, version) = found.get(tool).copied().unwrap_or((false, ""));
+            return ToolCheck::new(tool, *minimum, present, version);
+        }).collect();
+        for check in checks.iter().filter(|c| !c.ok) {
+            error!("Prerequisite not met on {}: {} (found {:?}, need {:?})",
+                   name, check.tool, check.version, check.minimum);
+        }
+        return Ok(PrerequisiteReport { node: name, tools: checks });
+    }
+
+    /*
+     * Compares what each path needs against the space left on its
+     * filesystem, adding up the needs of paths that share one. Where df
+     * can't tell, the deploy goes ahead and fails later if it must.
+     */
+    fn check_disk_space(&self, name: &str, sess: &dyn Transport, node: &Node, needs: &[(&str, u64)],
+                        timeout: Option<Duration>) -> Result<(), DeployResult> {
+        let paths: Vec<String> = needs.iter().map(|(path, _)| path.to_string()).collect();
+        let script = match self.is_windows(node) {
+            true => windows::powershell(&windows::free_space_script(&paths)),
+            false => unix::free_space_script(&paths),
+        };
+        let out = match self.execute(sess, script, timeout) {
+            Ok(out) if out.success() => out,
+            Ok(out) => {
+                error!("Failed to check free disk space: {} ({})", name, scrub(out.stderr.trim()));
+                return Ok(());
+            }
+            Err(e) => {
+                error!("Failed to check free disk space: {} ({})", name, scrub(&e.to_string()));
+                return Ok(());
+            }
+        };
+
+        let filesystems: Vec<(&str, u64)> = out.stdout.lines()
+            .filter_map(|line| line.trim().rsplit_once(' '))
+            .filter_map(|(fs, available)| available.parse::<u64>().ok().map(|a| (fs, a)))
+            .collect();
+        if filesystems.len() != needs.len() {
+            error!("Failed to check free disk space: {} (unexpected df output)", name);
+            return Ok(());
+        }
+
+        for (i, (filesystem, available)) in filesystems.iter().enumerate() {
+            let needed: u64 = filesystems.iter().zip(needs)
+                .filter(|((fs, _), _)| fs == filesystem)
+                .map(|(_, (_, size))| size)
+                .sum();
+            if needed > *available {
+                error!("Not enough disk space for {}: {} needs {} bytes, {} available",
+                       name, needs[i].0, needed, available);
+                return Err(DeployResult::InsufficientDiskSpace {
+                    path: needs[i].0.to_string(),
+                    needed,
+                    available: *available,
+                });
+            }
+        }
+        return Ok(());
+    }
+
+    fn stat_remote(&self, sess: &dyn Transport, node: &Node, path: &str,
+                   timeout: Option<Duration>) -> Result<Option<FileInfo>, DeltaError> {
+        let stat = match self.is_windows(node) {
+            true => windows::powershell(&windows::stat_script(path)),
+            false => unix::stat_script(&node.os, path),
+        };
+        let out = self.execute(sess, stat, timeout)?;
+        if !out.success() {
+            return Err(DeltaError::CommandFailed(format!("failed to stat {}: {}", path, out.stderr.trim())));
+        }
+        if out.stdout.trim() == "missing" {
+            return Ok(None);
+        }
+
+        let values: HashMap<&str, &str> = out.stdout.split_whitespace().filter_map(|w| w.split_once('=')).collect();
+        let field = |key: &str| values.get(key).copied()
+            .ok_or_else(|| DeltaError::Parse(format!("stat of {}: no {}", path, key)));
+        let number = |key: &str| field(key)?.parse::<u64>()
+            .map_err(|_| DeltaError::Parse(format!("stat of {}: bad {}", path, key)));
+        let mode = u32::from_str_radix(field("mode")?, 8)
+            .map_err(|_| DeltaError::Parse(format!("stat of {}: bad mode", path)))?;
+
+        return Ok(Some(FileInfo {
+            size: number("size")?,
+            mode,
+            mtime: number("mtime").unwrap_or(0),
+            is_dir: field("dir")? == "1",
+        }));
+    }
+
+    /* Whether the archive on the node has the expected checksum */
+    fn verify_archive(&self, name: &str, sess: &dyn Transport, node: &Node, remote_archive: &str,
+                      expected: &str, timeout: Option<Duration>) -> bool {
+        self.update_progress(name, |p| p.phase = DeployPhase::Verifying);
+
+        let checksum = match self.is_windows(node) {
+            true => windows::powershell(&windows::checksum_script(remote_archive)),
+            false => format!("{} {}", unix::checksum_tool(&node.os), shell_quote(remote_archive)),
+        };
+        let remote_checksum = match self.execute(sess, checksum, timeout) {
+            Ok(out) => parse_sha256sum(&out.stdout),
+            Err(e) => {
+                error!("Failed to checksum remote archive: {} ({})", name, scrub(&e.to_string()));
+                None
+            }
+        };
+
+        if remote_checksum.as_deref() != Some(expected) {
+            error!("Archive checksum mismatch: {} (expected {}, remote {})",
+                   name, expected, remote_checksum.unwrap_or_default());
+            return false;
+        }
+        return true;
+    }
+
+    fn remove_remote(&self, sess: &dyn Transport, node: &Node, path: &str, timeout: Option<Duration>) {
+        let remove = match self.is_windows(node) {
+            true => windows::powershell(&windows::remove_script(&[path.to_string()])),
+            false => format!("rm -f {}", shell_quote(path)),
+        };
+        let _ = self.execute(sess, remove, timeout);
+    }
+
+    /*
+     * Whether the archive with this checksum is in the node's artifact
+     * cache. Only verified uploads are listed in its manifest, so one cut
+     * short never counts, while the file it left can still be resumed.
+     */
+    fn has_artifact(&self, name: &str, sess: &dyn Transport, node: &Node, format: &ArchiveFormat,
+                    checksum: &str, timeout: Option<Duration>) -> bool {
+        let cache_dir = self.get_artifact_cache_dir(node);
+        let file = format!("{}{}", checksum, format.extension());
+        let lookup = match self.is_windows(node) {
+            true => windows::powershell(&windows::artifact_lookup_script(&cache_dir, checksum, &file)),
+            false => unix::artifact_lookup_script(&cache_dir, checksum, &file),
+        };
+        return match self.execute(sess, lookup, timeout) {
+            Ok(out) => out.stdout.trim() == "match",
+            Err(e) => {
+                error!("Failed to look up artifact cache: {} ({})", name, scrub(&e.to_string()));
+                false
+            }
+        };
+    }
+
+    /* Lists a verified archive in the cache manifest; failing that only costs an upload next time */
+    fn store_artifact(&self, name: &str, sess: &dyn Transport, node: &Node, format: &ArchiveFormat,
+                      checksum: &str, distr: &str) {
+        let cache_dir = self.get_artifact_cache_dir(node);
+        let file = format!("{}{}", checksum, format.extension());
+        let source = Path::new(distr).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+            .replace(|c: char| c.is_control(), "_");
+        let keep = self.get_artifacts_kept(node);
+        let store = match self.is_windows(node) {
+            true => windows::powershell(&windows::artifact_store_script(&cache_dir, checksum, &file, &source, keep)),
+            false => unix::artifact_store_script(&cache_dir, checksum, &file, &source, keep),
+        };
+        if !self.execute(sess, store, self.get_command_timeout(node)).is_ok_and(|out| out.success()) {
+            error!("Failed to update artifact cache manifest: {}", name);
+        }
+    }
+
+    #[cfg(feature = "delta_sync")]
+    fn sync_deploy(&self, name: &str, sess: &dyn Transport, distr: &str, remote_dir: &str,
+                   timeout: Option<Duration>) -> bool {
+        self.update_progress(name, |p| p.phase = DeployPhase::Syncing);
+
+        let exec = |cmd: String| self.execute(sess, cmd, timeout);
+        let checksum = unix::checksum_tool(&self.nodes[name].os);
+        let _permit = self.limiter.upload();
+        return match sync_tree(sess, Path::new(distr), remote_dir, checksum, &exec) {
+            Ok(n) => {
+                info!("Synced {} changed files: {}", n, name);
+                true
+            }
+            Err(e) => {
+                error!("Sync failed, falling back to full deploy: {} ({})", name, scrub(&e.to_string()));
+                false
+            }
+        };
+    }
+
+    #[cfg(not(feature = "delta_sync"))]
+    fn sync_deploy(&self, name: &str, _sess: &dyn Transport, _distr: &str, _remote_dir: &str,
+                   _timeout: Option<Duration>) -> bool {
+        error!("Sync mode requires the delta_sync feature, doing full deploy: {}", name);
+        return false;
+    }
+
+    pub fn run(&mut self, name: String, subject: DeploySubject) -> RunResult {
+        return self.run_with_options(name, subject, RunOptions::new());
+    }
+
+    /* Same as run(), passing extra arguments and environment to the server */
+    pub fn run_with_options(&mut self, name: String, subject: DeploySubject,
+                            options: RunOptions) -> RunResult {
+        return self.run_tracked(name, subject, options).result;
+    }
+
+    /* Same as run_with_options(), stopping with RunResult::Cancelled once token is cancelled */
+    pub fn run_cancellable(&mut self, name: String, subject: DeploySubject, options: RunOptions,
+                           token: &CancellationToken) -> RunResult {
+        let _scope = CancelScope::enter(token);
+        return self.run_with_options(name, subject, options);
+    }
+
+    /* Same as run_with_options(), also returning the id the start was logged and audited under */
+    pub fn run_tracked(&mut self, name: String, subject: DeploySubject,
+                       options: RunOptions) -> OperationResult<RunResult> {
+        let _lock = self.lock_node(&name);
+        let op = OperationScope::enter();
+        let span = info_span!("run", node = %name, subject = %subject, op_id = %op.id());
+        let _entered = span.enter();
+        let started = Instant::now();
+        let mut result = self.run_node(name.clone(), subject.clone(), options);
+        if result != RunResult::Ok && is_cancelled() {
+            result = RunResult::Cancelled;
+        }
+        self.audit(&name, AuditAction::Run, subject.to_string(),
+                   format!("{:?}", result), result == RunResult::Ok, started);
+        if result == RunResult::Ok {
+            self.reset_restarts(&name, &subject);
+            self.event_bus.publish(PoolEvent::InstanceStarted { node: name, subject });
+        }
+        return op.finish(result);
+    }
+
+    /*
+     * One supervisor pass: restarts instances that should be running but
+     * are gone, as their node's restart policy says. Nodes that can't be
+     * reached are left for the next pass.
+     */
+    pub fn supervise(&mut self) -> Vec<SupervisorEvent> {
+        let mut events = Vec::new();
+        let mut names: Vec<String> = self.nodes.keys().cloned().collect();
+        names.sort();
+        for name in names {
+            let policies: Vec<(DeploySubject, RestartPolicy)> = self.nodes[&name].restart_policies.iter()
+                .filter(|(_, p)| p.mode != RestartMode::Never)
+                .map(|(s, p)| (s.clone(), p.clone()))
+                .collect();
+            for (subject, policy) in policies {
+                events.extend(self.supervise_subject(&name, &subject, &policy));
+            }
+        }
+        return events;
+    }
+
+    fn supervise_subject(&mut self, name: &str, subject: &DeploySubject,
+                         policy: &RestartPolicy) -> Option<SupervisorEvent> {
+        let subject_st = self.instances.get(name)?.conn_status.subjects.get(subject)?.clone();
+        if !subject_st.running && subject_st.restart_after.is_none() {
+            return None;
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+        if subject_st.restart_after.is_some_and(|t| now < t) {
+            return None;
+        }
+
+        /* Checked and restarted under the lock, so a deploy from another pool can't interleave */
+        let _lock = self.lock_node(name);
+        let _ = self.ensure_connected(name.to_string());
+        let sess = self.session(name).ok()?;
+        let node = &self.nodes[name];
+        let timeout = self.get_command_timeout(node);
+        if self.check_alive(sess, node, subject, timeout).ok()?.alive {
+            return None;
+        }
+
+        let clean = policy.mode == RestartMode::OnFailure && self.exited_cleanly(sess, node, subject, timeout);
+        if subject_st.running {
+            self.event_bus.publish(PoolEvent::InstanceDied { node: name.to_string(), subject: subject.clone() });
+        }
+        if clean || !policy.allows(subject_st.restarts) {
+            let mut conn_status = self.instances[name].conn_status.clone();
+            let mut st = conn_status.get_subject(subject.clone());
+            st.running = false;
+            st.restart_after = None;
+            conn_status.set_subject(subject.clone(), st);
+            self.set_state(name.to_string(), conn_status);
+
+            if clean {
+                info!("Instance exited cleanly, not restarting: {}", name);
+                return None;
+            }
+            error!("Giving up on instance after {} restarts: {}", subject_st.restarts, name);
+            self.event_bus.publish(PoolEvent::RestartsExhausted {
+                node: name.to_string(), subject: subject.clone(), restarts: subject_st.restarts,
+            });
+            return Some(SupervisorEvent::GaveUp {
+                node: name.to_string(), subject: subject.clone(), restarts: subject_st.restarts,
+            });
+        }
+
+        let restarts = subject_st.restarts + 1;
+        let op = OperationScope::enter();
+        let span = info_span!("supervise", node = %name, subject = %subject, op_id = %op.id());
+        let _entered = span.enter();
+        info!("Instance is gone, restarting ({}): {}", restarts, name);
+        let started = Instant::now();
+        let options = self.last_run_options(name, subject);
+        let result = self.run_node(name.to_string(), subject.clone(), options);
+        self.audit(name, AuditAction::Restart, format!("{} (supervisor)", subject),
+                   format!("{:?}", result), result == RunResult::Ok, started);
+
+        /* A failed run leaves running unset, restart_after keeps it supervised */
+        let mut conn_status = self.instances[name].conn_status.clone();
+        let mut st = conn_status.get_subject(subject.clone());
+        st.restarts = restarts;
+        st.restart_after = Some(now + policy.delay(restarts).as_millis() as u64);
+        conn_status.set_subject(subject.clone(), st);
+        self.set_state(name.to_string(), conn_status);
+
+        if result == RunResult::Ok {
+            self.event_bus.publish(PoolEvent::InstanceRestarted {
+                node: name.to_string(), subject: subject.clone(), restarts,
+            });
+        }
+        return Some(match result {
+            RunResult::Ok => SupervisorEvent::Restarted { node: name.to_string(), subject: subject.clone(), restarts },
+            _ => SupervisorEvent::RestartFailed { node: name.to_string(), subject: subject.clone(), restarts },
+        });
+    }
+
+    /* Only units keep the exit status around; a vanished pid reads as a failure */
+    fn exited_cleanly(&self, sess: &dyn Transport, node: &Node, subject: &DeploySubject,
+                      timeout: Option<Duration>) -> bool {
+        let mode = self.get_run_mode(node);
+        if !mode.is_systemd() {
+            return false;
+        }
+
+        let cmd = format!("{} show -p Result -p ExecMainStatus {}", systemd::systemctl(&mode),
+                          shell_quote(&systemd::unit_name(subject)));
+        return match self.execute(sess, cmd, timeout) {
+            Ok(out) if out.success() => {
+                let lines: Vec<&str> = out.stdout.lines().map(|l| l.trim()).collect();
+                lines.contains(&"Result=success") && lines.contains(&"ExecMainStatus=0")
+            }
+            _ => false,
+        };
+    }
+
+    fn reset_restarts(&mut self, name: &str, subject: &DeploySubject) {
+        let Some(inst) = self.instances.get(name) else {
+            return;
+        };
+
+        let mut conn_status = inst.conn_status.clone();
+        let mut st = conn_status.get_subject(subject.clone());
+        st.restarts = 0;
+        st.restart_after = None;
+        conn_status.set_subject(subject.clone(), st);
+        self.set_state(name.to_string(), conn_status);
+    }
+
+    fn run_node(&mut self, name: String, subject: DeploySubject, options: RunOptions) -> RunResult {
+        if !self.nodes.contains_key(&name) {
+            error!("Node doesn't exist: {}", name);
+            return RunResult::NodeNotFound;
+        }
+
+        if let Err(e) = self.check_run_options(&options) {
+            error!("Invalid run options: {} ({})", name, e);
+            self.record_error(&name, format!("run {}: {}", subject, e));
+            return RunResult::InvalidArgument;
+        }
+
+        if let Err(e) = self.get_path(&self.nodes[&name], subject.remote_dir_param(), subject.default_remote_dir()) {
+            error!("Invalid run parameters: {} ({})", name, scrub(&e.to_string()));
+            self.record_error(&name, format!("run {}: {}", subject, e));
+            return RunResult::InvalidArgument;
+        }
+
+        /* Bind settings are checked up front, a dynamic port is picked once the old instance is gone */
+        let (bind_addr, port_spec) = match self.conn_params(&self.nodes[&name], &subject) {
+            Ok(p) => p,
+            Err(e) => {
+                error!("Invalid run parameters: {} ({})", name, e);
+                self.record_error(&name, format!("run {}: {}", subject, e));
+                return RunResult::InvalidArgument;
+            }
+        };
+
+        if self.instances.contains_key(&name) {
+            let _ = self.ensure_connected(name.clone());
+        }
+
+        let sess = match self.session(&name) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("{}", e);
+                self.record_error(&name, format!("run {}: {}", subject, e));
+                return RunResult::NodeNotConnected;
+            }
+        };
+
+        let node = &self.nodes[&name];
+        let inst = &self.instances[&name];
+        let timeout = self.get_command_timeout(node);
+
+        let mut conn_status = inst.conn_status.clone();
+        let mut subject_st = conn_status.get_subject(subject.clone());
+
+        subject_st.running = false;
+
+        /* Kill existing instance, if exists */
+        let _ = self.stop_instance(sess, node, &subject, timeout);
+
+        let bind_port = match self.resolve_bind_port(sess, node, &subject, &port_spec, timeout) {
+            Ok(p) => p,
+            Err(e) => {
+                error!("Failed to choose bind port: {} ({})", name, scrub(&e.to_string()));
+                self.record_error(&name, format!("run {}: {}", subject, e));
+                return match e {
+                    DeltaError::NoFreePort(_) => RunResult::PortInUse { pid: None },
+                    _ => RunResult::RunFailed,
+                };
+            }
+        };
+
+        if let Some(pid) = self.port_owner(sess, node, bind_port, timeout) {
+            error!("Port {} is already in use: {} (pid {})", bind_port, name,
+                   pid.map(|p| p.to_string()).unwrap_or("unknown".to_string()));
+            self.record_error(&name, format!("run {}: port {} in use", subject, bind_port));
+            return RunResult::PortInUse { pid };
+        }
+
+        /* Run new instance */
+        let started = self.start_instance(&name, sess, node, &subject, &bind_addr, bind_port,
+                                          &options, timeout);
+        subject_st.run_options = Some(options);
+        if let Err(e) = started {
+            self.record_error(&name, format!("run {}: {}", subject, e));
+            conn_status.set_subject(subject, subject_st);
+            self.set_state(name, conn_status);
+            return RunResult::RunFailed;
+        }
+
+        subject_st.running = true;
+        conn_status.set_subject(subject, subject_st);
+        self.set_state(name, conn_status);
+        return RunResult::Ok;
+    }
+
+    /*
+     * Stops and starts the instance again on the bind address and port the
+     * previous run() chose, falling back to the params if it left none.
+     */
+    pub fn restart(&mut self, name: String, subject: DeploySubject) -> RestartResult {
+        let _lock = self.lock_node(&name);
+        let op = OperationScope::enter();
+        let span = info_span!("restart", node = %name, subject = %subject, op_id = %op.id());
+        let _entered = span.enter();
+        let started = Instant::now();
+        let result = self.restart_node(name.clone(), subject.clone());
+        self.audit(&name, AuditAction::Restart, subject.to_string(), format!("{:?}", result),
+                   matches!(result, RestartResult::Ok { .. }), started);
+        return result;
+    }
+
+    fn restart_node(&mut self, name: String, subject: DeploySubject) -> RestartResult {
+        if !self.nodes.contains_key(&name) {
+            error!("Node doesn't exist: {}", name);
+            return RestartResult::NodeNotFound;
+        }
+
+        if self.instances.contains_key(&name) {
+            let _ = self.ensure_connected(name.clone());
+        }
+
+        let sess = match self.session(&name) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("{}", e);
+                return RestartResult::NodeNotConnected;
+            }
+        };
+
+        let node = &self.nodes[&name];
+        let timeout = self.get_command_timeout(node);
+        let remote_dir = self.get_remote_dir(node, &subject);
+        let options = self.last_run_options(&name, &subject);
+
+        let (bind_addr, bind_port) = match self.read_bind_params(sess, node, &remote_dir, timeout) {
+            Some(p) => p,
+            None => match self.infer_conn_params(sess, node, &subject, timeout) {
+                Ok(p) => p,
+                Err(e @ DeltaError::InvalidParameter(..)) => {
+                    error!("Invalid run parameters: {} ({})", name, e);
+                    return RestartResult::InvalidArgument;
+                }
+                Err(e) => {
+                    error!("Failed to choose bind port: {} ({})", name, scrub(&e.to_string()));
+                    return RestartResult::RunFailed;
+                }
+            },
+        };
+
+        let started_at = Instant::now();
+        let stopped = match self.stop_instance(sess, node, &subject, timeout) {
+            Ok(s) => s,
+            Err(e @ DeltaError::InvalidParameter(..)) => {
+                error!("Invalid stop parameters: {} ({})", name, e);
+                return RestartResult::InvalidArgument;
+            }
+            Err(e) => {
+                error!("Failed to stop instance: {} ({})", name, scrub(&e.to_string()));
+                return RestartResult::StopFailed;
+            }
+        };
+        let started = self.start_instance(&name, sess, node, &subject, &bind_addr, bind_port,
+                                          &options, timeout);
+        let elapsed_ms = started_at.elapsed().as_millis() as u64;
+
+        let mut conn_status = self.instances[&name].conn_status.clone();
+        let mut subject_st = conn_status.get_subject(subject.clone());
+        subject_st.running = started.is_ok();
+        conn_status.set_subject(subject.clone(), subject_st);
+        self.set_state(name.clone(), conn_status);
+
+        return match started {
+            Ok(pid) => {
+                info!("Restarted instance in {} ms: {}", elapsed_ms, name);
+                RestartResult::Ok { pid, elapsed_ms, stopped }
+            }
+            Err(e) => {
+                self.record_error(&name, format!("restart {}: {}", subject, e));
+                RestartResult::RunFailed
+            }
+        };
+    }
+
+    /* Options the subject was last run with, or the defaults */
+    fn last_run_options(&self, name: &str, subject: &DeploySubject) -> RunOptions {
+        return self.instances.get(name)
+            .and_then(|inst| inst.conn_status.subjects.get(subject))
+            .and_then(|st| st.run_options.clone())
+            .unwrap_or_else(RunOptions::new);
+    }
+
+    /* Launches the subject binary in the background and returns its pid once it survived startup */
+    #[allow(clippy::too_many_arguments)]
+    fn start_instance(&self, name: &str, sess: &dyn Transport, node: &Node, subject: &DeploySubject,
+                      bind_addr: &str, bind_port: u16, options: &RunOptions,
+                      timeout: Option<Duration>) -> Result<u32, String> {
+        let remote_dir = self.get_remote_dir(node, subject);
+        if self.is_windows(node) {
+            let script = self.windows_start_script(node, subject, bind_addr, bind_port, options)?;
+            let exec_result = self.execute_reported(name, sess, windows::powershell(&script), timeout)
+                .unwrap_or_else(|e| {
+                    error!("Failed to run instance: {} ({})", name, scrub(&e.to_string()));
+                    ExecOutput::new()
+                });
+            return NodePool::started_pid(name, exec_result);
+        }
+
+        /* A subshell keeps the cd local; exec leaves $! pointing at the server itself */
+        let mut launch = String::new();
+        if let Some(dir) = &options.working_dir {
+            launch.push_str(&format!("cd {} && ", shell_quote(dir)));
+        }
+        launch.push_str("exec ");
+        if !options.env.is_empty() {
+            let mut env: Vec<_> = options.env.iter().collect();
+            env.sort();
+            launch.push_str("env ");
+            for (key, value) in env {
+                launch.push_str(&format!("{}={} ", key, shell_quote(value)));
+            }
+        }
+        launch.push_str(&self.command_context(node, subject).bind(bind_addr, bind_port).render(
+            &self.command_template(node, subject.launch_command_param(), DEFAULT_LAUNCH_TEMPLATE)));
+        for arg in &options.extra_args {
+            launch.push(' ');
+            launch.push_str(&shell_quote(arg));
+        }
+
+        /* Output of the previous run is kept once, as *.log.1 */
+        let log_dir = shell_quote(&self.get_log_dir(node, subject));
+        let rotate = format!("l={0}; mkdir -p \"$l\" && for f in stdout stderr; do \
+                              if [ -f \"$l/$f.log\" ]; then mv -f \"$l/$f.log\" \"$l/$f.log.1\"; fi; done",
+                             log_dir);
+        let redirect = format!("< /dev/null >> {0}/stdout.log 2>> {0}/stderr.log", log_dir);
+        /* Whatever the instance said before dying ends up in the run error */
+        let on_failure = format!("|| {{ tail -n 20 {}/stderr.log >&2; false; }}", log_dir);
+        let dir = shell_quote(&remote_dir);
+
+        let mode = self.get_run_mode(node);
+        if mode.is_systemd() && !node.os.has_systemd() {
+            return Err(format!("systemd run modes are not available on {}", node.os));
+        }
+        let commands = if mode.is_systemd() {
+            /* The unit's MainPID goes to the pid file, so status() reads it the same way */
+            let (signal, grace) = self.get_stop_params(node).map_err(|e| e.to_string())?;
+            let launch = format!("exec {}\n{}", redirect, launch);
+            vec![
+                rotate,
+                systemd::install_script(&mode, subject, &remote_dir, &launch, &signal, grace),
+                format!("echo {} > {}/bind_addr", shell_quote(bind_addr), dir),
+                format!("echo {} > {}/bind_port", bind_port, dir),
+                "sleep 4".to_string(),
+                format!("p=$({}) && {} && [ \"$p\" -gt 0 ] && echo \"$p\" > {}/pid \
+                         && echo pid \"$p\" {}", systemd::main_pid_command(&mode, subject),
+                        systemd::alive_command(&mode, subject), dir, on_failure),
+            ]
+        } else {
+            vec![
+                rotate,
+                format!("({}) {} &", launch, redirect),
+                format!("echo $! > {}/pid", dir),
+                format!("echo {} > {}/bind_addr", shell_quote(bind_addr), dir),
+                format!("echo {} > {}/bind_port", bind_port, dir),
+                "sleep 4".to_string(),
+                format!("kill -0 \"$(cat {0}/pid)\" 2> /dev/null && echo pid \"$(cat {0}/pid)\" {1}", dir, on_failure),
+            ]
+        };
+
+        let exec_result = self.execute_vec(name, sess, commands, timeout).unwrap_or_else(|e| {
+            error!("Failed to run instance: {} ({})", name, scrub(&e.to_string()));
+            ExecOutput::new()
+        });
+
+        return NodePool::started_pid(name, exec_result);
+    }
+
+    /* PowerShell take on the launch line; the detached shell sets env and working dir itself */
+    fn windows_start_script(&self, node: &Node, subject: &DeploySubject, bind_addr: &str, bind_port: u16,
+                            options: &RunOptions) -> Result<String, String> {
+        if self.get_run_mode(node).is_systemd() {
+            return Err("systemd run modes are not available on Windows".to_string());
+        }
+
+        let mut launch = String::new();
+        if let Some(dir) = &options.working_dir {
+            launch.push_str(&format!("Set-Location -LiteralPath {}; ", ps_quote(dir)));
+        }
+        let mut env: Vec<_> = options.env.iter().collect();
+        env.sort();
+        for (key, value) in env {
+            launch.push_str(&format!("$env:{} = {}; ", key, ps_quote(value)));
+        }
+        launch.push_str(&self.command_context(node, subject).bind(bind_addr, bind_port).render(
+            &self.command_template(node, subject.launch_command_param(), windows::DEFAULT_LAUNCH_TEMPLATE)));
+        for arg in &options.extra_args {
+            launch.push(' ');
+            launch.push_str(&ps_quote(arg));
+        }
+
+        return Ok(windows::start_script(&launch, &self.get_remote_dir(node, subject),
+                                        &self.get_log_dir(node, subject), bind_addr, bind_port));
+    }
+
+    /* Pid from the "pid <n>" line the start commands print once the instance survived startup */
+    fn started_pid(name: &str, exec_result: ExecOutput) -> Result<u32, String> {
+        let pid = exec_result.stdout.lines()
+            .find_map(|l| l.trim().strip_prefix("pid ").and_then(|p| p.trim().parse::<u32>().ok()));
+        return match pid {
+            Some(pid) if exec_result.success() => Ok(pid),
+            _ => {
+                error!("Failed to run instance: {} ({})", name, scrub(exec_result.stderr.trim()));
+                Err(exec_result.stderr.trim().to_string())
+            }
+        };
+    }
+
+    /*
+     * What is actually running: pid and bind files left by run(), checked
+     * against the process table, with the start time taken from /proc (or
+     * ps on nodes without one, like macOS and FreeBSD). Also refreshes
+     * SubjectStatus::running.
+     */
+    pub fn status(&mut self, name: String, subject: DeploySubject) -> Result<RunStatus, DeltaError> {
+        if !self.nodes.contains_key(&name) {
+            return Err(DeltaError::NodeNotFound(name));
+        }
+
+        if self.instances.contains_key(&name) {
+            let _ = self.ensure_connected(name.clone());
+        }
+
+        let sess = self.session(&name)?;
+        let node = &self.nodes[&name];
+        let remote_dir = self.get_remote_dir(node, &subject);
+        let pid = self.pid_command(node, &subject);
+        let script = format!(
+            "d={0}; p=$({1}); \
+             echo \"pid=$p\"; echo \"addr=$(cat \"$d/bind_addr\" 2> /dev/null)\"; \
+             echo \"port=$(cat \"$d/bind_port\" 2> /dev/null)\"; \
+             if [ \"$p\" -gt 0 ] 2> /dev/null && kill -0 \"$p\" 2> /dev/null; then \
+                 now=$(date +%s); echo running=1; echo \"now=$now\"; {2}; \
+             fi",
+            shell_quote(&remote_dir), pid, unix::start_time_script(&node.os));
+        let out = self.execute(sess, script, self.get_command_timeout(node))?;
+
+        let fields: HashMap<&str, &str> = out.stdout.lines()
+            .filter_map(|l| l.split_once('='))
+            .map(|(k, v)| (k, v.trim()))
+            .collect();
+        let number = |key: &str| fields.get(key).and_then(|v| v.parse::<u64>().ok());
+
+        let mut status = RunStatus::new();
+        status.running = fields.get("running") == Some(&"1");
+        status.pid = fields.get("pid").and_then(|v| v.parse::<u32>().ok()).filter(|_| status.running);
+        status.bind_addr = fields.get("addr").unwrap_or(&"").to_string();
+        status.bind_port = fields.get("port").and_then(|v| v.parse::<u16>().ok());
+        if status.running {
+            status.started_at = number("started").or_else(|| {
+                let elapsed = fields.get("etime").and_then(|v| unix::parse_etime(v))?;
+                Some(number("now")?.saturating_sub(elapsed))
+            });
+            status.uptime_secs = number("now").zip(status.started_at).map(|(now, s)| now.saturating_sub(s));
+        }
+
+        let mut conn_status = self.instances[&name].conn_status.clone();
+        let mut subject_st = conn_status.get_subject(subject.clone());
+        subject_st.running = status.running;
+        conn_status.set_subject(subject, subject_st);
+        self.set_state(name, conn_status);
+
+        return Ok(status);
+    }
+
+    /*
+     * Takes over instances started by an earlier process, rebuilding the
+     * subject state from what is left on the node: a tree passing the test
+     * command counts as deployed, an instance passing the alive command as
+     * running, on the bind address and port from its bind files. Connects
+     * first when needed. Checksums and run options are not recorded on the
+     * node, the next deploy() and run() fill them in again.
+     */
+    pub fn adopt(&mut self, name: String) -> Result<ConnAliveStatus, DeltaError> {
+        let _lock = self.lock_node(&name);
+        let op = OperationScope::enter();
+        let span = info_span!("adopt", node = %name, op_id = %op.id());
+        let _entered = span.enter();
+        if !self.nodes.contains_key(&name) {
+            return Err(DeltaError::NodeNotFound(name));
+        }
+
+        if !self.instances.contains_key(&name) {
+            if self.connect(name.clone()) != ConnectResult::Ok {
+                return Err(DeltaError::NodeNotConnected(name));
+            }
+        } else {
+            let _ = self.ensure_connected(name.clone());
+        }
+
+        let alive = self.is_alive(name.clone());
+        let sess = self.session(&name)?;
+        let node = &self.nodes[&name];
+        let timeout = self.get_command_timeout(node);
+        let versioned = self.is_versioned(node) && !self.is_windows(node);
+        let mut conn_status = self.instances[&name].conn_status.clone();
+        let mut running_count = 0;
+        for subject in DeploySubject::all() {
+            let running = alive.subjects.get(&subject).is_some_and(|st| st.alive);
+            let test = self.get_test_command(node, &subject, &self.get_install_dir(node, &subject));
+            let deployed = running || self.execute(sess, test, timeout).is_ok_and(|out| out.success());
+
+            let mut subject_st = conn_status.get_subject(subject.clone());
+            subject_st.running = running;
+            if deployed {
+                subject_st.deploy_archive_copied = true;
+                subject_st.deploy_archive_extracted = true;
+                subject_st.deploy_archive_tested = true;
+                subject_st.deployed = true;
+                if versioned {
+                    subject_st.version = self.read_version_link(sess, &self.get_remote_dir(node, &subject),
+                                                                "current", timeout);
+                }
+            }
+            if running {
+                running_count += 1;
+            }
+            conn_status.set_subject(subject, subject_st);
+        }
+
+        self.set_state(name.clone(), conn_status);
+        info!("Adopted node: {} ({} running)", name, running_count);
+        return Ok(alive);
+    }
+
+    /*
+     * Output of the instance: the whole of its stdout and stderr logs, or
+     * their last tail_lines lines. Logs that don't exist read as empty.
+     */
+    pub fn fetch_logs(&mut self, name: String, subject: DeploySubject,
+                      tail_lines: Option<usize>) -> Result<InstanceLogs, DeltaError> {
+        if !self.nodes.contains_key(&name) {
+            return Err(DeltaError::NodeNotFound(name));
+        }
+
+        if self.instances.contains_key(&name) {
+            let _ = self.ensure_connected(name.clone());
+        }
+
+        let sess = self.session(&name)?;
+        let node = &self.nodes[&name];
+        let timeout = self.get_command_timeout(node);
+        let log_dir = self.get_log_dir(node, &subject);
+        let read = |file: &str| -> Result<String, DeltaError> {
+            let path = shell_quote(&format!("{}/{}", log_dir, file));
+            let cmd = match tail_lines {
+                Some(n) => format!("if [ -f {0} ]; then tail -n {1} {0}; fi", path, n),
+                None => format!("if [ -f {0} ]; then cat {0}; fi", path),
+            };
+            let out = self.execute(sess, cmd, timeout)?;
+            if !out.success() {
+                return Err(DeltaError::CommandFailed(format!("read {}: {}", path, out.stderr.trim())));
+            }
+            return Ok(out.stdout);
+        };
+
+        let mut logs = InstanceLogs::new();
+        logs.stdout = read("stdout.log")?;
+        logs.stderr = read("stderr.log")?;
+        return Ok(logs);
+    }
+
+    /*
+     * CPU, memory, fd and thread usage of the instance, None when it isn't
+     * running. With /proc the CPU share is measured over a second, so the
+     * call takes that long; otherwise ps is asked instead.
+     */
+    pub fn resource_usage(&mut self, name: String, subject: DeploySubject)
+                          -> Result<Option<ResourceUsage>, DeltaError> {
+        if !self.nodes.contains_key(&name) {
+            return Err(DeltaError::NodeNotFound(name));
+        }
+
+        if self.instances.contains_key(&name) {
+            let _ = self.ensure_connected(name.clone());
+        }
+
+        let sess = self.session(&name)?;
+        let node = &self.nodes[&name];
+        let script = format!(
+            "p=$({0}); \
+             if ! [ \"$p\" -gt 0 ] 2> /dev/null || ! kill -0 \"$p\" 2> /dev/null; then exit 0; fi; \
+             echo \"pid=$p\"; echo \"now=$(date +%s)\"; {1}",
+            self.pid_command(node, &subject), unix::usage_script(&node.os, RESOURCE_SAMPLE_SECS));
+        let timeout = self.get_command_timeout(node).map(|t| t + Duration::from_secs(RESOURCE_SAMPLE_SECS));
+        let out = self.execute(sess, script, timeout)?;
+        if !out.success() {
+            return Err(DeltaError::CommandFailed(format!("resource usage: {}", out.stderr.trim())));
+        }
+
+        let fields: HashMap<&str, &str> = out.stdout.lines()
+            .filter_map(|l| l.split_once('='))
+            .map(|(k, v)| (k, v.trim()))
+            .collect();
+        let number = |key: &str| fields.get(key).and_then(|v| v.parse::<u64>().ok());
+
+        let Some(pid) = fields.get("pid").and_then(|v| v.parse::<u32>().ok()) else {
+            return Ok(None);
+        };
+
+        let mut usage = ResourceUsage::new(subject, pid);
+        usage.cpu_percent = match (number("ticks"), number("hz")) {
+            (Some(ticks), Some(hz)) if hz > 0 =>
+                ticks as f64 * 100.0 / (hz as f64 * RESOURCE_SAMPLE_SECS as f64),
+            _ => fields.get("pcpu").and_then(|v| v.parse::<f64>().ok()).unwrap_or(0.0),
+        };
+        usage.rss_bytes = number("rss").unwrap_or(0);
+        usage.open_fds = number("fds");
+        usage.threads = fields.get("threads").and_then(|v| v.parse::<u32>().ok());
+        usage.sampled_at = number("now").unwrap_or(0);
+        return Ok(Some(usage));
+    }
+
+    /* Usage of every running instance on every connected node; failures are logged and skipped */
+    pub fn resource_snapshot(&mut self) -> HashMap<String, Vec<ResourceUsage>> {
+        let mut snapshot = HashMap::new();
+        let names: Vec<String> = self.instances.keys().cloned().collect();
+        for name in names {
+            let mut usages = Vec::new();
+            for subject in DeploySubject::all() {
+                match self.resource_usage(name.clone(), subject) {
+                    Ok(Some(usage)) => usages.push(usage),
+                    Ok(None) => {}
+                    Err(e) => error!("Failed to sample resource usage: {} ({})", name, scrub(&e.to_string())),
+                }
+            }
+            snapshot.insert(name, usages);
+        }
+        return snapshot;
+    }
+
+    /* Follows both logs of the instance over a separate connection, until the stream is dropped */
+    pub fn stream_logs(&self, name: String, subject: DeploySubject) -> Result<LogStream, DeltaError> {
+        if !self.nodes.contains_key(&name) {
+            return Err(DeltaError::NodeNotFound(name));
+        }
+
+        let sess = self.open_side_session(&name, "log stream")?;
+        return LogStream::open(sess, &self.get_log_dir(&self.nodes[&name], &subject), LOG_STREAM_BACKLOG);
+    }
+
+    /*
+     * Address clients of the server reach it on: the bind parameters recorded
+     * by the last run() when connected, the configured ones otherwise. A
+     * wildcard bind is reported as the node's own address. A dynamic port is
+     * only known from what run() recorded.
+     */
+    pub fn endpoint(&self, name: String, subject: DeploySubject) -> Result<Endpoint, DeltaError> {
+        let Some(node) = self.nodes.get(&name) else {
+            return Err(DeltaError::NodeNotFound(name));
+        };
+
+        let recorded = self.session(&name).ok().and_then(|sess| {
+            return self.read_bind_params(sess, node, &self.get_remote_dir(node, &subject), self.get_command_timeout(node));
+        });
+        let (mut addr, port) = match recorded {
+            Some(params) => params,
+            None => match self.conn_params(node, &subject)? {
+                (addr, BindPort::Fixed(port)) => (addr, port),
+                _ => return Err(DeltaError::CommandFailed(format!("no bind port recorded: {}", name))),
+            },
+        };
+
+        if addr.parse::<IpAddr>().map(|a| a.is_unspecified()).unwrap_or(false) {
+            addr = self.get_ssh_address(node)?.0;
+        }
+        return Ok(Endpoint::new("tcp", addr, port));
+    }
+
+    /*
+     * A stream connected to the server. A loopback endpoint, or any endpoint
+     * of a node behind a jump host, is reached through a direct-tcpip channel
+     * of a session of its own, so the stream outlives pool operations.
+     * Instances on the local transport are connected to directly.
+     */
+    pub fn connect_client(&self, name: String, subject: DeploySubject) -> Result<TcpStream, DeltaError> {
+        let endpoint = self.endpoint(name.clone(), subject)?;
+        let node = &self.nodes[&name];
+        let connect_timeout = self.get_timeout(node, NodeParameters::ConnectTimeout, DEFAULT_CONNECT_TIMEOUT);
+        let direct = self.get_conn_method(node) == ConnMethod::Local
+            || (!endpoint.is_loopback() && self.get_node_param(node, NodeParameters::JumpHost).is_empty());
+        if direct {
+            return Ok(connect_tcp(&endpoint.addr, endpoint.port, connect_timeout)?);
+        }
+
+        let sess = self.open_side_session(&name, "client tunnel")?;
+        let channel = sess.channel_direct_tcpip(&endpoint.addr, endpoint.port, None)?;
+        let stream = local_tunnel(sess, channel)
+            .ok_or(DeltaError::Io(io::Error::other("failed to set up the local end of the tunnel")))?;
+        info!("Client tunnel to {} opened: {}", endpoint, name);
+        return Ok(stream);
+    }
+
+    /*
+     * Local address the subject's server can be reached on. A loopback
+     * endpoint, or any endpoint of a node behind a jump host, gets a loopback
+     * listener tunnelled over SSH, kept open until unforward() or until the
+     * node disconnects; calling again returns the same listener while the
+     * endpoint stays the same. The tunnel has a session of its own, as the
+     * node's session is busy with pool operations. Other endpoints, and
+     * instances on the local transport, are returned as they are.
+     */
+    pub fn forward(&mut self, name: String, subject: DeploySubject) -> Result<SocketAddr, DeltaError> {
+        let endpoint = self.endpoint(name.clone(), subject.clone())?;
+        let node = &self.nodes[&name];
+        let direct = self.get_conn_method(node) == ConnMethod::Local
+            || (!endpoint.is_loopback() && self.get_node_param(node, NodeParameters::JumpHost).is_empty());
+        if direct {
+            return (endpoint.addr.as_str(), endpoint.port).to_socket_addrs()?.next()
+                .ok_or(DeltaError::Io(io::Error::other(format!("failed to resolve {}", endpoint.addr))));
+        }
+
+        let Some(inst) = self.instances.get(&name) else {
+            return Err(DeltaError::NodeNotConnected(name));
+        };
+        if let Some(forward) = inst.forwards.get(&subject) {
+            if forward.forwards_to(&endpoint.addr, endpoint.port) {
+                return Ok(forward.local_addr());
+            }
+        }
+
+        let sess = self.open_side_session(&name, "forward")?;
+        let forward = PortForward::open(sess, &endpoint.addr, endpoint.port)?;
+        let local_addr = forward.local_addr();
+        if let Some(inst) = self.instances.get_mut(&name) {
+            inst.forwards.insert(subject, forward);
+        }
+        return Ok(local_addr);
+    }
+
+    /*
+     * SSH session of its own for streams that outlive pool operations. Only
+     * SSH nodes have one to give; the others say so rather than get a session
+     * to an address that may not even run sshd.
+     */
+    fn open_side_session(&self, name: &str, purpose: &str) -> Result<Session, DeltaError> {
+        let method = match self.connector {
+            Some(_) => ConnMethod::Custom,
+            None => self.get_conn_method(&self.nodes[name]),
+        };
+        if matches!(method, ConnMethod::Local | ConnMethod::Docker | ConnMethod::Kubernetes | ConnMethod::Custom) {
+            return Err(DeltaError::Unsupported(format!("{} over the {:?} transport: {}", purpose, method, name)));
+        }
+
+        return self.open_session(name).map(|(sess, _address)| sess).map_err(|result| {
+            error!("Failed to open {}: {} ({:?})", purpose, name, result);
+            DeltaError::NodeNotConnected(name.to_string())
+        });
+    }
+
+    /* Closes the tunnel forward() opened; false if there was none */
+    pub fn unforward(&mut self, name: String, subject: DeploySubject) -> bool {
+        return self.instances.get_mut(&name)
+            .and_then(|inst| inst.forwards.remove(&subject))
+            .is_some();
+    }
+
+    /* Bind address and port recorded by the last run(), if both are still there and valid */
+    fn read_bind_params(&self, sess: &dyn Transport, node: &Node, remote_dir: &str,
+                        timeout: Option<Duration>) -> Option<(String, u16)> {
+        let cmd = match self.is_windows(node) {
+            true => windows::powershell(&windows::read_bind_script(remote_dir)),
+            false => format!("cat {0}/bind_addr {0}/bind_port", shell_quote(remote_dir)),
+        };
+        let out = self.execute(sess, cmd, timeout).ok()?;
+        if !out.success() {
+            return None;
+        }
+
+        let mut lines = out.stdout.lines();
+        let bind_addr = parse_bind_addr(lines.next()?)?;
+        let bind_port = lines.next()?.trim().parse::<u16>().ok().filter(|p| *p > 0)?;
+        return Some((bind_addr, bind_port));
+    }
+
+    /* Stops the instance started by run(), killing it if it outlives the grace period */
+    pub fn stop(&mut self, name: String, subject: DeploySubject) -> StopResult {
+        let _lock = self.lock_node(&name);
+        let op = OperationScope::enter();
+        let span = info_span!("stop", node = %name, subject = %subject, op_id = %op.id());
+        let _entered = span.enter();
+        let started = Instant::now();
+        let result = self.stop_node(name.clone(), subject.clone());
+        self.audit(&name, AuditAction::Stop, subject.to_string(), format!("{:?}", result),
+                   matches!(result, StopResult::Ok(_) | StopResult::NotRunning), started);
+        if matches!(result, StopResult::Ok(_)) {
+            self.event_bus.publish(PoolEvent::InstanceStopped { node: name, subject });
+        }
+        return result;
+    }
+
+    fn stop_node(&mut self, name: String, subject: DeploySubject) -> StopResult {
+        if !self.nodes.contains_key(&name) {
+            error!("Node doesn't exist: {}", name);
+            return StopResult::NodeNotFound;
+        }
+
+        if self.instances.contains_key(&name) {
+            let _ = self.ensure_connected(name.clone());
+        }
+
+        let sess = match self.session(&name) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("{}", e);
+                return StopResult::NodeNotConnected;
+            }
+        };
+
+        let node = &self.nodes[&name];
+        let stopped = self.stop_instance(sess, node, &subject, self.get_command_timeout(node));
+
+        let mut conn_status = self.instances[&name].conn_status.clone();
+        let mut subject_st = conn_status.get_subject(subject.clone());
+        let result = match stopped {
+            Ok(Some(method)) => StopResult::Ok(method),
+            Ok(None) => StopResult::NotRunning,
+            Err(e @ DeltaError::InvalidParameter(..)) => {
+                error!("Invalid stop parameters: {} ({})", name, e);
+                return StopResult::InvalidArgument;
+            }
+            Err(e) => {
+                error!("Failed to stop instance: {} ({})", name, scrub(&e.to_string()));
+                self.record_error(&name, format!("stop {}: {}", subject, e));
+                return StopResult::StopFailed;
+            }
+        };
+
+        subject_st.running = false;
+        subject_st.restarts = 0;
+        subject_st.restart_after = None;
+        conn_status.set_subject(subject, subject_st);
+        self.set_state(name.clone(), conn_status);
+
+        info!("Stopped instance: {}", name);
+        return result;
+    }
+
+    /*
+     * StopSignal (SIGTERM by default), then SIGKILL once StopGracePeriod
+     * passes; the pid file goes away either way. Ok(None) if nothing was
+     * running.
+     */
+    fn stop_instance(&self, sess: &dyn Transport, node: &Node, subject: &DeploySubject,
+                     timeout: Option<Duration>) -> Result<Option<StopMethod>, DeltaError> {
+        let (signal, grace) = self.get_stop_params(node)?;
+        let remote_dir = self.get_remote_dir(node, subject);
+        let mode = self.get_run_mode(node);
+        /* Units stop with the signal and grace period they were started with */
+        let script = if self.is_windows(node) {
+            windows::powershell(&windows::stop_script(&remote_dir, grace))
+        } else if mode.is_systemd() {
+            systemd::stop_script(&mode, subject, &remote_dir)
+        } else {
+            format!(
+            "f={0}/pid; p=$(cat \"$f\" 2> /dev/null); \
+             if ! [ \"$p\" -gt 0 ] 2> /dev/null || ! kill -0 \"$p\" 2> /dev/null; then \
+                 rm -f \"$f\"; echo not-running; exit 0; \
+             fi; \
+             kill -{2} \"$p\"; i=0; \
+             while kill -0 \"$p\" 2> /dev/null && [ $i -lt {1} ]; do sleep 1; i=$((i + 1)); done; \
+             if kill -0 \"$p\" 2> /dev/null; then kill -KILL \"$p\" && echo killed; else echo terminated; fi; \
+             rm -f \"$f\"",
+            shell_quote(&remote_dir), grace, signal)
+        };
+
+        /* The command may legitimately take the whole grace period */
+        let timeout = timeout.map(|t| t.max(Duration::from_secs(grace + 5)));
+        let out = self.execute(sess, script, timeout)?;
+        return match out.stdout.trim() {
+            "not-running" => Ok(None),
+            "terminated" if self.is_windows(node) => Ok(Some(StopMethod::Graceful { signal: "taskkill".to_string() })),
+            "terminated" => Ok(Some(StopMethod::Graceful { signal: format!("SIG{}", signal) })),
+            "killed" => Ok(Some(StopMethod::Killed)),
+            _ => Err(DeltaError::CommandFailed(format!("stop: {}", out.stderr.trim()))),
+        };
+    }
+
+    /*
+     * Some(pid) when something listens on the port, the pid being None if
+     * the node won't tell whose socket it is. A failed check counts as a free
+     * port, the start itself reports a real conflict then.
+     */
+    fn port_owner(&self, sess: &dyn Transport, node: &Node, port: u16,
+                  timeout: Option<Duration>) -> Option<Option<u32>> {
+        let script = match self.is_windows(node) {
+            true => windows::powershell(&windows::port_owner_script(port)),
+            false => unix::port_owner_script(&node.os, port),
+        };
+        let out = self.execute(sess, script, timeout).ok()?;
+        let fields: HashMap<&str, &str> = out.stdout.lines()
+            .filter_map(|l| l.split_once('='))
+            .map(|(k, v)| (k.trim(), v.trim()))
+            .collect();
+        if fields.get("in_use") != Some(&"1") {
+            return None;
+        }
+        return Some(fields.get("pid").and_then(|v| v.parse::<u32>().ok()));
+    }
+
+    /* Signal name without the SIG prefix, and the grace period in seconds */
+    fn get_stop_params(&self, node: &Node) -> Result<(String, u64), DeltaError> {
+        let value = self.try_get_node_param(node, NodeParameters::StopSignal)?;
+        let upper = value.trim().to_uppercase();
+        let signal = upper.strip_prefix("SIG").unwrap_or(&upper);
+        let signal = match signal {
+            "" => DEFAULT_STOP_SIGNAL,
+            s if STOP_SIGNALS.contains(&s) => s,
+            _ => return Err(DeltaError::InvalidParameter(NodeParameters::StopSignal.to_string(), value)),
+        };
+
+        let value = self.try_get_node_param(node, NodeParameters::StopGracePeriod)?;
+        let grace = match value.trim() {
+            "" => DEFAULT_STOP_GRACE_PERIOD,
+            v => v.parse::<u64>()
+                .map_err(|_| DeltaError::InvalidParameter(NodeParameters::StopGracePeriod.to_string(), value.clone()))?,
+        };
+
+        return Ok((signal.to_string(), grace));
+    }
+
+    fn upload_file(&self, name: &str, sess: &dyn Transport, method: TransferMethod, resume: bool,
+                   local_path: String, remote_path: String) -> Result<(), DeltaError> {
+        let file = File::open(local_path)?;
+        let file_size = file.metadata()?.len();
+
+        let mut offset = 0;
+        if resume {
+            offset = sess.file_size(&remote_path, &method).unwrap_or(0);
+            if offset > file_size {
+                offset = 0;
+            }
+            if offset > 0 {
+                info!("Resuming upload to {} at {} of {} bytes", name, offset, file_size);
+            }
+        }
+
+        let _permit = self.limiter.upload();
+        let mut reader = BufReader::new(file);
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut on_progress = |sent: u64| self.report_upload(name, offset + sent, file_size);
+        on_progress(0);
+
+        return sess.upload(&mut reader, &remote_path, 0o644, file_size - offset, offset,
+                           &method, &mut on_progress);
+    }
+
+    /* Returns the bytes uploaded; directories get the local modes too, except on Windows */
+    fn upload_tree(&self, name: &str, sess: &dyn Transport, node: &Node, local_dir: &Path,
+                   remote_dir: &str) -> Result<u64, DeltaError> {
+        let (dirs, files) = walk_dir(local_dir)?;
+
+        let mut paths = vec![remote_dir.to_string()];
+        let mut modes = vec![file_mode(local_dir)];
+        for dir in &dirs {
+            paths.push(format!("{}/{}", remote_dir, dir.to_string_lossy()));
+            modes.push(file_mode(&local_dir.join(dir)));
+        }
+        let mkdir = match self.is_windows(node) {
+            true => windows::powershell(&windows::mkdir_script(&paths)),
+            false => format!("mkdir -p {}", paths.iter().map(|p| shell_quote(p)).collect::<Vec<_>>().join(" ")),
+        };
+        let timeout = self.get_command_timeout(node);
+        let out = self.execute(sess, mkdir, timeout)?;
+        if !out.success() {
+            return Err(DeltaError::CommandFailed(
+                format!("failed to create directories: {}", out.stderr.trim())));
+        }
+
+        let method = TransferMethod::from_param(&self.get_node_param(node, NodeParameters::TransferMethod));
+        let mut total = 0;
+        for file in &files {
+            total += local_dir.join(file).metadata()?.len();
+        }
+
+        let _permit = self.limiter.upload();
+        let mut sent = 0;
+        self.report_upload(name, sent, total);
+        for file in &files {
+            let local_path = local_dir.join(file);
+            let local_file = File::open(&local_path)?;
+            let size = local_file.metadata()?.len();
+            let remote_path = format!("{}/{}", remote_dir, file.to_string_lossy());
+
+            let mut on_progress = |n: u64| self.report_upload(name, sent + n, total);
+            sess.upload(&mut BufReader::new(local_file), &remote_path, file_mode(&local_path), size, 0,
+                        &method, &mut on_progress)?;
+            sent += size;
+        }
+
+        /* Last, so a read-only directory doesn't keep its files out */
+        if !self.is_windows(node) {
+            let chmod: Vec<String> = paths.iter().zip(&modes)
+                .map(|(path, mode)| format!("chmod {:o} {}", mode, shell_quote(path)))
+                .collect();
+            let out = self.execute(sess, chmod.join(" && "), timeout)?;
+            if !out.success() {
+                return Err(DeltaError::CommandFailed(
+                    format!("failed to set directory modes: {}", out.stderr.trim())));
+            }
+        }
+
+        return Ok(total);
+    }
+
+    fn report_upload(&self, name: &str, sent: u64, total: u64) {
+        self.update_progress(name, |p| {
+            p.bytes_sent = sent;
+            p.bytes_total = total;
+        });
+        if let Some(callback) = &self.progress_callback {
+            callback(name, sent, total);
+        }
+    }
+
+    fn execute(&self, sess: &dyn Transport, cmd: String,
+               timeout: Option<Duration>) -> Result<ExecOutput, DeltaError> {
+        check_cancelled()?;
+        return sess.execute(&cmd, timeout, None);
+    }
+
+    /* Like execute, but forwards output to the pool's output callback, if any */
+    fn execute_reported(&self, name: &str, sess: &dyn Transport, cmd: String,
+                        timeout: Option<Duration>) -> Result<ExecOutput, DeltaError> {
+        check_cancelled()?;
+        let sink = self.output_sink(name);
+        return sess.execute(&cmd, timeout, sink.as_deref());
+    }
+
+    fn execute_vec(&self, name: &str, sess: &dyn Transport, commands: Vec<String>,
+                   timeout: Option<Duration>) -> Result<ExecOutput, DeltaError> {
+        check_cancelled()?;
+        let sink = self.output_sink(name);
+        return sess.execute_script(&commands, timeout, sink.as_deref());
+    }
+
+    fn output_sink(&self, name: &str) -> Option<Box<LineSink>> {
+        let callback = self.output_callback.clone()?;
+        let name = name.to_string();
+        return Some(Box::new(move |stream, line| callback(&name, stream, line)));
+    }
+
+    fn check_output(name: &str, step: &str, output: &ExecOutput) -> bool {
+        if !output.success() {
+            error!("Failed to {}: {} (exit code {}, '{}')",
+                   step, name, output.exit_code, scrub(output.stderr.trim()));
+            return false;
+        }
+
+        return true;
+    }
+
+    /* Username, password, identity file, key passphrase */
+    fn get_credentials(&self, node: &Node)
+                       -> Result<(String, Secret<String>, String, Secret<String>), DeltaError> {
+        return Ok((
+            self.try_get_node_param(node, NodeParameters::Username)?,
+            Secret::new(self.try_get_node_param(node, NodeParameters::Password)?),
+            self.try_get_node_param(node, NodeParameters::IdentityFile)?,
+            Secret::new(self.try_get_node_param(node, NodeParameters::KeyPassphrase)?),
+        ));
+    }
+
+    /* Host and port from fqdn; without a port there, SshPort or 22 */
+    fn get_ssh_address(&self, node: &Node) -> Result<(String, u16), DeltaError> {
+        let (host, port) = parse_host_port(&node.fqdn)?;
+        return match port {
+            Some(p) => Ok((host, p)),
+            None => Ok((host, self.get_port(node, NodeParameters::SshPort, DEFAULT_SSH_PORT)?)),
+        };
+    }
+
+    fn get_timeout(&self, node: &Node, param: NodeParameters, default_secs: u64) -> Duration {
+        let value = self.get_node_param(node, param);
+        return match value.parse::<u64>() {
+            Ok(secs) if secs > 0 => Duration::from_secs(secs),
+            _ => Duration::from_secs(default_secs),
+        };
+    }
+
+    /* Bad values are reported by check_deploy_params(), here they read as the default */
+    fn get_remote_dir(&self, node: &Node, subject: &DeploySubject) -> String {
+        let default = self.platform_default(node, subject.default_remote_dir());
+        return self.get_path(node, subject.remote_dir_param(), &default)
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or(default);
+    }
+
+    /* Where the active tree lives: the remote dir itself or its "current" link */
+    fn get_install_dir(&self, node: &Node, subject: &DeploySubject) -> String {
+        let remote_dir = self.get_remote_dir(node, subject);
+        if self.is_versioned(node) {
+            return format!("{}/current", remote_dir);
+        }
+        return remote_dir;
+    }
+
+    /* Custom test commands run from inside the freshly installed tree */
+    fn get_test_command(&self, node: &Node, subject: &DeploySubject, install_dir: &str) -> String {
+        let context = self.escaped(node, CommandContext::new(subject, &self.get_remote_dir(node, subject), install_dir));
+        let cmd = self.get_node_param(node, subject.test_command_param());
+        if self.is_windows(node) {
+            return windows::powershell(&match cmd.trim().is_empty() {
+                true => context.render(windows::DEFAULT_TEST_TEMPLATE),
+                false => format!("Set-Location -LiteralPath {}; {}", ps_quote(install_dir), context.render(&cmd)),
+            });
+        }
+        if cmd.trim().is_empty() {
+            return context.render(DEFAULT_TEST_TEMPLATE);
+        }
+        return format!("cd {} && {}", shell_quote(install_dir), context.render(&cmd));
+    }
+
+    /* Placeholders of the active tree; bind values are added by whoever knows them */
+    fn command_context(&self, node: &Node, subject: &DeploySubject) -> CommandContext {
+        let context = CommandContext::new(subject, &self.get_remote_dir(node, subject),
+                                          &self.get_install_dir(node, subject))
+            .set("log_dir", &self.get_log_dir(node, subject))
+            .set("unit", &systemd::unit_name(subject))
+            .set("systemctl", systemd::systemctl(&self.get_run_mode(node)));
+        return self.escaped(node, context);
+    }
+
+    /* Placeholder values escaped for the language the node's templates are in */
+    fn escaped(&self, node: &Node, context: CommandContext) -> CommandContext {
+        return match self.is_windows(node) {
+            true => context.escape_with(ps_escape),
+            false => context,
+        };
+    }
+
+    /* Output of the instance, kept with the tree so undeploy() takes it along */
+    fn get_log_dir(&self, node: &Node, subject: &DeploySubject) -> String {
+        return format!("{}/logs", self.get_remote_dir(node, subject));
+    }
+
+    /* Shell command printing the pid of the instance, if there is one */
+    fn pid_command(&self, node: &Node, subject: &DeploySubject) -> String {
+        let mode = self.get_run_mode(node);
+        if mode.is_systemd() {
+            return systemd::main_pid_command(&mode, subject);
+        }
+        return format!("cat {}/pid 2> /dev/null", shell_quote(&self.get_remote_dir(node, subject)));
+    }
+
+    /* Pod named by KubePod, or the node's address; namespace and container default to the pod's */
+    #[cfg(feature = "kubernetes")]
+    fn open_kubernetes(&self, node: &Node) -> Result<(ConnMethod, Box<dyn Transport>, String), ConnectResult> {
+        let mut pod = self.get_node_param(node, NodeParameters::KubePod);
+        if pod.is_empty() {
+            pod = node.fqdn.clone();
+        }
+        let transport = KubernetesTransport::open(&self.get_node_param(node, NodeParameters::KubeNamespace), &pod,
+                                                  &self.get_node_param(node, NodeParameters::KubeContainer))?;
+        return Ok((ConnMethod::Kubernetes, Box::new(transport), pod));
+    }
+
+    #[cfg(not(feature = "kubernetes"))]
+    fn open_kubernetes(&self, node: &Node) -> Result<(ConnMethod, Box<dyn Transport>, String), ConnectResult> {
+        error!("Kubernetes transport requires the kubernetes feature: {}", node.fqdn);
+        return Err(ConnectResult::InvalidParameter);
+    }
+
+    /* Held for the whole of an operation that changes what is on the node */
+    fn lock_node(&self, name: &str) -> Option<NodeLock> {
+        return self.nodes.get(name).map(|node| NodeLock::acquire(&node.fqdn));
+    }
+
+    fn get_conn_method(&self, node: &Node) -> ConnMethod {
+        return ConnMethod::from_param(&self.get_node_param(node, NodeParameters::Transport), &node.fqdn);
+    }
+
+    fn get_run_mode(&self, node: &Node) -> RunMode {
+        return RunMode::from_param(&self.get_node_param(node, NodeParameters::RunMode));
+    }
+
+    fn command_template(&self, node: &Node, param: NodeParameters, default: &str) -> String {
+        let template = self.get_node_param(node, param);
+        if template.trim().is_empty() {
+            return default.to_string();
+        }
+        return template;
+    }
+
+    fn is_versioned(&self, node: &Node) -> bool {
+        return self.get_bool(node, NodeParameters::VersionedDeploy, false).unwrap_or(false);
+    }
+
+    fn get_remote_tmp_dir(&self, node: &Node) -> String {
+        let default = self.platform_default(node, DEFAULT_REMOTE_TMP_DIR);
+        return self.get_path(node, NodeParameters::RemoteTmpDir, &default)
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or(default);
+    }
+
+    /* Defaults to a directory under RemoteTmpDir, shared by the subjects */
+    fn get_artifact_cache_dir(&self, node: &Node) -> String {
+        let default = format!("{}/delta-artifacts", self.get_remote_tmp_dir(node));
+        return self.get_path(node, NodeParameters::ArtifactCacheDir, &default)
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or(default);
+    }
+
+    fn get_artifacts_kept(&self, node: &Node) -> usize {
+        return match self.get_node_param(node, NodeParameters::ArtifactCacheSize).trim().parse::<usize>() {
+            Ok(n) if n > 0 => n,
+            _ => DEFAULT_ARTIFACTS_KEPT,
+        };
+    }
+
+    fn is_windows(&self, node: &Node) -> bool {
+        return node.platform_kind == PlatformKind::Windows;
+    }
+
+    /* Defaults under /tmp move to %TEMP% on Windows */
+    fn platform_default(&self, node: &Node, default: &str) -> String {
+        if self.is_windows(node) && !node.temp_dir.is_empty() {
+            if let Some(rest) = default.strip_prefix("/tmp") {
+                return format!("{}{}", node.temp_dir, rest);
+            }
+        }
+        return default.to_string();
+    }
 
-        return s;
+    /* Typed deploy params, so a typo fails the deploy instead of being ignored */
+    fn check_deploy_params(&self, node: &Node, subject: &DeploySubject) -> Result<(), DeltaError> {
+        self.get_path(node, subject.remote_dir_param(), subject.default_remote_dir())?;
+        self.get_path(node, NodeParameters::RemoteTmpDir, DEFAULT_REMOTE_TMP_DIR)?;
+        self.get_path(node, NodeParameters::ArtifactCacheDir, DEFAULT_REMOTE_TMP_DIR)?;
+        self.get_bool(node, NodeParameters::VersionedDeploy, false)?;
+        self.get_bool(node, NodeParameters::SyncMode, false)?;
+        self.get_bool(node, NodeParameters::ResumableUpload, false)?;
+        self.get_bind_port(node, subject)?;
+        return Ok(());
     }
 
-    fn execute_vec(&self, sess: &Session, commands: Vec<String>) -> String {
-        let mut channel = sess.channel_session().unwrap();
-        let mut exec_result : String = "".to_string();
-        channel.shell().unwrap();
-        for command in commands {
-            channel.write_all(command.as_bytes()).unwrap();
-            channel.write_all(b"\n").unwrap();
+    /*
+     * Everything is quoted on the way out; what quoting can't make safe is
+     * rejected, control characters included, as a line break would end the
+     * here-document the systemd launch script is written with.
+     */
+    fn check_run_options(&self, options: &RunOptions) -> Result<(), DeltaError> {
+        if let Some(key) = options.env.keys().find(|k| !is_env_name(k)) {
+            return Err(DeltaError::InvalidParameter("env".to_string(), key.clone()));
         }
-        channel.send_eof().unwrap();
-        channel.read_to_string(&mut exec_result).unwrap();
 
-        return exec_result;
+        /* Args and dirs may carry credentials, errors name where the bad value is instead */
+        if let Some(index) = options.extra_args.iter().position(|a| !is_shell_safe(a)) {
+            return Err(DeltaError::InvalidParameter("extra_args".to_string(), format!("argument {}", index)));
+        }
+
+        if let Some((key, _)) = options.env.iter().find(|(_, v)| !is_shell_safe(v)) {
+            return Err(DeltaError::InvalidParameter("env".to_string(), key.clone()));
+        }
+
+        if let Some(dir) = &options.working_dir {
+            if dir.is_empty() || !is_shell_safe(dir) {
+                return Err(DeltaError::InvalidParameter("working_dir".to_string(), "empty or control characters".to_string()));
+            }
+        }
+        return Ok(());
+    }
+
+    fn get_command_timeout(&self, node: &Node) -> Option<Duration> {
+        return match self.get_node_param(node, NodeParameters::CommandTimeout).parse::<u64>() {
+            Ok(secs) if secs > 0 => Some(Duration::from_secs(secs)),
+            _ => None,
+        };
+    }
+
+    fn get_jump_host(&self, node: &Node, fqdn: String) -> Result<JumpHost, DeltaError> {
+        let mut username = self.try_get_node_param(node, NodeParameters::JumpUsername)?;
+        if username.is_empty() {
+            username = self.try_get_node_param(node, NodeParameters::Username)?;
+        }
+
+        return Ok(JumpHost {
+            fqdn,
+            username,
+            password: Secret::new(self.try_get_node_param(node, NodeParameters::JumpPassword)?),
+            known_hosts: KnownHosts::from_param(
+                self.get_node_param(node, NodeParameters::KnownHostsFile)),
+            strict: self.get_node_param(node, NodeParameters::StrictHostKeyChecking) == "yes",
+            connect_timeout: self.get_timeout(node, NodeParameters::ConnectTimeout,
+                                              DEFAULT_CONNECT_TIMEOUT),
+            handshake_timeout: self.get_timeout(node, NodeParameters::HandshakeTimeout,
+                                                DEFAULT_HANDSHAKE_TIMEOUT),
+        });
+    }
+
+    fn session(&self, name: &str) -> Result<&dyn Transport, DeltaError> {
+        if !self.is_session_usable(name) {
+            return Err(DeltaError::NodeNotConnected(name.to_string()));
+        }
+
+        return match self.instances[name].transport.as_ref() {
+            Some(t) => Ok(t.as_ref()),
+            None => Err(DeltaError::NodeNotConnected(name.to_string())),
+        };
+    }
+
+    fn is_session_usable(&self, name: &str) -> bool {
+        return match self.instances.get(name) {
+            Some(inst) => inst.conn_status.connected && inst.transport.is_some(),
+            None => false,
+        };
+    }
+
+    fn update_progress<F: FnOnce(&mut DeployProgress)>(&self, name: &str, f: F) {
+        let mut progress = self.deploy_progress.lock().unwrap_or_else(|e| e.into_inner());
+        let mut changed = None;
+        if let Some(p) = progress.get_mut(name) {
+            let phase = p.phase.clone();
+            f(p);
+            if p.phase != phase {
+                info!(phase = %p.phase, "Deploy phase: {}", p.phase);
+                changed = Some((p.subject.clone(), p.phase.clone()));
+            }
+        } else {
+            let mut p = DeployProgress::new(DeploySubject::Sa);
+            f(&mut p);
+            progress.insert(name.to_string(), p);
+        }
+        drop(progress);
+
+        /* Outside the lock, subscribers may well ask for the progress */
+        if let Some((subject, phase)) = changed {
+            self.event_bus.publish(PoolEvent::DeployPhaseChanged { node: name.to_string(), subject, phase });
+        }
+    }
+
+    /* Logs the outcome, adds it to the node's history and hands it to the audit sink */
+    fn audit(&self, name: &str, action: AuditAction, detail: String, result: String, success: bool,
+             started: Instant) {
+        info!(outcome = %scrub(&result), success, "{} finished: {}", action, scrub(&detail));
+        if let Some(inst) = self.instances.get(name) {
+            let duration = started.elapsed();
+            let finished_at = NodePool::unix_millis(SystemTime::now());
+            inst.record(OperationRecord {
+                action: action.clone(),
+                detail: scrub(&detail),
+                result: scrub(&result),
+                success,
+                started_at: finished_at.saturating_sub(duration.as_millis() as u64),
+                finished_at,
+                duration_ms: duration.as_millis() as u64,
+                operation_id: current_operation_id().unwrap_or_default(),
+            });
+        }
+
+        let Some(sink) = &self.audit_sink else {
+            return;
+        };
+
+        let (fqdn, remote_user) = match self.nodes.get(name) {
+            Some(node) => (node.fqdn.clone(), self.get_node_param(node, NodeParameters::Username)),
+            None => ("".to_string(), "".to_string()),
+        };
+
+        sink.record(&AuditRecord {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            user: env::var("USER").or_else(|_| env::var("USERNAME")).unwrap_or_default(),
+            remote_user,
+            node: name.to_string(),
+            fqdn,
+            action,
+            detail: scrub(&detail),
+            result: scrub(&result),
+            success,
+            operation_id: current_operation_id().unwrap_or_default(),
+        });
+    }
+
+    fn audit_upload<T>(&self, name: &str, local: &str, remote: &str, result: &Result<T, DeltaError>,
+                       started: Instant) {
+        let (outcome, success) = match result {
+            Ok(_) => ("Ok".to_string(), true),
+            Err(e) => (e.to_string(), false),
+        };
+        self.audit(name, AuditAction::Upload, format!("{} -> {}", local, remote), outcome, success, started);
+    }
+
+    fn unix_millis(time: SystemTime) -> u64 {
+        return time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+    }
+
+    fn record_error(&mut self, name: &str, error: String) {
+        if let Some(node) = self.nodes.get_mut(name) {
+            node.last_error = Some(scrub(&error));
+        }
     }
 
     fn set_state(&mut self, name: String, conn_status: ConnStatus)
     {
-        let m = self.instances.get_mut(&name);
-        m.unwrap().conn_status = conn_status.clone();
+        if let Some(inst) = self.instances.get_mut(&name) {
+            inst.conn_status = conn_status;
+        }
     }
 
-    fn infer_conn_params(&self, node: &Node) -> (String, String) {
-        let mut bind_addr = self.get_node_param(node, NodeParameters::BindAddr);
+    /* Bind address and port, a dynamic port being picked on the node */
+    fn infer_conn_params(&self, sess: &dyn Transport, node: &Node, subject: &DeploySubject,
+                         timeout: Option<Duration>) -> Result<(String, u16), DeltaError> {
+        let (bind_addr, port_spec) = self.conn_params(node, subject)?;
+        let bind_port = self.resolve_bind_port(sess, node, subject, &port_spec, timeout)?;
+        return Ok((bind_addr, bind_port));
+    }
 
-        if bind_addr.contains("'") || bind_addr.contains("\"") {
-            error!("Reset bind address due to bad symbols: {}", bind_addr);
-            bind_addr = "".to_string();
+    /* Bind address and port setting as configured */
+    fn conn_params(&self, node: &Node, subject: &DeploySubject) -> Result<(String, BindPort), DeltaError> {
+        let mut bind_addr = self.try_get_node_param(node, NodeParameters::BindAddr)?.trim().to_string();
+        if bind_addr.is_empty() {
+            bind_addr = "127.0.0.1".to_string();
         }
 
-        if bind_addr == "" {
-            bind_addr = "127.0.0.1".to_string();
+        let bind_addr = parse_bind_addr(&bind_addr)
+            .ok_or(DeltaError::InvalidParameter(NodeParameters::BindAddr.to_string(), bind_addr))?;
+
+        return Ok((bind_addr, self.get_bind_port(node, subject)?));
+    }
+
+    /* A port, "auto" for any free ephemeral one or "<lo>-<hi>" for the first free one of a range */
+    fn get_bind_port(&self, node: &Node, subject: &DeploySubject) -> Result<BindPort, DeltaError> {
+        let param = subject.bind_port_param();
+        let value = self.try_get_node_param(node, param.clone())?;
+        if value.trim().is_empty() {
+            return Ok(BindPort::Fixed(subject.default_bind_port()));
+        }
+        return BindPort::parse(&value).ok_or(DeltaError::InvalidParameter(param.to_string(), value));
+    }
+
+    /*
+     * The port to bind: a fixed one as is, a dynamic one out of those nothing
+     * listens on. Ports the other subjects are configured with or last ran on
+     * are skipped too, so subjects sharing a range don't collide.
+     */
+    fn resolve_bind_port(&self, sess: &dyn Transport, node: &Node, subject: &DeploySubject,
+                         port_spec: &BindPort, timeout: Option<Duration>) -> Result<u16, DeltaError> {
+        if let BindPort::Fixed(port) = port_spec {
+            return Ok(*port);
         }
 
-        let mut bind_port = self.get_node_param(node, NodeParameters::BindPort);
-        if !bind_port.parse::<u16>().is_ok() {
-            error!("Reset bind port due to bad symbols: {}", bind_port);
-            bind_port = "".to_string();
+        let script = match self.is_windows(node) {
+            true => windows::powershell(&windows::listening_ports_script()),
+            false => unix::listening_ports_script(&node.os),
+        };
+        let out = self.execute(sess, script, timeout)?;
+        let mut taken = HashSet::new();
+        let mut ephemeral = DEFAULT_EPHEMERAL_PORTS;
+        for line in out.stdout.lines() {
+            match line.split_once('=') {
+                Some(("port", port)) => {
+                    if let Ok(port) = port.trim().parse::<u16>() {
+                        taken.insert(port);
+                    }
+                }
+                Some(("range", range)) => {
+                    let bounds: Vec<u16> = range.split_whitespace().filter_map(|p| p.parse::<u16>().ok()).collect();
+                    if let [lo, hi] = bounds[..] {
+                        if lo > 0 && lo <= hi {
+                            ephemeral = (lo, hi);
+                        }
+                    }
+                }
+                _ => {}
+            }
         }
-        if bind_port == "" {
-            bind_port = "5700".to_string();
+
+        for other in DeploySubject::all().into_iter().filter(|s| s != subject) {
+            if let Ok(BindPort::Fixed(port)) = self.get_bind_port(node, &other) {
+                taken.insert(port);
+            }
+            if let Some((_, port)) = self.read_bind_params(sess, node, &self.get_remote_dir(node, &other), timeout) {
+                taken.insert(port);
+            }
         }
-        return (bind_addr, bind_port)
 
+        /* From the bottom of a configured range; anywhere in the ephemeral one, so concurrent picks spread */
+        let (lo, hi, start) = match port_spec {
+            BindPort::Range(lo, hi) => (*lo, *hi, *lo),
+            _ => {
+                let span = (ephemeral.1 - ephemeral.0) as u32 + 1;
+                let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+                (ephemeral.0, ephemeral.1, ephemeral.0 + (nanos % span) as u16)
+            }
+        };
+        let port = pick_free_port(lo, hi, start, &taken)
+            .ok_or(DeltaError::NoFreePort(format!("{}-{}", lo, hi)))?;
+        info!("Picked bind port {} for {}", port, subject);
+        return Ok(port);
     }
 }
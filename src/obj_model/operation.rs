@@ -0,0 +1,90 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use std::cell::RefCell;
+use uuid::Uuid;
+
+use crate::data_model::operation_result::OperationResult;
+
+thread_local! {
+    static CURRENT_OPERATION: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/*
+ * Operation id of the public call running on this thread. Calls nested in
+ * it (a reconnect inside deploy(), a run() inside rollback()) share the
+ * id, so one id covers everything the caller asked for.
+ */
+pub struct OperationScope {
+    id: String,
+    outermost: bool,
+}
+
+impl OperationScope {
+    pub fn enter() -> OperationScope {
+        return CURRENT_OPERATION.with(|current| {
+            let mut current = current.borrow_mut();
+            if let Some(id) = current.as_ref() {
+                return OperationScope { id: id.clone(), outermost: false };
+            }
+
+            let id = Uuid::new_v4().to_string();
+            *current = Some(id.clone());
+            return OperationScope { id, outermost: true };
+        });
+    }
+
+    /* Outermost scope under an id handed out beforehand, e.g. that of a job */
+    pub fn enter_as(id: &str) -> OperationScope {
+        return CURRENT_OPERATION.with(|current| {
+            let mut current = current.borrow_mut();
+            if let Some(id) = current.as_ref() {
+                return OperationScope { id: id.clone(), outermost: false };
+            }
+
+            *current = Some(id.to_string());
+            return OperationScope { id: id.to_string(), outermost: true };
+        });
+    }
+
+    pub fn id(&self) -> &str {
+        return &self.id;
+    }
+
+    pub fn finish<R>(&self, result: R) -> OperationResult<R> {
+        return OperationResult { operation_id: self.id.clone(), result };
+    }
+}
+
+impl Drop for OperationScope {
+    fn drop(&mut self) {
+        if self.outermost {
+            CURRENT_OPERATION.with(|current| *current.borrow_mut() = None);
+        }
+    }
+}
+
+pub fn current_operation_id() -> Option<String> {
+    return CURRENT_OPERATION.with(|current| current.borrow().clone());
+}
@@ -0,0 +1,173 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::obj_model::jump_host::write_all;
+use crate::obj_model::net::format_host_port;
+use ssh2::{Channel, Session};
+use std::io::{self, ErrorKind, Read};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tracing::{error, info};
+
+/*
+ * Loopback listener whose connections are tunnelled to the target through
+ * direct-tcpip channels of one SSH session. A single thread owns the
+ * session, accepting and pumping every connection with the session
+ * non-blocking; it only blocks to open a channel. Dropping the forward
+ * closes the listener, the connections and the session.
+ */
+pub struct PortForward {
+    local_addr: SocketAddr,
+    target: (String, u16),
+    stop: Arc<AtomicBool>,
+    done: Arc<AtomicBool>,
+}
+
+struct Link {
+    stream: TcpStream,
+    channel: Channel,
+}
+
+impl PortForward {
+    pub fn open(sess: Session, target_host: &str, target_port: u16) -> io::Result<PortForward> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let done = Arc::new(AtomicBool::new(false));
+
+        let target = (target_host.to_string(), target_port);
+        let (thread_target, thread_stop, thread_done) = (target.clone(), stop.clone(), done.clone());
+        thread::spawn(move || {
+            PortForward::serve(sess, listener, &thread_target, &thread_stop);
+            thread_done.store(true, Ordering::SeqCst);
+        });
+
+        info!("Forwarding {} to {}", local_addr, format_host_port(target_host, target_port));
+        return Ok(PortForward { local_addr, target, stop, done });
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        return self.local_addr;
+    }
+
+    /* Still serving the given target */
+    pub fn forwards_to(&self, host: &str, port: u16) -> bool {
+        return !self.done.load(Ordering::SeqCst) && self.target.0 == host && self.target.1 == port;
+    }
+
+    fn serve(sess: Session, listener: TcpListener, target: &(String, u16), stop: &AtomicBool) {
+        sess.set_blocking(false);
+        let mut links: Vec<Link> = Vec::new();
+        let mut buffer = vec![0; 16384];
+        while !stop.load(Ordering::SeqCst) {
+            let mut idle = true;
+
+            match listener.accept() {
+                Ok((stream, _addr)) => {
+                    idle = false;
+                    sess.set_blocking(true);
+                    let channel = sess.channel_direct_tcpip(&target.0, target.1, None);
+                    sess.set_blocking(false);
+                    match channel {
+                        Ok(channel) if stream.set_nonblocking(true).is_ok() => links.push(Link { stream, channel }),
+                        Ok(_channel) => {}
+                        Err(e) => error!("Failed to open forwarded channel to {}: {}",
+                                         format_host_port(&target.0, target.1), e),
+                    }
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => {
+                    error!("Forward listener failed: {}", e);
+                    break;
+                }
+            }
+
+            links.retain_mut(|link| match link.pump(&mut buffer) {
+                Some(active) => {
+                    idle &= !active;
+                    true
+                }
+                None => {
+                    let _ = link.channel.close();
+                    false
+                }
+            });
+
+            if idle {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        for mut link in links {
+            let _ = link.channel.close();
+        }
+    }
+}
+
+impl Drop for PortForward {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Link {
+    /* One round of shuffling both ways: whether bytes moved, None once either end is gone */
+    fn pump(&mut self, buffer: &mut [u8]) -> Option<bool> {
+        let mut active = false;
+
+        match self.stream.read(buffer) {
+            Ok(0) => return None,
+            Ok(n) => {
+                if !write_all(&mut self.channel, &buffer[..n]) {
+                    return None;
+                }
+                active = true;
+            }
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(_e) => return None,
+        }
+
+        match self.channel.read(buffer) {
+            Ok(0) => {
+                if self.channel.eof() {
+                    return None;
+                }
+            }
+            Ok(n) => {
+                if !write_all(&mut self.stream, &buffer[..n]) {
+                    return None;
+                }
+                active = true;
+            }
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(_e) => return None,
+        }
+
+        return Some(active);
+    }
+}
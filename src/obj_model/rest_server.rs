@@ -0,0 +1,209 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::add_node_request::AddNodeRequest;
+use crate::data_model::delta_error::DeltaError;
+use crate::data_model::deploy_subject::DeploySubject;
+use crate::data_model::error_response::ErrorResponse;
+use crate::data_model::node_summary::NodeSummary;
+use crate::data_model::operation_result::OperationResult;
+use crate::data_model::result::add_result::AddResult;
+use crate::data_model::result::connect_result::ConnectResult;
+use crate::data_model::result::deploy_result::DeployResult;
+use crate::data_model::result::disconnect_result::DisconnectResult;
+use crate::data_model::result::remove_result::RemoveResult;
+use crate::data_model::result::run_result::RunResult;
+use crate::data_model::run_options::RunOptions;
+use crate::data_model::run_status::RunStatus;
+use crate::data_model::secret::{register_secret, scrub, Secret};
+use crate::obj_model::async_node_pool::AsyncNodePool;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+use tracing::info;
+
+struct ApiError(DeltaError);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match &self.0 {
+            DeltaError::NodeNotFound(_) => StatusCode::NOT_FOUND,
+            DeltaError::NodeNotConnected(_) | DeltaError::NoFreePort(_) => StatusCode::CONFLICT,
+            DeltaError::InvalidParameter(_, _) | DeltaError::Parse(_) => StatusCode::BAD_REQUEST,
+            DeltaError::Unsupported(_) => StatusCode::NOT_IMPLEMENTED,
+            e if e.is_timeout() => StatusCode::GATEWAY_TIMEOUT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        return (status, Json(ErrorResponse { error: scrub(&self.0.to_string()) })).into_response();
+    }
+}
+
+/*
+ * HTTP front end for a pool. Operations answer 200 with their result enum,
+ * the same value the in-process call returns; only calls returning a
+ * DeltaError map to error statuses.
+ *
+ *   GET    /nodes                          list
+ *   POST   /nodes                          add (AddNodeRequest)
+ *   DELETE /nodes/{name}                   remove
+ *   POST   /nodes/{name}/connect           connect
+ *   POST   /nodes/{name}/disconnect        disconnect
+ *   POST   /nodes/{name}/deploy/{subject}  deploy
+ *   POST   /nodes/{name}/run/{subject}     run (optional RunOptions)
+ *   GET    /nodes/{name}/status/{subject}  status
+ *   GET    /events                         WebSocket, one PoolEvent as JSON per message
+ *
+ * Whoever reaches the API can add nodes and deploy local files to them, so
+ * with a token set every request needs "Authorization: Bearer <token>".
+ * Without one, serve() only binds loopback addresses; anything wider should
+ * have a token and TLS in front.
+ */
+pub struct RestServer {
+    pool: AsyncNodePool,
+    token: Option<Secret<String>>,
+}
+
+impl RestServer {
+    pub fn new(pool: AsyncNodePool) -> RestServer {
+        return RestServer { pool, token: None };
+    }
+
+    pub fn with_token(mut self, token: String) -> RestServer {
+        register_secret(&token);
+        self.token = Some(Secret::new(token));
+        return self;
+    }
+
+    /* For mounting the endpoints into an application's own router; the token check comes along */
+    pub fn router(&self) -> Router {
+        let router = Router::new()
+            .route("/nodes", get(list).post(add))
+            .route("/nodes/{name}", delete(remove))
+            .route("/nodes/{name}/connect", post(connect))
+            .route("/nodes/{name}/disconnect", post(disconnect))
+            .route("/nodes/{name}/deploy/{subject}", post(deploy))
+            .route("/nodes/{name}/run/{subject}", post(run))
+            .route("/nodes/{name}/status/{subject}", get(status))
+            .route("/events", get(events))
+            .with_state(self.pool.clone());
+        return match &self.token {
+            Some(token) => router.layer(middleware::from_fn_with_state(token.clone(), require_token)),
+            None => router,
+        };
+    }
+
+    pub async fn serve(self, addr: SocketAddr) -> Result<(), DeltaError> {
+        if self.token.is_none() && !addr.ip().is_loopback() {
+            return Err(DeltaError::InvalidParameter(
+                "addr".to_string(), format!("{} is not loopback and no token is set", addr)));
+        }
+        let listener = TcpListener::bind(addr).await?;
+        info!("REST server listening on {}", listener.local_addr()?);
+        axum::serve(listener, self.router()).await?;
+        return Ok(());
+    }
+}
+
+async fn require_token(State(token): State<Secret<String>>, request: Request, next: Next) -> Response {
+    let presented = request.headers().get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if !presented.is_some_and(|p| token.matches(p)) {
+        return (StatusCode::UNAUTHORIZED, Json(ErrorResponse { error: "unauthorized".to_string() })).into_response();
+    }
+    return next.run(request).await;
+}
+
+async fn list(State(pool): State<AsyncNodePool>) -> Json<Vec<NodeSummary>> {
+    return Json(pool.list().await);
+}
+
+async fn add(State(pool): State<AsyncNodePool>, Json(req): Json<AddNodeRequest>) -> Json<AddResult> {
+    return Json(pool.add(req.name, req.fqdn, req.params).await);
+}
+
+async fn remove(State(pool): State<AsyncNodePool>, Path(name): Path<String>) -> Json<RemoveResult> {
+    return Json(pool.remove(name).await);
+}
+
+async fn connect(State(pool): State<AsyncNodePool>,
+                 Path(name): Path<String>) -> Json<OperationResult<ConnectResult>> {
+    return Json(pool.connect_tracked(name).await);
+}
+
+async fn disconnect(State(pool): State<AsyncNodePool>, Path(name): Path<String>) -> Json<DisconnectResult> {
+    return Json(pool.disconnect(name).await);
+}
+
+async fn deploy(State(pool): State<AsyncNodePool>,
+                Path((name, subject)): Path<(String, DeploySubject)>) -> Json<OperationResult<DeployResult>> {
+    return Json(pool.deploy_tracked(name, subject).await);
+}
+
+async fn run(State(pool): State<AsyncNodePool>, Path((name, subject)): Path<(String, DeploySubject)>,
+             options: Option<Json<RunOptions>>) -> Json<OperationResult<RunResult>> {
+    let options = options.map(|Json(o)| o).unwrap_or_else(RunOptions::new);
+    return Json(pool.run_tracked(name, subject, options).await);
+}
+
+async fn events(State(pool): State<AsyncNodePool>, ws: WebSocketUpgrade) -> Response {
+    return ws.on_upgrade(move |socket| push_events(pool, socket));
+}
+
+/* Every connection has a subscription of its own, dropped with the connection */
+async fn push_events(pool: AsyncNodePool, mut socket: WebSocket) {
+    let mut events = pool.subscribe();
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let Some(event) = event else {
+                    break;
+                };
+                let Ok(text) = serde_json::to_string(&event) else {
+                    continue;
+                };
+                if socket.send(Message::Text(text.into())).await.is_err() {
+                    break;
+                }
+            }
+            incoming = socket.recv() => {
+                /* Clients only listen, anything but a close is ignored */
+                if matches!(incoming, None | Some(Err(_)) | Some(Ok(Message::Close(_)))) {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn status(State(pool): State<AsyncNodePool>,
+                Path((name, subject)): Path<(String, DeploySubject)>) -> Result<Json<RunStatus>, ApiError> {
+    return pool.status(name, subject).await.map(Json).map_err(ApiError);
+}
@@ -0,0 +1,85 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::delta_error::DeltaError;
+use crate::data_model::secret::Secret;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/* Prefix marking a credential param as a reference into the secrets provider */
+pub const SECRET_PREFIX: &str = "secret:";
+
+/*
+ * Source of credentials referenced from params as "secret:<key>". Implement
+ * it to plug in Vault, SOPS or any other backend.
+ */
+pub trait SecretsProvider {
+    fn resolve(&self, key: &str) -> Result<Secret<String>, DeltaError>;
+}
+
+pub type SecretsProviderRef = Arc<dyn SecretsProvider + Send + Sync>;
+
+/* "secret:db_password" reads $<prefix>DB_PASSWORD */
+pub struct EnvSecretsProvider {
+    pub prefix: String,
+}
+
+impl EnvSecretsProvider {
+    pub fn new(prefix: String) -> EnvSecretsProvider {
+        return EnvSecretsProvider { prefix };
+    }
+}
+
+impl SecretsProvider for EnvSecretsProvider {
+    fn resolve(&self, key: &str) -> Result<Secret<String>, DeltaError> {
+        let var = format!("{}{}", self.prefix, key.to_uppercase().replace(['-', '.', '/'], "_"));
+        return env::var(&var)
+            .map(Secret::new)
+            .map_err(|_| DeltaError::MissingEnvVar(var));
+    }
+}
+
+/* "secret:db/password" reads <dir>/db/password, like Docker or Kubernetes secret mounts */
+pub struct FileSecretsProvider {
+    pub dir: PathBuf,
+}
+
+impl FileSecretsProvider {
+    pub fn new(dir: PathBuf) -> FileSecretsProvider {
+        return FileSecretsProvider { dir };
+    }
+}
+
+impl SecretsProvider for FileSecretsProvider {
+    fn resolve(&self, key: &str) -> Result<Secret<String>, DeltaError> {
+        if key.is_empty() || key.split('/').any(|c| c.is_empty() || c == "..") {
+            return Err(DeltaError::InvalidParameter("secret key".to_string(), key.to_string()));
+        }
+
+        let value = fs::read_to_string(self.dir.join(key))?;
+        return Ok(Secret::new(value.trim_end_matches(['\r', '\n']).to_string()));
+    }
+}
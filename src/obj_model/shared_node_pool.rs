@@ -0,0 +1,816 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::conn_alive_status::{ConnAliveStatus, SubjectAliveStatus};
+use crate::data_model::conn_status::ConnStatus;
+use crate::data_model::deploy_subject::DeploySubject;
+use crate::data_model::endpoint::Endpoint;
+use crate::data_model::result::activate_result::ActivateResult;
+use crate::data_model::result::add_result::AddResult;
+use crate::data_model::result::connect_result::ConnectResult;
+use crate::data_model::result::deploy_result::DeployResult;
+use crate::data_model::result::disconnect_result::DisconnectResult;
+use crate::data_model::result::remove_result::RemoveResult;
+use crate::data_model::result::rename_result::RenameResult;
+use crate::data_model::result::restart_result::RestartResult;
+use crate::data_model::result::rollback_result::RollbackResult;
+use crate::data_model::result::run_result::RunResult;
+use crate::data_model::result::stop_result::StopResult;
+use crate::data_model::result::undeploy_result::UndeployResult;
+use crate::data_model::result::update_result::UpdateResult;
+use crate::data_model::result::upgrade_result::UpgradeResult;
+use crate::data_model::pool_event::PoolEvent;
+use crate::data_model::pool_limits::PoolLimits;
+use crate::data_model::prerequisite_report::PrerequisiteReport;
+use crate::data_model::resource_usage::ResourceUsage;
+use crate::data_model::restart_policy::RestartPolicy;
+use crate::data_model::retry_policy::RetryPolicy;
+use crate::data_model::delta_error::DeltaError;
+use crate::data_model::exec_output::ExecOutput;
+use crate::data_model::file_info::FileInfo;
+use crate::data_model::installed_versions::InstalledVersions;
+use crate::data_model::instance_logs::InstanceLogs;
+use crate::data_model::job_status::JobStatus;
+use crate::data_model::node_summary::NodeSummary;
+use crate::data_model::operation_record::OperationRecord;
+use crate::data_model::operation_result::OperationResult;
+use crate::data_model::run_options::RunOptions;
+use crate::data_model::run_status::RunStatus;
+use crate::data_model::supervisor_event::SupervisorEvent;
+use crate::data_model::deploy_progress::DeployProgress;
+#[cfg(feature = "inventory")]
+use crate::obj_model::ansible::read_ansible_inventory;
+use crate::obj_model::audit::AuditSinkRef;
+#[cfg(feature = "encryption")]
+use crate::obj_model::credential_store::CredentialStore;
+use crate::obj_model::event_bus::EventBus;
+use crate::obj_model::fan_out::fan_out;
+use crate::obj_model::inventory::Inventory;
+use crate::obj_model::job_queue::{JobHandle, JobQueue};
+use crate::obj_model::limiter::Limiter;
+use crate::obj_model::log_stream::LogStream;
+use crate::obj_model::node_builder::NodeBuilder;
+use crate::obj_model::node_pattern::NodePattern;
+use crate::obj_model::operation::OperationScope;
+use crate::obj_model::node_pool::{DeployProgressMap, NodePool, OutputCallback, ProgressCallback};
+use crate::obj_model::secrets::SecretsProviderRef;
+use crate::obj_model::transport::ConnectorRef;
+use crate::obj_model::ssh_config::read_ssh_config;
+use crate::obj_model::tag_expr::TagExpr;
+use tracing::error;
+use std::collections::HashMap;
+use std::fs;
+use std::net::{SocketAddr, TcpStream};
+use std::path::Path;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, RwLock};
+use std::time::Duration;
+
+/*
+ * Thread-safe node pool. Every node lives in its own single-node NodePool
+ * behind a mutex, so operations on different nodes run concurrently while
+ * operations on the same node serialize. The registry lock is only held
+ * while looking nodes up, adding or removing them.
+ */
+pub struct SharedNodePool {
+    registry: RwLock<HashMap<String, Arc<Mutex<NodePool>>>>,
+    str_params: RwLock<HashMap<String, String>>,
+    retry_policy: RwLock<RetryPolicy>,
+    output_callback: RwLock<Option<OutputCallback>>,
+    progress_callback: RwLock<Option<ProgressCallback>>,
+    deploy_progress: DeployProgressMap,
+    max_workers: RwLock<usize>,
+    secrets_provider: RwLock<Option<SecretsProviderRef>>,
+    audit_sink: RwLock<Option<AuditSinkRef>>,
+    event_bus: EventBus,
+    connector: RwLock<Option<ConnectorRef>>,
+    limiter: Limiter,
+    /* Started by the first submit(), with max_workers workers */
+    jobs: OnceLock<JobQueue>,
+}
+
+impl SharedNodePool {
+    pub fn new() -> SharedNodePool {
+        return SharedNodePool::from_pool(NodePool::new());
+    }
+
+    pub fn from_pool(mut pool: NodePool) -> SharedNodePool {
+        let mut registry = HashMap::new();
+        for (name, node) in pool.nodes.drain() {
+            let mut sub = NodePool::new();
+            if let Some(inst) = pool.instances.remove(&name) {
+                sub.instances.insert(name.clone(), inst);
+            }
+            sub.nodes.insert(name.clone(), node);
+            registry.insert(name, Arc::new(Mutex::new(sub)));
+        }
+
+        return SharedNodePool {
+            registry: RwLock::new(registry),
+            str_params: RwLock::new(pool.str_params),
+            retry_policy: RwLock::new(pool.retry_policy),
+            output_callback: RwLock::new(pool.output_callback),
+            progress_callback: RwLock::new(pool.progress_callback),
+            deploy_progress: pool.deploy_progress,
+            max_workers: RwLock::new(pool.max_workers),
+            secrets_provider: RwLock::new(pool.secrets_provider),
+            audit_sink: RwLock::new(pool.audit_sink),
+            event_bus: pool.event_bus,
+            connector: RwLock::new(pool.connector),
+            limiter: pool.limiter,
+            jobs: OnceLock::new(),
+        };
+    }
+
+    pub fn load(path: &Path) -> Result<SharedNodePool, DeltaError> {
+        return Ok(SharedNodePool::from_pool(NodePool::load(path)?));
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), DeltaError> {
+        return self.to_inventory().write(path);
+    }
+
+    #[cfg(feature = "encryption")]
+    pub fn load_encrypted(path: &Path, store: &CredentialStore) -> Result<SharedNodePool, DeltaError> {
+        return Ok(SharedNodePool::from_pool(NodePool::load_encrypted(path, store)?));
+    }
+
+    #[cfg(feature = "encryption")]
+    pub fn save_encrypted(&self, path: &Path, store: &CredentialStore) -> Result<(), DeltaError> {
+        let mut inventory = self.to_inventory();
+        store.seal(&mut inventory)?;
+        return inventory.write(path);
+    }
+
+    pub fn to_inventory(&self) -> Inventory {
+        let mut inventory = Inventory {
+            str_params: self.str_params.read().unwrap_or_else(|e| e.into_inner()).clone(),
+            retry_policy: Some(self.retry_policy.read().unwrap_or_else(|e| e.into_inner()).clone()),
+            limits: Some(self.limiter.limits()),
+            nodes: Default::default(),
+        };
+
+        for name in self.names() {
+            if let Some(node) = self.with_node(&name, |pool| pool.nodes.get(&name).cloned()).flatten() {
+                inventory.nodes.insert(name, node);
+            }
+        }
+
+        return inventory;
+    }
+
+    pub fn set_param(&self, key: String, value: String) {
+        let mut str_params = self.str_params.write().unwrap_or_else(|e| e.into_inner());
+        str_params.insert(key, value);
+    }
+
+    pub fn set_retry_policy(&self, policy: RetryPolicy) {
+        let mut retry_policy = self.retry_policy.write().unwrap_or_else(|e| e.into_inner());
+        *retry_policy = policy;
+    }
+
+    pub fn set_output_callback(&self, callback: Option<OutputCallback>) {
+        let mut output_callback = self.output_callback.write().unwrap_or_else(|e| e.into_inner());
+        *output_callback = callback;
+    }
+
+    pub fn set_progress_callback(&self, callback: Option<ProgressCallback>) {
+        let mut progress_callback = self.progress_callback.write().unwrap_or_else(|e| e.into_inner());
+        *progress_callback = callback;
+    }
+
+    pub fn set_secrets_provider(&self, provider: Option<SecretsProviderRef>) {
+        let mut secrets_provider = self.secrets_provider.write().unwrap_or_else(|e| e.into_inner());
+        *secrets_provider = provider;
+    }
+
+    pub fn set_connector(&self, connector: Option<ConnectorRef>) {
+        let mut current = self.connector.write().unwrap_or_else(|e| e.into_inner());
+        *current = connector;
+    }
+
+    pub fn set_audit_sink(&self, sink: Option<AuditSinkRef>) {
+        let mut audit_sink = self.audit_sink.write().unwrap_or_else(|e| e.into_inner());
+        *audit_sink = sink;
+    }
+
+    pub fn subscribe(&self) -> Receiver<PoolEvent> {
+        return self.event_bus.subscribe();
+    }
+
+    /* The bus every node publishes to, for subscribing with a callback or publishing from outside */
+    pub fn event_bus(&self) -> &EventBus {
+        return &self.event_bus;
+    }
+
+    pub fn set_max_workers(&self, workers: usize) {
+        let mut max_workers = self.max_workers.write().unwrap_or_else(|e| e.into_inner());
+        *max_workers = workers;
+    }
+
+    pub fn limits(&self) -> PoolLimits {
+        return self.limiter.limits();
+    }
+
+    /* Applies to batches already running too; they share one budget */
+    pub fn set_limits(&self, limits: PoolLimits) {
+        self.limiter.set_limits(limits);
+    }
+
+    /*
+     * Runs op in the background on one of max_workers workers; the handle
+     * reports its status and hands out the result when it is done.
+     */
+    pub fn submit<R, F>(self: &Arc<Self>, op: F) -> JobHandle<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&SharedNodePool) -> R + Send + 'static,
+    {
+        let pool = self.clone();
+        return self.job_queue().submit(move || op(&pool));
+    }
+
+    /* Status of a submitted job, as long as it is queued, running or recently finished */
+    pub fn job_status(&self, id: &str) -> Option<JobStatus> {
+        return self.jobs.get()?.status(id);
+    }
+
+    fn job_queue(&self) -> &JobQueue {
+        return self.jobs.get_or_init(|| JobQueue::new(*self.max_workers.read().unwrap_or_else(|e| e.into_inner())));
+    }
+
+    /* Doesn't take the node lock, so it can be polled while a deploy is running */
+    pub fn get_deploy_progress(&self, name: String) -> Option<DeployProgress> {
+        let progress = self.deploy_progress.lock().unwrap_or_else(|e| e.into_inner());
+        return progress.get(&name).cloned();
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let registry = self.registry.read().unwrap_or_else(|e| e.into_inner());
+        return registry.keys().cloned().collect();
+    }
+
+    /* Runs f with exclusive access to the node, None if it doesn't exist */
+    pub fn with_node<R, F>(&self, name: &str, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut NodePool) -> R,
+    {
+        let entry = {
+            let registry = self.registry.read().unwrap_or_else(|e| e.into_inner());
+            registry.get(name).cloned()
+        };
+
+        let entry = match entry {
+            Some(e) => e,
+            None => {
+                error!("Node doesn't exist: {}", name);
+                return None;
+            }
+        };
+
+        let mut pool = SharedNodePool::lock(&entry);
+        self.sync_params(&mut pool);
+        return Some(f(&mut pool));
+    }
+
+    pub fn list(&self) -> Vec<NodeSummary> {
+        let mut list: Vec<NodeSummary> = self.names().into_iter()
+            .filter_map(|name| self.with_node(&name.clone(), |pool| pool.summary(name)).flatten())
+            .collect();
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        return list;
+    }
+
+    pub fn history(&self, name: String) -> Option<Vec<OperationRecord>> {
+        return self.with_node(&name.clone(), |pool| pool.history(name)).flatten();
+    }
+
+    pub fn add(&self, name: String, fqdn: String,
+               node_params: HashMap<String, String>) -> AddResult {
+        let mut registry = self.registry.write().unwrap_or_else(|e| e.into_inner());
+        if registry.contains_key(&name) {
+            error!("Node already exists: {}", name);
+            return AddResult::NodeAlreadyExists;
+        }
+
+        let mut sub = NodePool::new();
+        let result = sub.add(name.clone(), fqdn, node_params);
+        registry.insert(name, Arc::new(Mutex::new(sub)));
+        return result;
+    }
+
+    pub fn add_node(&self, builder: NodeBuilder) -> Result<AddResult, DeltaError> {
+        let mut sub = NodePool::new();
+        let result = sub.add_node(builder)?;
+        let Some(name) = sub.nodes.keys().next().cloned() else {
+            return Ok(result);
+        };
+
+        let mut registry = self.registry.write().unwrap_or_else(|e| e.into_inner());
+        if registry.contains_key(&name) {
+            error!("Node already exists: {}", name);
+            return Ok(AddResult::NodeAlreadyExists);
+        }
+
+        registry.insert(name, Arc::new(Mutex::new(sub)));
+        return Ok(result);
+    }
+
+    pub fn import_ssh_config(&self, path: &Path) -> Result<HashMap<String, AddResult>, DeltaError> {
+        let mut results = HashMap::new();
+        for host in read_ssh_config(path)? {
+            let result = self.add(host.name.clone(), host.fqdn, host.params);
+            results.insert(host.name, result);
+        }
+        return Ok(results);
+    }
+
+    #[cfg(feature = "inventory")]
+    pub fn import_ansible(&self, path: &Path) -> Result<HashMap<String, AddResult>, DeltaError> {
+        let mut results = HashMap::new();
+        for host in read_ansible_inventory(path)? {
+            let result = self.add(host.name.clone(), host.fqdn, host.params);
+            if result == AddResult::Ok {
+                for tag in host.tags {
+                    self.tag(host.name.clone(), tag);
+                }
+            }
+            results.insert(host.name, result);
+        }
+        return Ok(results);
+    }
+
+    pub fn remove(&self, name: String) -> RemoveResult {
+        let entry = {
+            let mut registry = self.registry.write().unwrap_or_else(|e| e.into_inner());
+            registry.remove(&name)
+        };
+
+        return match entry {
+            /* Wait for in-flight operations on the node before dropping it */
+            Some(e) => SharedNodePool::lock(&e).remove(name),
+            None => {
+                error!("Node doesn't exist: {}", name);
+                RemoveResult::NodeNotFound
+            }
+        };
+    }
+
+    pub fn tag(&self, name: String, tag: String) -> bool {
+        return self.with_node(&name.clone(), |pool| pool.tag(name, tag)).unwrap_or(false);
+    }
+
+    pub fn untag(&self, name: String, tag: String) -> bool {
+        return self.with_node(&name.clone(), |pool| pool.untag(name, tag)).unwrap_or(false);
+    }
+
+    pub fn set_restart_policy(&self, name: String, subject: DeploySubject,
+                              policy: Option<RestartPolicy>) -> bool {
+        return self.with_node(&name.clone(), |pool| pool.set_restart_policy(name, subject, policy))
+            .unwrap_or(false);
+    }
+
+    /* One supervisor pass over all nodes; see Supervisor for running it in the background */
+    pub fn supervise(&self) -> Vec<SupervisorEvent> {
+        return self.fan_out_nodes(self.names(), |name| {
+            self.with_node(&name, |pool| pool.supervise()).unwrap_or_default()
+        }).into_values().flatten().collect();
+    }
+
+    pub fn resolve_tags(&self, expr: &str) -> Result<Vec<String>, DeltaError> {
+        let expr = TagExpr::parse(expr)?;
+        let mut names: Vec<String> = self.names().into_iter()
+            .filter(|name| {
+                self.with_node(name, |pool| expr.matches(&pool.nodes[name].tags)).unwrap_or(false)
+            })
+            .collect();
+        names.sort();
+        return Ok(names);
+    }
+
+    pub fn select(&self, pattern: &str, with_fqdn: bool) -> Result<Vec<String>, DeltaError> {
+        let pattern = NodePattern::parse(pattern)?;
+        let mut names: Vec<String> = self.names().into_iter()
+            .filter(|name| {
+                pattern.matches(name) || (with_fqdn && self.with_node(name, |pool| {
+                    pattern.matches(&pool.nodes[name].fqdn)
+                }).unwrap_or(false))
+            })
+            .collect();
+        names.sort();
+        return Ok(names);
+    }
+
+    pub fn rename(&self, old: String, new: String) -> RenameResult {
+        let entry = {
+            let registry = self.registry.read().unwrap_or_else(|e| e.into_inner());
+            registry.get(&old).cloned()
+        };
+        let Some(entry) = entry else {
+            error!("Node doesn't exist: {}", old);
+            return RenameResult::NodeNotFound;
+        };
+
+        /*
+         * Waits for in-flight operations on the node, which stays reachable
+         * under its old name meanwhile, then re-keys it in one step. A remove
+         * or rename that got in first leaves the old name on another entry.
+         */
+        let mut pool = SharedNodePool::lock(&entry);
+        let mut registry = self.registry.write().unwrap_or_else(|e| e.into_inner());
+        if !registry.get(&old).is_some_and(|e| Arc::ptr_eq(e, &entry)) {
+            error!("Node doesn't exist: {}", old);
+            return RenameResult::NodeNotFound;
+        }
+        if registry.contains_key(&new) {
+            error!("Node already exists: {}", new);
+            return RenameResult::NameAlreadyExists;
+        }
+
+        self.sync_params(&mut pool);
+        let result = pool.rename(old.clone(), new.clone());
+        if result == RenameResult::Ok {
+            registry.remove(&old);
+            registry.insert(new, entry.clone());
+        }
+        return result;
+    }
+
+    pub fn connect(&self, name: String) -> ConnectResult {
+        return self.with_node(&name.clone(), |pool| pool.connect(name))
+            .unwrap_or(ConnectResult::NodeNotFound);
+    }
+
+    pub fn connect_tracked(&self, name: String) -> OperationResult<ConnectResult> {
+        let op = OperationScope::enter();
+        return self.with_node(&name.clone(), |pool| pool.connect_tracked(name))
+            .unwrap_or_else(|| op.finish(ConnectResult::NodeNotFound));
+    }
+
+    pub fn ensure_connected(&self, name: String) -> ConnectResult {
+        return self.with_node(&name.clone(), |pool| pool.ensure_connected(name))
+            .unwrap_or(ConnectResult::NodeNotFound);
+    }
+
+    pub fn update(&self, name: String, fqdn: Option<String>,
+                  params: HashMap<String, String>) -> UpdateResult {
+        return self.with_node(&name.clone(), |pool| pool.update(name, fqdn, params))
+            .unwrap_or(UpdateResult::NodeNotFound);
+    }
+
+    pub fn disconnect(&self, name: String) -> DisconnectResult {
+        return self.with_node(&name.clone(), |pool| pool.disconnect(name))
+            .unwrap_or(DisconnectResult::NodeNotFound);
+    }
+
+    pub fn is_connected(&self, name: String) -> ConnStatus {
+        return self.with_node(&name.clone(), |pool| pool.is_connected(name))
+            .unwrap_or(ConnStatus::new(false));
+    }
+
+    pub fn probe(&self, name: String) -> ConnStatus {
+        return self.with_node(&name.clone(), |pool| pool.probe(name))
+            .unwrap_or(ConnStatus::new(false));
+    }
+
+    pub fn is_alive(&self, name: String) -> ConnAliveStatus {
+        return self.with_node(&name.clone(), |pool| pool.is_alive(name))
+            .unwrap_or_else(ConnAliveStatus::new);
+    }
+
+    pub fn is_alive_cached(&self, name: String, max_age: Duration) -> ConnAliveStatus {
+        return self.with_node(&name.clone(), |pool| pool.is_alive_cached(name, max_age))
+            .unwrap_or_else(ConnAliveStatus::new);
+    }
+
+    pub fn is_subject_alive(&self, name: String, subject: DeploySubject) -> SubjectAliveStatus {
+        return self.with_node(&name.clone(), |pool| pool.is_subject_alive(name, subject))
+            .unwrap_or_else(SubjectAliveStatus::new);
+    }
+
+    /* is_alive() of every connected node; nodes that aren't connected are left out */
+    pub fn is_alive_all(&self) -> HashMap<String, ConnAliveStatus> {
+        let connected: Vec<String> = self.names().into_iter()
+            .filter(|name| self.is_connected(name.clone()).connected)
+            .collect();
+        return self.fan_out_nodes(connected, |name| self.is_alive(name));
+    }
+
+    /* is_alive_all() served from results at most max_age old where there are any */
+    pub fn is_alive_all_cached(&self, max_age: Duration) -> HashMap<String, ConnAliveStatus> {
+        let connected: Vec<String> = self.names().into_iter()
+            .filter(|name| self.is_connected(name.clone()).connected)
+            .collect();
+        return self.fan_out_nodes(connected, |name| self.is_alive_cached(name, max_age));
+    }
+
+    pub fn deploy(&self, name: String, subject: DeploySubject) -> DeployResult {
+        return self.with_node(&name.clone(), |pool| pool.deploy(name, subject))
+            .unwrap_or(DeployResult::NodeNotFound);
+    }
+
+    pub fn deploy_tracked(&self, name: String, subject: DeploySubject) -> OperationResult<DeployResult> {
+        let op = OperationScope::enter();
+        return self.with_node(&name.clone(), |pool| pool.deploy_tracked(name, subject))
+            .unwrap_or_else(|| op.finish(DeployResult::NodeNotFound));
+    }
+
+    pub fn run(&self, name: String, subject: DeploySubject) -> RunResult {
+        return self.with_node(&name.clone(), |pool| pool.run(name, subject))
+            .unwrap_or(RunResult::NodeNotFound);
+    }
+
+    pub fn run_with_options(&self, name: String, subject: DeploySubject,
+                            options: RunOptions) -> RunResult {
+        return self.with_node(&name.clone(), |pool| pool.run_with_options(name, subject, options))
+            .unwrap_or(RunResult::NodeNotFound);
+    }
+
+    pub fn run_tracked(&self, name: String, subject: DeploySubject,
+                       options: RunOptions) -> OperationResult<RunResult> {
+        let op = OperationScope::enter();
+        return self.with_node(&name.clone(), |pool| pool.run_tracked(name, subject, options))
+            .unwrap_or_else(|| op.finish(RunResult::NodeNotFound));
+    }
+
+    pub fn endpoint(&self, name: String, subject: DeploySubject) -> Result<Endpoint, DeltaError> {
+        return self.with_node(&name.clone(), |pool| pool.endpoint(name.clone(), subject))
+            .unwrap_or(Err(DeltaError::NodeNotFound(name)));
+    }
+
+    pub fn connect_client(&self, name: String, subject: DeploySubject) -> Result<TcpStream, DeltaError> {
+        return self.with_node(&name.clone(), |pool| pool.connect_client(name.clone(), subject))
+            .unwrap_or(Err(DeltaError::NodeNotFound(name)));
+    }
+
+    pub fn remote_stat(&self, name: String, path: String) -> Result<Option<FileInfo>, DeltaError> {
+        return self.with_node(&name.clone(), |pool| pool.remote_stat(name.clone(), path))
+            .unwrap_or(Err(DeltaError::NodeNotFound(name)));
+    }
+
+    pub fn remote_exists(&self, name: String, path: String) -> Result<bool, DeltaError> {
+        return self.with_node(&name.clone(), |pool| pool.remote_exists(name.clone(), path))
+            .unwrap_or(Err(DeltaError::NodeNotFound(name)));
+    }
+
+    pub fn check_prerequisites(&self, name: String) -> Result<PrerequisiteReport, DeltaError> {
+        return self.with_node(&name.clone(), |pool| pool.check_prerequisites(name.clone()))
+            .unwrap_or(Err(DeltaError::NodeNotFound(name)));
+    }
+
+    pub fn download_file(&self, name: String, remote_path: String, local_path: &Path) -> Result<u64, DeltaError> {
+        return self.with_node(&name.clone(), |pool| pool.download_file(name.clone(), remote_path, local_path))
+            .unwrap_or(Err(DeltaError::NodeNotFound(name)));
+    }
+
+    pub fn upload_dir(&self, name: String, local_dir: &Path, remote_dir: String) -> Result<u64, DeltaError> {
+        return self.with_node(&name.clone(), |pool| pool.upload_dir(name.clone(), local_dir, remote_dir))
+            .unwrap_or(Err(DeltaError::NodeNotFound(name)));
+    }
+
+    /* Same as NodePool::copy_between(), for nodes that may well sit in different pools */
+    pub fn copy_between(&self, src_node: String, src_path: String,
+                        dst_node: String, dst_path: String) -> Result<u64, DeltaError> {
+        let spool = NodePool::spool_path();
+        let source = format!("{}:{}", src_node, src_path);
+        let copied = self.with_node(&src_node.clone(), |pool| pool.spool_download(src_node.clone(), src_path, &spool))
+            .unwrap_or(Err(DeltaError::NodeNotFound(src_node)))
+            .and_then(|_| {
+                self.with_node(&dst_node.clone(), |pool| pool.spool_upload(dst_node.clone(), &spool, &source, dst_path))
+                    .unwrap_or(Err(DeltaError::NodeNotFound(dst_node)))
+            });
+        let _ = fs::remove_file(&spool);
+        return copied;
+    }
+
+    pub fn forward(&self, name: String, subject: DeploySubject) -> Result<SocketAddr, DeltaError> {
+        return self.with_node(&name.clone(), |pool| pool.forward(name.clone(), subject))
+            .unwrap_or(Err(DeltaError::NodeNotFound(name)));
+    }
+
+    pub fn unforward(&self, name: String, subject: DeploySubject) -> bool {
+        return self.with_node(&name.clone(), |pool| pool.unforward(name, subject)).unwrap_or(false);
+    }
+
+    pub fn fetch_logs(&self, name: String, subject: DeploySubject,
+                      tail_lines: Option<usize>) -> Result<InstanceLogs, DeltaError> {
+        return self.with_node(&name.clone(), |pool| pool.fetch_logs(name.clone(), subject, tail_lines))
+            .unwrap_or(Err(DeltaError::NodeNotFound(name)));
+    }
+
+    pub fn resource_usage(&self, name: String, subject: DeploySubject)
+                          -> Result<Option<ResourceUsage>, DeltaError> {
+        return self.with_node(&name.clone(), |pool| pool.resource_usage(name.clone(), subject))
+            .unwrap_or(Err(DeltaError::NodeNotFound(name)));
+    }
+
+    /* Nodes are sampled in parallel, each one takes about a second per running instance */
+    pub fn resource_snapshot(&self) -> HashMap<String, Vec<ResourceUsage>> {
+        return self.fan_out_nodes(self.names(), |name| {
+            self.with_node(&name, |pool| pool.resource_snapshot()).unwrap_or_default()
+        }).into_values().flatten().collect();
+    }
+
+    /* The node lock is only held while the stream is opened */
+    pub fn stream_logs(&self, name: String, subject: DeploySubject) -> Result<LogStream, DeltaError> {
+        return self.with_node(&name.clone(), |pool| pool.stream_logs(name.clone(), subject))
+            .unwrap_or(Err(DeltaError::NodeNotFound(name)));
+    }
+
+    pub fn restart(&self, name: String, subject: DeploySubject) -> RestartResult {
+        return self.with_node(&name.clone(), |pool| pool.restart(name, subject))
+            .unwrap_or(RestartResult::NodeNotFound);
+    }
+
+    pub fn stop(&self, name: String, subject: DeploySubject) -> StopResult {
+        return self.with_node(&name.clone(), |pool| pool.stop(name, subject))
+            .unwrap_or(StopResult::NodeNotFound);
+    }
+
+    pub fn deploy_many(&self, names: Vec<String>, subject: DeploySubject) -> HashMap<String, DeployResult> {
+        return self.fan_out_nodes(names, |name| self.deploy(name, subject.clone()));
+    }
+
+    pub fn execute_command(&self, name: String, cmd: String) -> Result<ExecOutput, DeltaError> {
+        return self.with_node(&name.clone(), |pool| pool.execute_command(name.clone(), cmd))
+            .unwrap_or(Err(DeltaError::NodeNotFound(name)));
+    }
+
+    pub fn execute_many(&self, names: Vec<String>, cmd: String) -> HashMap<String, Result<ExecOutput, DeltaError>> {
+        return self.fan_out_nodes(names, |name| self.execute_command(name, cmd.clone()));
+    }
+
+    /* Same as NodePool::execute_on(): connected nodes matching selector only */
+    pub fn execute_on(&self, selector: &str, cmd: String)
+                      -> Result<HashMap<String, Result<ExecOutput, DeltaError>>, DeltaError> {
+        let names = self.select(selector, false)?.into_iter()
+            .filter(|name| self.is_connected(name.clone()).connected)
+            .collect();
+        return Ok(self.execute_many(names, cmd));
+    }
+
+    pub fn connect_all(&self) -> HashMap<String, ConnectResult> {
+        return self.fan_out_nodes(self.names(), |name| self.connect(name));
+    }
+
+    /* Same as connect_all(), each node's result carrying the id of its own attempt */
+    pub fn connect_all_tracked(&self) -> HashMap<String, OperationResult<ConnectResult>> {
+        return self.fan_out_nodes(self.names(), |name| self.connect_tracked(name));
+    }
+
+    pub fn disconnect_all(&self) -> HashMap<String, DisconnectResult> {
+        return self.fan_out_nodes(self.names(), |name| self.disconnect(name));
+    }
+
+    fn fan_out_nodes<R, F>(&self, names: Vec<String>, f: F) -> HashMap<String, R>
+    where
+        R: Send,
+        F: Fn(String) -> R + Sync,
+    {
+        let workers = *self.max_workers.read().unwrap_or_else(|e| e.into_inner());
+        return fan_out(names, workers, |name| {
+            let _permit = self.limiter.session();
+            return (name.clone(), f(name));
+        }).into_iter().collect();
+    }
+
+    pub fn rollback(&self, name: String, subject: DeploySubject) -> RollbackResult {
+        return self.with_node(&name.clone(), |pool| pool.rollback(name, subject))
+            .unwrap_or(RollbackResult::NodeNotFound);
+    }
+
+    pub fn upgrade(&self, name: String, subject: DeploySubject, new_distr: String) -> UpgradeResult {
+        return self.with_node(&name.clone(), |pool| pool.upgrade(name, subject, new_distr))
+            .unwrap_or(UpgradeResult::NodeNotFound);
+    }
+
+    pub fn undeploy(&self, name: String, subject: DeploySubject) -> UndeployResult {
+        return self.with_node(&name.clone(), |pool| pool.undeploy(name, subject))
+            .unwrap_or(UndeployResult::NodeNotFound);
+    }
+
+    pub fn adopt(&self, name: String) -> Result<ConnAliveStatus, DeltaError> {
+        return self.with_node(&name.clone(), |pool| pool.adopt(name.clone()))
+            .unwrap_or(Err(DeltaError::NodeNotFound(name)));
+    }
+
+    pub fn status(&self, name: String, subject: DeploySubject) -> Result<RunStatus, DeltaError> {
+        return self.with_node(&name.clone(), |pool| pool.status(name.clone(), subject))
+            .unwrap_or(Err(DeltaError::NodeNotFound(name)));
+    }
+
+    pub fn list_versions(&self, name: String, subject: DeploySubject) -> Result<InstalledVersions, DeltaError> {
+        return self.with_node(&name.clone(), |pool| pool.list_versions(name.clone(), subject))
+            .unwrap_or(Err(DeltaError::NodeNotFound(name)));
+    }
+
+    pub fn activate(&self, name: String, subject: DeploySubject, version: String) -> ActivateResult {
+        return self.with_node(&name.clone(), |pool| pool.activate(name, subject, version))
+            .unwrap_or(ActivateResult::NodeNotFound);
+    }
+
+    fn sync_params(&self, pool: &mut NodePool) {
+        pool.str_params = self.str_params.read().unwrap_or_else(|e| e.into_inner()).clone();
+        pool.retry_policy = self.retry_policy.read().unwrap_or_else(|e| e.into_inner()).clone();
+        pool.output_callback = self.output_callback.read().unwrap_or_else(|e| e.into_inner()).clone();
+        pool.progress_callback = self.progress_callback.read().unwrap_or_else(|e| e.into_inner()).clone();
+        pool.deploy_progress = self.deploy_progress.clone();
+        pool.secrets_provider = self.secrets_provider.read().unwrap_or_else(|e| e.into_inner()).clone();
+        pool.audit_sink = self.audit_sink.read().unwrap_or_else(|e| e.into_inner()).clone();
+        pool.event_bus = self.event_bus.clone();
+        pool.connector = self.connector.read().unwrap_or_else(|e| e.into_inner()).clone();
+        pool.limiter = self.limiter.clone();
+    }
+
+    fn lock(entry: &Arc<Mutex<NodePool>>) -> MutexGuard<'_, NodePool> {
+        return entry.lock().unwrap_or_else(|e| e.into_inner());
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::obj_model::mock_transport::mock_pool;
+    use std::sync::mpsc;
+    use std::thread;
+
+    fn sorted_names(shared: &SharedNodePool) -> Vec<String> {
+        let mut names = shared.names();
+        names.sort();
+        return names;
+    }
+
+    #[test]
+    fn rename_waits_for_the_node_with_its_old_name_still_visible() {
+        let shared = SharedNodePool::from_pool(mock_pool(&["a", "b"]).0);
+        let (busy_tx, busy_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+
+        thread::scope(|scope| {
+            scope.spawn(|| shared.with_node("a", move |_| {
+                busy_tx.send(()).unwrap();
+                release_rx.recv().unwrap();
+            }));
+            busy_rx.recv().unwrap();
+            let rename = scope.spawn(|| shared.rename("a".to_string(), "c".to_string()));
+
+            thread::sleep(Duration::from_millis(50));
+            assert!(!rename.is_finished());
+            assert_eq!(sorted_names(&shared), ["a", "b"]);
+            assert_eq!(shared.with_node("b", |_| true), Some(true));
+
+            release_tx.send(()).unwrap();
+            assert_eq!(rename.join().unwrap(), RenameResult::Ok);
+        });
+        assert_eq!(sorted_names(&shared), ["b", "c"]);
+        assert!(shared.with_node("c", |pool| pool.nodes.contains_key("c")).unwrap());
+    }
+
+    #[test]
+    fn concurrent_renames_of_one_node_let_one_through() {
+        let shared = SharedNodePool::from_pool(mock_pool(&["a", "b"]).0);
+        let results: Vec<RenameResult> = thread::scope(|scope| {
+            let renames: Vec<_> = (0..8)
+                .map(|i| {
+                    let shared = &shared;
+                    scope.spawn(move || shared.rename("a".to_string(), format!("a{}", i)))
+                })
+                .collect();
+            return renames.into_iter().map(|r| r.join().unwrap()).collect();
+        });
+
+        assert_eq!(results.iter().filter(|r| **r == RenameResult::Ok).count(), 1, "{:?}", results);
+        assert!(results.iter().all(|r| matches!(r, RenameResult::Ok | RenameResult::NodeNotFound)));
+        let names = sorted_names(&shared);
+        assert_eq!(names.len(), 2, "{:?}", names);
+        assert!(names.contains(&"b".to_string()) && !names.contains(&"a".to_string()));
+    }
+
+    #[test]
+    fn rename_onto_a_taken_name_keeps_both_nodes() {
+        let shared = SharedNodePool::from_pool(mock_pool(&["a", "b"]).0);
+        assert_eq!(shared.rename("a".to_string(), "b".to_string()), RenameResult::NameAlreadyExists);
+        assert_eq!(sorted_names(&shared), ["a", "b"]);
+    }
+}
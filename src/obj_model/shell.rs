@@ -0,0 +1,97 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+/* Wraps a value in single quotes so that the remote shell takes it literally */
+pub fn shell_quote(value: &str) -> String {
+    return format!("'{}'", shell_escape(value));
+}
+
+/* The value as it has to appear between single quotes someone else wrote */
+pub fn shell_escape(value: &str) -> String {
+    return value.replace('\'', "'\\''");
+}
+
+/*
+ * Whether quoting gets the value through intact: a NUL can't be passed in
+ * a command at all, and line breaks would split the line-based scripts
+ * (here-documents, unit files, pid files) the value ends up in.
+ */
+pub fn is_shell_safe(value: &str) -> bool {
+    return !value.chars().any(|c| c.is_control());
+}
+
+/* Whether the name can be used as an environment variable in a POSIX shell */
+pub fn is_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    return match chars.next() {
+        Some(c) if c == '_' || c.is_ascii_alphabetic() =>
+            chars.all(|c| c == '_' || c.is_ascii_alphanumeric()),
+        _ => false,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    /* What sh makes of the quoted value, as an argument of printf */
+    fn through_sh(value: &str) -> String {
+        let cmd = format!("printf %s {}", shell_quote(value));
+        let out = Command::new("sh").arg("-c").arg(cmd).output().unwrap();
+        return String::from_utf8(out.stdout).unwrap();
+    }
+
+    #[test]
+    fn quoted_values_reach_the_command_intact() {
+        for value in ["", "plain", "two words", "it's", "''", "$(id) `id` ${HOME}", "a;b|c&d>e", "\\n*?[x]"] {
+            assert_eq!(through_sh(value), value);
+        }
+    }
+
+    #[test]
+    fn escape_closes_and_reopens_the_quotes() {
+        assert_eq!(shell_escape("it's"), "it'\\''s");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn control_characters_are_not_shell_safe() {
+        assert!(is_shell_safe("/srv/app dir/it's $HOME"));
+        assert!(is_shell_safe("ünïcode"));
+        for value in ["a\nb", "a\rb", "a\0b", "a\tb", "\u{1b}[0m"] {
+            assert!(!is_shell_safe(value), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn env_names_follow_posix() {
+        for name in ["PATH", "_x", "A1_B2"] {
+            assert!(is_env_name(name), "{}", name);
+        }
+        for name in ["", "1A", "A-B", "A B", "A=B", "Ä"] {
+            assert!(!is_env_name(name), "{}", name);
+        }
+    }
+}
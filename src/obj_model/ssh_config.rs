@@ -0,0 +1,163 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::node_parameters::NodeParameters;
+use glob::Pattern;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/* A concrete host from an OpenSSH client config, ready to be added as a node */
+pub struct SshConfigHost {
+    pub name: String,
+    pub fqdn: String,
+    pub params: HashMap<String, String>,
+}
+
+struct Block {
+    patterns: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+pub fn default_path() -> PathBuf {
+    let home = env::var("HOME").unwrap_or_default();
+    return PathBuf::from(home).join(".ssh").join("config");
+}
+
+pub fn read_ssh_config(path: &Path) -> io::Result<Vec<SshConfigHost>> {
+    return Ok(parse_ssh_config(&fs::read_to_string(path)?));
+}
+
+/*
+ * Every non-wildcard Host alias becomes a node. Options are resolved like
+ * ssh does: all matching blocks in file order, first value wins, so "Host *"
+ * defaults at the end apply. Match blocks and Include aren't supported.
+ */
+pub fn parse_ssh_config(text: &str) -> Vec<SshConfigHost> {
+    let blocks = parse_blocks(text);
+
+    let mut aliases: Vec<String> = vec![];
+    for block in &blocks {
+        for p in &block.patterns {
+            if !p.contains(['*', '?', '!']) && !aliases.contains(p) {
+                aliases.push(p.clone());
+            }
+        }
+    }
+
+    return aliases.into_iter().map(|alias| resolve(&blocks, alias)).collect();
+}
+
+fn parse_blocks(text: &str) -> Vec<Block> {
+    let mut blocks = vec![];
+    /* Options before the first Host apply to every host */
+    let mut current = Block { patterns: vec!["*".to_string()], options: vec![] };
+    let mut skipping = false;
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (key, value) = match line.split_once(|c: char| c.is_whitespace() || c == '=') {
+            Some((k, v)) => (k.to_lowercase(), v.trim_start_matches(|c: char| c.is_whitespace() || c == '=')),
+            None => continue,
+        };
+        let value = value.trim().trim_matches('"').to_string();
+
+        match key.as_str() {
+            "host" => {
+                blocks.push(current);
+                current = Block { patterns: value.split_whitespace().map(String::from).collect(), options: vec![] };
+                skipping = false;
+            }
+            "match" => {
+                blocks.push(current);
+                current = Block { patterns: vec![], options: vec![] };
+                skipping = true;
+            }
+            _ if !skipping => current.options.push((key, value)),
+            _ => {}
+        }
+    }
+
+    blocks.push(current);
+    return blocks;
+}
+
+fn matches(patterns: &[String], alias: &str) -> bool {
+    let mut matched = false;
+    for p in patterns {
+        let (negated, p) = match p.strip_prefix('!') {
+            Some(p) => (true, p),
+            None => (false, p.as_str()),
+        };
+
+        if Pattern::new(p).map(|p| p.matches(alias)).unwrap_or(false) {
+            if negated {
+                return false;
+            }
+            matched = true;
+        }
+    }
+    return matched;
+}
+
+fn resolve(blocks: &[Block], alias: String) -> SshConfigHost {
+    let mut options: HashMap<String, String> = HashMap::new();
+    for block in blocks.iter().filter(|b| matches(&b.patterns, &alias)) {
+        for (key, value) in &block.options {
+            options.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+    }
+
+    let fqdn = options.get("hostname").cloned().unwrap_or_else(|| alias.clone());
+
+    /* Kept apart from the host, "host:port" would misread an IPv6 HostName */
+    let mut params = HashMap::new();
+    if let Some(port) = options.get("port") {
+        params.insert(NodeParameters::SshPort.to_string(), port.clone());
+    }
+    if let Some(user) = options.get("user") {
+        params.insert(NodeParameters::Username.to_string(), user.clone());
+    }
+    if let Some(identity) = options.get("identityfile") {
+        params.insert(NodeParameters::IdentityFile.to_string(), expand_home(identity));
+    }
+
+    return SshConfigHost { name: alias, fqdn, params };
+}
+
+pub fn expand_home(path: &str) -> String {
+    return match path.strip_prefix("~/") {
+        Some(rest) => PathBuf::from(env::var("HOME").unwrap_or_default())
+            .join(rest)
+            .to_string_lossy()
+            .to_string(),
+        None => path.to_string(),
+    };
+}
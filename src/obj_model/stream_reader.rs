@@ -0,0 +1,114 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::delta_error::DeltaError;
+use crate::data_model::exec_output::{ExecOutput, OutputStream};
+use crate::obj_model::cancellation::check_cancelled;
+use ssh2::{Channel, Session};
+use std::io::{ErrorKind, Read};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub type LineSink = dyn Fn(OutputStream, &str);
+
+/*
+ * Reads stdout and stderr of a started channel as the data arrives and hands
+ * every complete line to sink. The session is switched to non-blocking mode
+ * for the duration of the read, so the timeout is tracked here.
+ */
+pub fn collect_streaming(sess: &Session, channel: &mut Channel, timeout: Option<Duration>,
+                         sink: &LineSink) -> Result<ExecOutput, DeltaError> {
+    let deadline = timeout.map(|t| Instant::now() + t);
+    let mut output = ExecOutput::new();
+    let mut stdout_buf = Vec::new();
+    let mut stderr_buf = Vec::new();
+
+    sess.set_blocking(false);
+    let result = (|| {
+        loop {
+            let got_out = read_some(channel, &mut stdout_buf)?;
+            let got_err = read_some(&mut channel.stderr(), &mut stderr_buf)?;
+
+            emit_lines(&mut stdout_buf, &mut output.stdout, OutputStream::Stdout, sink, false);
+            emit_lines(&mut stderr_buf, &mut output.stderr, OutputStream::Stderr, sink, false);
+
+            if got_out || got_err {
+                continue;
+            }
+
+            if channel.eof() {
+                return Ok(());
+            }
+
+            check_cancelled()?;
+
+            if let Some(d) = deadline {
+                if Instant::now() >= d {
+                    return Err(DeltaError::Timeout("streaming command".to_string()));
+                }
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+    })();
+    sess.set_blocking(true);
+    result?;
+
+    emit_lines(&mut stdout_buf, &mut output.stdout, OutputStream::Stdout, sink, true);
+    emit_lines(&mut stderr_buf, &mut output.stderr, OutputStream::Stderr, sink, true);
+
+    channel.wait_close()?;
+    output.exit_code = channel.exit_status()?;
+    return Ok(output);
+}
+
+pub fn read_some<R: Read>(reader: &mut R, buf: &mut Vec<u8>) -> Result<bool, DeltaError> {
+    let mut chunk = [0u8; 4096];
+    return match reader.read(&mut chunk) {
+        Ok(0) => Ok(false),
+        Ok(n) => {
+            buf.extend_from_slice(&chunk[..n]);
+            Ok(true)
+        }
+        Err(ref e) if e.kind() == ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(DeltaError::from(e)),
+    };
+}
+
+fn emit_lines(buf: &mut Vec<u8>, text: &mut String, stream: OutputStream,
+              sink: &LineSink, flush: bool) {
+    while let Some(pos) = buf.iter().position(|b| *b == b'\n') {
+        let line: Vec<u8> = buf.drain(..=pos).collect();
+        let line = String::from_utf8_lossy(&line);
+        text.push_str(&line);
+        sink(stream.clone(), line.trim_end_matches(['\r', '\n']));
+    }
+
+    if flush && !buf.is_empty() {
+        let line = String::from_utf8_lossy(buf).to_string();
+        buf.clear();
+        text.push_str(&line);
+        sink(stream, &line);
+    }
+}
@@ -0,0 +1,88 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::supervisor_event::SupervisorEvent;
+use crate::obj_model::shared_node_pool::SharedNodePool;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+pub type SupervisorCallback = Arc<dyn Fn(&SupervisorEvent) + Send + Sync>;
+
+/*
+ * Background thread running SharedNodePool::supervise() every interval.
+ * It only holds a weak reference, so it ends with the pool as well as on
+ * stop() or drop.
+ */
+pub struct Supervisor {
+    stopped: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Supervisor {
+    pub fn start(pool: &Arc<SharedNodePool>, interval: Duration,
+                 callback: Option<SupervisorCallback>) -> Supervisor {
+        let pool = Arc::downgrade(pool);
+        let stopped = Arc::new(AtomicBool::new(false));
+        let flag = stopped.clone();
+
+        let handle = thread::spawn(move || {
+            while !flag.load(Ordering::SeqCst) {
+                let Some(pool) = pool.upgrade() else {
+                    break;
+                };
+
+                for event in pool.supervise() {
+                    if let Some(callback) = &callback {
+                        callback(&event);
+                    }
+                }
+                drop(pool);
+
+                thread::park_timeout(interval);
+            }
+        });
+
+        return Supervisor { stopped, handle: Some(handle) };
+    }
+
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
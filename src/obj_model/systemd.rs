@@ -0,0 +1,142 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::deploy_subject::DeploySubject;
+use crate::data_model::run_mode::RunMode;
+use crate::obj_model::shell::shell_quote;
+
+/* Alive check used instead of the pid file when a unit runs the server */
+/* Delimiter of the here-documents the unit and launcher are written with */
+const HEREDOC_END: &str = "DELTA_API_EOF";
+
+pub fn unit_name(subject: &DeploySubject) -> String {
+    return format!("delta-api-{}.service", subject.binary());
+}
+
+pub fn systemctl(mode: &RunMode) -> &'static str {
+    return match mode {
+        RunMode::SystemdUser => "systemctl --user",
+        _ => "systemctl",
+    };
+}
+
+/* Shell word for the unit file path; $HOME has to stay expandable */
+pub fn unit_path(mode: &RunMode, subject: &DeploySubject) -> String {
+    return match mode {
+        RunMode::SystemdUser => format!("\"$HOME/.config/systemd/user/\"{}", shell_quote(&unit_name(subject))),
+        _ => shell_quote(&format!("/etc/systemd/system/{}", unit_name(subject))),
+    };
+}
+
+/* Sets $S to whatever privileged commands have to be prefixed with */
+pub fn privileged(mode: &RunMode) -> &'static str {
+    return match mode {
+        RunMode::SystemdUser => "S=",
+        _ => "S=; [ \"$(id -u)\" = 0 ] || S='sudo -n'",
+    };
+}
+
+/* Shell expression printing the main pid of the unit, 0 when it is not running */
+pub fn main_pid_command(mode: &RunMode, subject: &DeploySubject) -> String {
+    return format!("{} show -p MainPID --value {} 2> /dev/null", systemctl(mode),
+                   shell_quote(&unit_name(subject)));
+}
+
+/* Default alive check; a custom one is a template with {systemctl} and {unit} */
+pub fn alive_command(mode: &RunMode, subject: &DeploySubject) -> String {
+    return format!("{} is-active --quiet {}", systemctl(mode), shell_quote(&unit_name(subject)));
+}
+
+/* Single-quoted word of an ExecStart line, where \\, % and $ mean something too */
+fn unit_quote(value: &str) -> String {
+    return format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'")
+        .replace('%', "%%").replace('$', "$$"));
+}
+
+pub fn unit_file(mode: &RunMode, subject: &DeploySubject, launcher: &str,
+                 signal: &str, grace: u64) -> String {
+    let wanted_by = match mode {
+        RunMode::SystemdUser => "default.target",
+        _ => "multi-user.target",
+    };
+
+    return format!(
+        "[Unit]\n\
+         Description={0} deployed by delta-api\n\
+         After=network.target\n\
+         \n\
+         [Service]\n\
+         Type=simple\n\
+         ExecStart=/bin/sh {1}\n\
+         KillSignal=SIG{2}\n\
+         TimeoutStopSec={3}\n\
+         \n\
+         [Install]\n\
+         WantedBy={4}\n",
+        subject.binary(), unit_quote(launcher), signal, grace, wanted_by);
+}
+
+/*
+ * Writes the launcher script and the unit, then (re)starts the unit. The
+ * launch line is the same one backgrounding would have used. Runs in a
+ * subshell so that set -e stays out of the commands fed after it.
+ */
+pub fn install_script(mode: &RunMode, subject: &DeploySubject, remote_dir: &str, launch: &str,
+                      signal: &str, grace: u64) -> String {
+    let launcher = format!("{}/launch.sh", remote_dir);
+    let unit = shell_quote(&unit_name(subject));
+    let systemctl = systemctl(mode);
+    let mut script = format!("(\n{}\nset -e\n", privileged(mode));
+    if *mode == RunMode::SystemdUser {
+        /* Without lingering the unit dies with the last session of the user */
+        script.push_str("loginctl enable-linger 2> /dev/null || true\n");
+        script.push_str("mkdir -p \"$HOME/.config/systemd/user\"\n");
+    }
+    script.push_str(&format!("cat > {} <<'{}'\n#!/bin/sh\n{}\n{}\n", shell_quote(&launcher), HEREDOC_END, launch,
+                             HEREDOC_END));
+    script.push_str(&format!("$S tee {} > /dev/null <<'{}'\n{}{}\n", unit_path(mode, subject), HEREDOC_END,
+                             unit_file(mode, subject, &launcher, signal, grace), HEREDOC_END));
+    script.push_str(&format!("$S {0} daemon-reload\n$S {0} enable --quiet {1}\n$S {0} restart {1}\n",
+                             systemctl, unit));
+    script.push(')');
+    return script;
+}
+
+/* Prints not-running, terminated or killed, like the backgrounded stop does */
+pub fn stop_script(mode: &RunMode, subject: &DeploySubject, remote_dir: &str) -> String {
+    return format!(
+        "{0}; u={2}; \
+         if ! {1} is-active --quiet \"$u\"; then rm -f {3}/pid; echo not-running; exit 0; fi; \
+         $S {1} stop \"$u\" || exit 1; \
+         if [ \"$({1} show -p Result --value \"$u\")\" = timeout ]; then echo killed; else echo terminated; fi; \
+         rm -f {3}/pid",
+        privileged(mode), systemctl(mode), shell_quote(&unit_name(subject)), shell_quote(remote_dir));
+}
+
+/* Stops, disables and removes the unit; fine to run when there is none */
+pub fn remove_script(mode: &RunMode, subject: &DeploySubject) -> String {
+    return format!(
+        "{0}; $S {1} disable --now --quiet {2} 2> /dev/null; $S rm -f {3}; $S {1} daemon-reload",
+        privileged(mode), systemctl(mode), shell_quote(&unit_name(subject)), unit_path(mode, subject));
+}
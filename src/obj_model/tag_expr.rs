@@ -0,0 +1,147 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::delta_error::DeltaError;
+use std::collections::HashSet;
+
+/*
+ * Boolean expression over node tags, e.g. "prod && !canary" or
+ * "(eu || us) && db". ! binds tighter than &&, which binds tighter than ||.
+ */
+#[derive(PartialEq, Clone, Debug)]
+pub enum TagExpr {
+    Tag(String),
+    Not(Box<TagExpr>),
+    And(Box<TagExpr>, Box<TagExpr>),
+    Or(Box<TagExpr>, Box<TagExpr>),
+}
+
+#[derive(PartialEq, Clone, Debug)]
+enum Token {
+    Tag(String),
+    Not,
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+impl TagExpr {
+    pub fn parse(expr: &str) -> Result<TagExpr, DeltaError> {
+        let invalid = || DeltaError::InvalidParameter("tag expression".to_string(), expr.to_string());
+
+        let tokens = tokenize(expr).ok_or_else(invalid)?;
+        let mut pos = 0;
+        let parsed = parse_or(&tokens, &mut pos).ok_or_else(invalid)?;
+        if pos != tokens.len() {
+            return Err(invalid());
+        }
+
+        return Ok(parsed);
+    }
+
+    pub fn matches(&self, tags: &HashSet<String>) -> bool {
+        return match self {
+            TagExpr::Tag(t) => tags.contains(t),
+            TagExpr::Not(e) => !e.matches(tags),
+            TagExpr::And(a, b) => a.matches(tags) && b.matches(tags),
+            TagExpr::Or(a, b) => a.matches(tags) || b.matches(tags),
+        };
+    }
+}
+
+pub fn is_valid_tag(tag: &str) -> bool {
+    return !tag.is_empty() && tag.chars().all(is_tag_char);
+}
+
+fn is_tag_char(c: char) -> bool {
+    return c.is_alphanumeric() || "_-.:/".contains(c);
+}
+
+fn tokenize(expr: &str) -> Option<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = expr.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => {}
+            '!' => tokens.push(Token::Not),
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '&' if chars.next() == Some('&') => tokens.push(Token::And),
+            '|' if chars.next() == Some('|') => tokens.push(Token::Or),
+            c if is_tag_char(c) => {
+                let mut tag = c.to_string();
+                while let Some(&n) = chars.peek() {
+                    if !is_tag_char(n) {
+                        break;
+                    }
+                    tag.push(n);
+                    chars.next();
+                }
+                tokens.push(Token::Tag(tag));
+            }
+            _ => return None,
+        }
+    }
+
+    return Some(tokens);
+}
+
+fn parse_or(tokens: &[Token], pos: &mut usize) -> Option<TagExpr> {
+    let mut left = parse_and(tokens, pos)?;
+    while tokens.get(*pos) == Some(&Token::Or) {
+        *pos += 1;
+        left = TagExpr::Or(Box::new(left), Box::new(parse_and(tokens, pos)?));
+    }
+    return Some(left);
+}
+
+fn parse_and(tokens: &[Token], pos: &mut usize) -> Option<TagExpr> {
+    let mut left = parse_unary(tokens, pos)?;
+    while tokens.get(*pos) == Some(&Token::And) {
+        *pos += 1;
+        left = TagExpr::And(Box::new(left), Box::new(parse_unary(tokens, pos)?));
+    }
+    return Some(left);
+}
+
+fn parse_unary(tokens: &[Token], pos: &mut usize) -> Option<TagExpr> {
+    let token = tokens.get(*pos)?.clone();
+    *pos += 1;
+
+    return match token {
+        Token::Not => Some(TagExpr::Not(Box::new(parse_unary(tokens, pos)?))),
+        Token::Tag(t) => Some(TagExpr::Tag(t)),
+        Token::Open => {
+            let inner = parse_or(tokens, pos)?;
+            if tokens.get(*pos) != Some(&Token::Close) {
+                return None;
+            }
+            *pos += 1;
+            Some(inner)
+        }
+        _ => None,
+    };
+}
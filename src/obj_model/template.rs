@@ -0,0 +1,104 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::deploy_subject::DeploySubject;
+use crate::obj_model::net::format_host_port;
+use crate::obj_model::shell::shell_escape;
+
+/* Built-in commands, each replaceable per subject through a node param */
+pub const DEFAULT_LAUNCH_TEMPLATE: &str = "'{install_dir}/bin/{binary}' --server 'tcp://{bind}'";
+pub const DEFAULT_TEST_TEMPLATE: &str = "'{install_dir}/bin/{binary}' --version";
+pub const DEFAULT_ALIVE_TEMPLATE: &str = "kill -0 \"$(cat '{remote_dir}/pid')\"";
+
+/*
+ * Values a remote command template refers to as {name}. Anything in braces
+ * that isn't a known name is left alone, so ${VAR} and awk programs survive.
+ * Placeholders are meant to sit between single quotes, as in the defaults;
+ * values are escaped for that, so a quote in a path can't end the string.
+ */
+#[derive(Clone, Debug)]
+pub struct CommandContext {
+    vars: Vec<(&'static str, String)>,
+    escape: fn(&str) -> String,
+}
+
+impl CommandContext {
+    pub fn new(subject: &DeploySubject, remote_dir: &str, install_dir: &str) -> CommandContext {
+        return CommandContext {
+            vars: vec![
+                ("subject", subject.to_string()),
+                ("binary", subject.binary().to_string()),
+                ("remote_dir", remote_dir.to_string()),
+                ("install_dir", install_dir.to_string()),
+            ],
+            escape: shell_escape,
+        };
+    }
+
+    /* For templates in another language than sh, e.g. PowerShell */
+    pub fn escape_with(mut self, escape: fn(&str) -> String) -> CommandContext {
+        self.escape = escape;
+        return self;
+    }
+
+    pub fn bind(self, bind_addr: &str, bind_port: u16) -> CommandContext {
+        return self.set("bind_addr", bind_addr)
+            .set("bind_port", &bind_port.to_string())
+            .set("bind", &format_host_port(bind_addr, bind_port));
+    }
+
+    pub fn set(mut self, name: &'static str, value: &str) -> CommandContext {
+        match self.vars.iter_mut().find(|(n, _)| *n == name) {
+            Some(var) => var.1 = value.to_string(),
+            None => self.vars.push((name, value.to_string())),
+        }
+        return self;
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        return self.vars.iter().find(|(n, _)| *n == name).map(|(_, v)| v.as_str());
+    }
+
+    pub fn render(&self, template: &str) -> String {
+        let mut out = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            let tail = &rest[start + 1..];
+            let value = tail.find('}').and_then(|end| self.get(&tail[..end]).map(|v| (end, v)));
+            match value {
+                Some((end, v)) => {
+                    out.push_str(&(self.escape)(v));
+                    rest = &tail[end + 1..];
+                }
+                None => {
+                    out.push('{');
+                    rest = tail;
+                }
+            }
+        }
+        out.push_str(rest);
+        return out;
+    }
+}
@@ -0,0 +1,301 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::delta_error::DeltaError;
+use crate::obj_model::node::Node;
+use crate::data_model::result::connect_result::ConnectResult;
+use crate::data_model::exec_output::ExecOutput;
+use crate::data_model::secret::scrub;
+use crate::data_model::transfer_method::TransferMethod;
+use crate::obj_model::cancellation::{check_cancelled, current_token};
+use crate::obj_model::shell::shell_quote;
+use crate::obj_model::stream_reader::{collect_streaming, LineSink};
+use ssh2::{Channel, OpenFlags, OpenType, Session};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug_span, error};
+
+const PROBE_TIMEOUT_MS: u32 = 10000;
+
+/*
+ * How NodePool reaches a node: runs shell commands there and moves files to
+ * and from it. Each connected node has one; SshTransport is the default.
+ */
+pub trait Transport: Send {
+    /* Runs cmd in the node's shell; with a sink, output lines are reported as they arrive */
+    fn execute(&self, cmd: &str, timeout: Option<Duration>,
+               sink: Option<&LineSink>) -> Result<ExecOutput, DeltaError>;
+
+    /* Feeds the commands, one per line, to a single shell, so one can rely on another */
+    fn execute_script(&self, commands: &[String], timeout: Option<Duration>,
+                      sink: Option<&LineSink>) -> Result<ExecOutput, DeltaError>;
+
+    /*
+     * Writes the len bytes left in reader to remote_path from offset on; with
+     * offset 0 the file is replaced. on_progress gets the bytes written so far.
+     */
+    #[allow(clippy::too_many_arguments)]
+    fn upload(&self, reader: &mut dyn Read, remote_path: &str, mode: i32, len: u64, offset: u64,
+              method: &TransferMethod, on_progress: &mut dyn FnMut(u64)) -> Result<(), DeltaError>;
+
+    /* Copies remote_path into writer, returning the bytes copied */
+    fn download(&self, remote_path: &str, writer: &mut dyn Write,
+                method: &TransferMethod) -> Result<u64, DeltaError>;
+
+    /* Size of remote_path, None when it can't be had */
+    fn file_size(&self, remote_path: &str, method: &TransferMethod) -> Option<u64>;
+
+    /* Whether the connection still carries commands */
+    fn is_alive(&self) -> bool;
+
+    /* The SSH session underneath, for what only SSH offers (tunnels) */
+    fn ssh_session(&self) -> Option<&Session> {
+        return None;
+    }
+}
+
+/*
+ * Opens transports for a pool in place of SSH. Set one on the pool to run
+ * nodes over another backend or a scripted one.
+ */
+pub trait Connector {
+    fn connect(&self, name: &str, node: &Node) -> Result<Box<dyn Transport>, ConnectResult>;
+}
+
+pub type ConnectorRef = Arc<dyn Connector + Send + Sync>;
+
+pub fn copy_stream<R: Read + ?Sized, W: Write + ?Sized>(reader: &mut R, writer: &mut W,
+                                                        on_progress: &mut dyn FnMut(u64)) -> Result<u64, DeltaError> {
+    let mut buffer = vec![0; 4096];
+    let mut total = 0;
+    loop {
+        check_cancelled()?;
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+
+        writer.write_all(&buffer[..n])?;
+        total += n as u64;
+        on_progress(total);
+    }
+
+    return Ok(total);
+}
+
+pub struct SshTransport {
+    sess: Session,
+}
+
+impl SshTransport {
+    pub fn new(sess: Session) -> SshTransport {
+        return SshTransport { sess };
+    }
+
+    /* Opens a channel, lets f start the command and collects its output */
+    fn with_channel<F>(&self, what: &str, timeout: Option<Duration>, sink: Option<&LineSink>,
+                       f: F) -> Result<ExecOutput, DeltaError>
+    where
+        F: FnOnce(&mut Channel) -> Result<(), DeltaError>,
+    {
+        let span = debug_span!("command", cmd = %scrub(what));
+        let _entered = span.enter();
+        check_cancelled()?;
+        let timeout_ms = timeout.map(|t| t.as_millis().min(u32::MAX as u128) as u32).unwrap_or(0);
+        self.sess.set_timeout(timeout_ms);
+
+        let result = self.sess.channel_session().map_err(DeltaError::from).and_then(|mut channel| {
+            let output = f(&mut channel).and_then(|_| match sink {
+                Some(sink) => collect_streaming(&self.sess, &mut channel, timeout, sink),
+                /* A blocking read can't be interrupted, the streaming one checks in between */
+                None if current_token().is_some() => collect_streaming(&self.sess, &mut channel, timeout, &|_, _| {}),
+                None => SshTransport::collect_output(&mut channel),
+            });
+            if output.is_err() {
+                let _ = channel.close();
+            }
+            return output;
+        });
+
+        self.sess.set_timeout(0);
+
+        return match result {
+            Err(e) if e.is_timeout() => {
+                error!("Command timed out after {} ms: {}", timeout_ms, scrub(what));
+                Err(DeltaError::Timeout(scrub(what)))
+            }
+            r => r,
+        };
+    }
+
+    fn collect_output(channel: &mut Channel) -> Result<ExecOutput, DeltaError> {
+        let mut output = ExecOutput::new();
+        channel.read_to_string(&mut output.stdout)?;
+        channel.stderr().read_to_string(&mut output.stderr)?;
+        channel.wait_close()?;
+        output.exit_code = channel.exit_status()?;
+
+        return Ok(output);
+    }
+}
+
+impl Transport for SshTransport {
+    fn execute(&self, cmd: &str, timeout: Option<Duration>,
+               sink: Option<&LineSink>) -> Result<ExecOutput, DeltaError> {
+        return self.with_channel(cmd, timeout, sink, |channel| {
+            channel.exec(cmd)?;
+            return Ok(());
+        });
+    }
+
+    fn execute_script(&self, commands: &[String], timeout: Option<Duration>,
+                      sink: Option<&LineSink>) -> Result<ExecOutput, DeltaError> {
+        return self.with_channel(&commands.join("; "), timeout, sink, |channel| {
+            channel.shell()?;
+            for command in commands {
+                channel.write_all(command.as_bytes())?;
+                channel.write_all(b"\n")?;
+            }
+            channel.send_eof()?;
+            return Ok(());
+        });
+    }
+
+    fn upload(&self, reader: &mut dyn Read, remote_path: &str, mode: i32, len: u64, offset: u64,
+              method: &TransferMethod, on_progress: &mut dyn FnMut(u64)) -> Result<(), DeltaError> {
+        match method {
+            TransferMethod::Scp if offset == 0 => {
+                let mut remote_file = self.sess.scp_send(Path::new(remote_path), mode, len, None)?;
+                copy_stream(reader, &mut remote_file, on_progress)?;
+
+                remote_file.send_eof()?;
+                remote_file.wait_eof()?;
+                remote_file.close()?;
+                remote_file.wait_close()?;
+            }
+            TransferMethod::Scp => {
+                /* scp can't append, so stream the tail through the shell */
+                let mut channel = self.sess.channel_session()?;
+                channel.exec(&format!("cat >> {}", shell_quote(remote_path)))?;
+                copy_stream(reader, &mut channel, on_progress)?;
+
+                channel.send_eof()?;
+                channel.wait_eof()?;
+                channel.close()?;
+                channel.wait_close()?;
+                if channel.exit_status()? != 0 {
+                    return Err(DeltaError::CommandFailed(
+                        format!("failed to append to {}", remote_path)));
+                }
+            }
+            TransferMethod::Sftp => {
+                let sftp = self.sess.sftp()?;
+                let mut flags = OpenFlags::WRITE | OpenFlags::CREATE;
+                if offset == 0 {
+                    flags |= OpenFlags::TRUNCATE;
+                }
+                let mut remote_file = sftp.open_mode(
+                    Path::new(remote_path),
+                    flags,
+                    mode,
+                    OpenType::File,
+                )?;
+                remote_file.seek(SeekFrom::Start(offset))?;
+                copy_stream(reader, &mut remote_file, on_progress)?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    fn download(&self, remote_path: &str, writer: &mut dyn Write,
+                method: &TransferMethod) -> Result<u64, DeltaError> {
+        return match method {
+            TransferMethod::Scp => {
+                let (mut remote_file, _stat) = self.sess.scp_recv(Path::new(remote_path))?;
+                let copied = copy_stream(&mut remote_file, writer, &mut |_| {})?;
+
+                remote_file.send_eof()?;
+                remote_file.wait_eof()?;
+                remote_file.close()?;
+                remote_file.wait_close()?;
+                Ok(copied)
+            }
+            TransferMethod::Sftp => {
+                let sftp = self.sess.sftp()?;
+                let mut remote_file = sftp.open(Path::new(remote_path))?;
+                copy_stream(&mut remote_file, writer, &mut |_| {})
+            }
+        };
+    }
+
+    fn file_size(&self, remote_path: &str, method: &TransferMethod) -> Option<u64> {
+        return match method {
+            TransferMethod::Sftp => {
+                let sftp = self.sess.sftp().ok()?;
+                sftp.stat(Path::new(remote_path)).ok()?.size
+            }
+            TransferMethod::Scp => {
+                let out = self.execute(&format!("wc -c < {}", shell_quote(remote_path)), None, None).ok()?;
+                if !out.success() {
+                    return None;
+                }
+                out.stdout.trim().parse::<u64>().ok()
+            }
+        };
+    }
+
+    fn is_alive(&self) -> bool {
+        if let Err(e) = self.sess.keepalive_send() {
+            error!("Failed to send keepalive: {}", e);
+            return false;
+        }
+
+        self.sess.set_timeout(PROBE_TIMEOUT_MS);
+        let result = self.sess.channel_session().and_then(|mut channel| {
+            channel.exec("true")?;
+            channel.wait_close()?;
+            return channel.exit_status();
+        });
+        self.sess.set_timeout(0);
+
+        return match result {
+            Ok(0) => true,
+            Ok(status) => {
+                error!("Probe command exited with {}", status);
+                false
+            }
+            Err(e) => {
+                error!("Probe failed: {}", scrub(&e.to_string()));
+                false
+            }
+        };
+    }
+
+    fn ssh_session(&self) -> Option<&Session> {
+        return Some(&self.sess);
+    }
+}
@@ -0,0 +1,257 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::platform::Platform;
+use crate::obj_model::artifact_url::ArtifactUrl;
+use crate::obj_model::shell::shell_quote;
+
+/*
+ * Where the Unix flavours differ in the commands sent to them: macOS and
+ * FreeBSD have no /proc to read process details from and no sha256sum in
+ * the base system. Scripts here expect the pid in $p.
+ */
+
+/* Prints "<digest>  <path>" lines for the files given after it */
+pub fn checksum_tool(platform: &Platform) -> &'static str {
+    return match platform {
+        Platform::MacOs => "shasum -a 256",
+        Platform::FreeBsd => "sha256 -r",
+        _ => "sha256sum",
+    };
+}
+
+/*
+ * Prints os=, kernel=, arch=, cpus=, memory= (bytes) and distro= in one
+ * round trip, failing where there's no uname: that's how Windows is told
+ * apart. Run through sh, whatever the login shell.
+ */
+pub fn facts_script() -> String {
+    let script = "uname -s > /dev/null 2>&1 || exit 1\n\
+                  echo \"os=$(uname -s)\"\n\
+                  echo \"kernel=$(uname -sr)\"\n\
+                  echo \"arch=$(uname -m)\"\n\
+                  echo \"cpus=$(getconf _NPROCESSORS_ONLN 2> /dev/null || sysctl -n hw.ncpu 2> /dev/null)\"\n\
+                  if [ -r /proc/meminfo ]; then \
+                      echo \"memory=$(awk '/^MemTotal:/ { printf \"%.0f\", $2 * 1024 }' /proc/meminfo)\"; \
+                  else echo \"memory=$(sysctl -n hw.memsize 2> /dev/null || sysctl -n hw.physmem 2> /dev/null)\"; fi\n\
+                  if [ -r /etc/os-release ]; then echo \"distro=$(. /etc/os-release; echo \"$PRETTY_NAME\")\"; \
+                  elif command -v sw_vers > /dev/null 2>&1; then echo \"distro=$(sw_vers -productName) $(sw_vers -productVersion)\"; \
+                  else echo \"distro=$(uname -sr)\"; fi";
+    return format!("sh -c {}", shell_quote(script));
+}
+
+/*
+ * Runs the alive command and, if it succeeds, prints bind_addr= and
+ * bind_port= from the instance's bind files, so a probe is one round trip.
+ * The subshell keeps an exit in the command from skipping the rest.
+ */
+pub fn alive_probe_script(alive: &str, remote_dir: &str) -> String {
+    return format!("(\n{0}\n) > /dev/null 2>&1 || exit 1\n\
+                    printf 'bind_addr=%s\\nbind_port=%s\\n' \"$(cat {1}/bind_addr)\" \"$(cat {1}/bind_port)\"",
+                   alive, shell_quote(remote_dir));
+}
+
+/*
+ * The artifact cache keeps verified archives named by their checksum, with
+ * a manifest of "<sha256> <file> <source>" lines, least recently used
+ * first. The lookup creates the cache and prints match when the archive is
+ * listed and present, moving it to the end of the manifest.
+ */
+pub fn artifact_lookup_script(cache_dir: &str, checksum: &str, file: &str) -> String {
+    return format!("mkdir -p {0} && cd {0} || exit 1\n\
+                    if [ -f {2} ] && grep -q '^{1} ' manifest 2> /dev/null; then \
+                        {{ grep -v '^{1} ' manifest; grep '^{1} ' manifest; }} > manifest.new \
+                        && mv manifest.new manifest && echo match; \
+                    fi", shell_quote(cache_dir), checksum, shell_quote(file));
+}
+
+/*
+ * Prints "<filesystem> <bytes available>" for each path, in order, going
+ * by the closest existing parent of paths that aren't there yet.
+ */
+pub fn free_space_script(paths: &[String]) -> String {
+    let quoted: Vec<String> = paths.iter().map(|p| shell_quote(p)).collect();
+    return format!("for p in {}; do \
+                        while [ ! -e \"$p\" ]; do p=$(dirname \"$p\"); done; \
+                        df -Pk \"$p\" | awk 'NR == 2 {{ printf \"%s %.0f\\n\", $1, $4 * 1024 }}'; \
+                    done", quoted.join(" "));
+}
+
+/* Downloads the artifact to dest with curl, or wget where there is none; s3:// goes through the AWS CLI */
+pub fn fetch_script(artifact: &ArtifactUrl, dest: &str) -> String {
+    if artifact.is_s3() {
+        return format!("aws s3 cp --only-show-errors {} {}", shell_quote(&artifact.url), shell_quote(dest));
+    }
+    return format!("if command -v curl > /dev/null 2>&1; then curl -fsSL --retry 3 -o {1} {0}; \
+                    else wget -q -O {1} {0}; fi", shell_quote(&artifact.url), shell_quote(dest));
+}
+
+/* Lists a verified archive in the manifest and drops all but the keep most recent */
+pub fn artifact_store_script(cache_dir: &str, checksum: &str, file: &str, source: &str, keep: usize) -> String {
+    return format!("cd {0} || exit 1\n\
+                    {{ grep -v '^{1} ' manifest 2> /dev/null; printf '%s %s %s\\n' {1} {2} {3}; }} > manifest.new \
+                    && mv manifest.new manifest || exit 1\n\
+                    n=$(wc -l < manifest)\n\
+                    if [ \"$n\" -gt {4} ]; then \
+                        head -n $((n - {4})) manifest | cut -d' ' -f2 | xargs rm -f; \
+                        tail -n {4} manifest > manifest.new && mv manifest.new manifest; \
+                    fi", shell_quote(cache_dir), checksum, shell_quote(file), shell_quote(source), keep);
+}
+
+/*
+ * Prints size=, mode= (octal), mtime= and dir= for path, separated by
+ * spaces or newlines, or missing when there is nothing there.
+ */
+pub fn stat_script(platform: &Platform, path: &str) -> String {
+    let stat = match platform {
+        Platform::MacOs | Platform::FreeBsd => "stat -L -f 'size=%z mode=%Lp mtime=%m'",
+        _ => "stat -L -c 'size=%s mode=%a mtime=%Y'",
+    };
+    return format!("p={}; if [ ! -e \"$p\" ]; then echo missing; exit 0; fi; \
+                    {} \"$p\" && if [ -d \"$p\" ]; then echo dir=1; else echo dir=0; fi",
+                   shell_quote(path), stat);
+}
+
+/*
+ * Tools deploys run and the oldest version that does the job, None where
+ * any will: GNU tar gained -J in 1.22, while the bsdtar of macOS and
+ * FreeBSD unpacks xz itself and needs no xz binary.
+ */
+pub fn prerequisites(platform: &Platform) -> &'static [(&'static str, Option<&'static str>)] {
+    return match platform {
+        Platform::MacOs | Platform::FreeBsd => &[("tar", Some("2.8")), ("bash", Some("3.2")), ("kill", None)],
+        _ => &[("tar", Some("1.22")), ("xz", Some("5.0")), ("bash", Some("3.2")), ("kill", None)],
+    };
+}
+
+/* Prints "<tool> missing", or "<tool> found" and the first line of its --version */
+pub fn prerequisites_script(tools: &[(&str, Option<&str>)]) -> String {
+    let checks: Vec<String> = tools.iter().map(|(tool, minimum)| {
+        let version = match minimum {
+            Some(_) => format!(" \"$({} --version 2>&1 | head -n 1)\"", tool),
+            None => String::new(),
+        };
+        return format!("if command -v {0} > /dev/null 2>&1; then echo {0} found{1}; else echo {0} missing; fi",
+                       tool, version);
+    }).collect();
+    return checks.join("\n");
+}
+
+/* Prints started=<epoch secs> from /proc, or etime=<[[dd-]hh:]mm:ss> from ps */
+pub fn start_time_script(platform: &Platform) -> String {
+    let ps = "echo \"etime=$(ps -o etime= -p \"$p\")\"";
+    if !platform.has_procfs() {
+        return ps.to_string();
+    }
+
+    return format!(
+        "if [ -r \"/proc/$p/stat\" ]; then \
+             st=$(sed 's/.*) //' \"/proc/$p/stat\" | cut -d' ' -f20); \
+             bt=$(awk '/^btime/ {{ print $2 }}' /proc/stat); \
+             echo \"started=$((bt + st / $(getconf CLK_TCK)))\"; \
+         else \
+             {}; \
+         fi", ps);
+}
+
+/*
+ * Prints ticks/hz/threads/rss/fds sampled over sample_secs from /proc, or
+ * pcpu/rss from ps where there is no /proc.
+ */
+pub fn usage_script(platform: &Platform, sample_secs: u64) -> String {
+    let ps = "ps -o pcpu= -o rss= -p \"$p\" | awk '{ print \"pcpu=\" $1; print \"rss=\" $2 * 1024 }'";
+    if !platform.has_procfs() {
+        return ps.to_string();
+    }
+
+    return format!(
+        "if [ -r \"/proc/$p/stat\" ]; then \
+             t1=$(sed 's/.*) //' \"/proc/$p/stat\" | cut -d' ' -f12,13 | tr ' ' '+'); sleep {0}; \
+             st=$(sed 's/.*) //' \"/proc/$p/stat\"); t2=$(echo \"$st\" | cut -d' ' -f12,13 | tr ' ' '+'); \
+             echo \"ticks=$((($t2) - ($t1)))\"; echo \"hz=$(getconf CLK_TCK)\"; \
+             echo \"threads=$(echo \"$st\" | cut -d' ' -f18)\"; \
+             echo \"rss=$(($(cut -d' ' -f2 \"/proc/$p/statm\") * $(getconf PAGESIZE)))\"; \
+             if [ -r \"/proc/$p/fd\" ]; then echo \"fds=$(ls \"/proc/$p/fd\" | wc -l)\"; fi; \
+         else \
+             {1}; \
+         fi", sample_secs, ps);
+}
+
+/*
+ * Prints in_use=1 when something listens on the TCP port, followed by
+ * pid=<n> if the tool shows the owner (ss and lsof only do for processes
+ * the user may inspect). ss or netstat on Linux, sockstat on FreeBSD,
+ * lsof with a netstat fallback on macOS.
+ */
+pub fn port_owner_script(platform: &Platform, port: u16) -> String {
+    return match platform {
+        Platform::FreeBsd => format!(
+            "sockstat -46l -P tcp -p {} | awk 'NR > 1 {{ print \"in_use=1\"; print \"pid=\" $3; exit }}'", port),
+        Platform::MacOs => format!(
+            "o=$(lsof -nP -iTCP:{0} -sTCP:LISTEN -t 2> /dev/null | head -n 1); \
+             if [ -n \"$o\" ]; then echo in_use=1; echo \"pid=$o\"; \
+             elif netstat -an -p tcp 2> /dev/null | awk '$NF == \"LISTEN\" && $4 ~ /[.]{0}$/' | grep -q .; then \
+                 echo in_use=1; \
+             fi", port),
+        _ => format!(
+            "if command -v ss > /dev/null 2>&1; then o=$(ss -Htlnp \"sport = :{0}\" 2> /dev/null); \
+             else o=$(netstat -tlnp 2> /dev/null | awk '$4 ~ /:{0}$/'); fi; \
+             if [ -n \"$o\" ]; then \
+                 echo in_use=1; \
+                 echo \"$o\" | sed -n 's/.*pid=\\([0-9]*\\).*/pid=\\1/p; s/.* \\([0-9][0-9]*\\)\\/.*/pid=\\1/p' | head -n 1; \
+             fi", port),
+    };
+}
+
+/*
+ * Prints port=<n> for every listening TCP port and range=<lo> <hi> for the
+ * ephemeral ports, the latter empty where the node won't say.
+ */
+pub fn listening_ports_script(platform: &Platform) -> String {
+    let listening = match platform {
+        Platform::FreeBsd => "sockstat -46l -P tcp | awk 'NR > 1 { print $6 }' | sed 's/.*://'",
+        Platform::MacOs => "netstat -an -p tcp | awk '$NF == \"LISTEN\" { print $4 }' | sed 's/.*[.]//'",
+        _ => "if command -v ss > /dev/null 2>&1; then ss -Htln; \
+              else netstat -tln 2> /dev/null | awk '$6 == \"LISTEN\"'; fi | awk '{ print $4 }' | sed 's/.*://'",
+    };
+    let range = match platform.has_procfs() {
+        true => "cat /proc/sys/net/ipv4/ip_local_port_range 2> /dev/null",
+        false => "sysctl -n net.inet.ip.portrange.first net.inet.ip.portrange.last 2> /dev/null | tr '\\n' ' '",
+    };
+    return format!("{} | sed 's/^/port=/'; echo \"range=$({})\"", listening, range);
+}
+
+/* Seconds out of the elapsed time ps prints as [[dd-]hh:]mm:ss */
+pub fn parse_etime(etime: &str) -> Option<u64> {
+    let (days, clock) = match etime.trim().split_once('-') {
+        Some((d, c)) => (d.parse::<u64>().ok()?, c),
+        None => (0, etime.trim()),
+    };
+
+    let mut secs = 0;
+    for part in clock.split(':') {
+        secs = secs * 60 + part.parse::<u64>().ok()?;
+    }
+    return Some(days * 86400 + secs);
+}
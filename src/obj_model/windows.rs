@@ -0,0 +1,265 @@
+/*
+ * Delta API
+ *
+ * Copyright 2024 Maxim Menshikov
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the “Software”),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS
+ * OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use crate::data_model::archive_format::ArchiveFormat;
+use crate::obj_model::artifact_url::ArtifactUrl;
+
+/*
+ * Commands for Windows nodes reached over OpenSSH, whatever the default
+ * shell there is (cmd or PowerShell). Scripts are PowerShell, handed over
+ * base64-encoded so that neither shell gets to reinterpret the quoting.
+ * Command params of a Windows node are PowerShell as well.
+ */
+
+/* Run when uname fails; prints the version banner and %TEMP% */
+pub const DETECT_COMMAND: &str = "cmd /c \"ver & echo %TEMP%\"";
+
+pub const DEFAULT_LAUNCH_TEMPLATE: &str = "& '{install_dir}/bin/{binary}.exe' --server 'tcp://{bind}'";
+pub const DEFAULT_TEST_TEMPLATE: &str = "& '{install_dir}/bin/{binary}.exe' --version";
+pub const DEFAULT_ALIVE_TEMPLATE: &str =
+    "if (-not (Get-Process -Id (Get-Content -LiteralPath '{remote_dir}/pid') -ErrorAction SilentlyContinue)) { exit 1 }";
+
+/* Same as the POSIX facts script, but for os= */
+pub const FACTS_SCRIPT: &str = "$o = Get-CimInstance Win32_OperatingSystem\n\
+                                \"kernel=Windows_NT $($o.Version)\"\n\
+                                \"arch=$env:PROCESSOR_ARCHITECTURE\"\n\
+                                \"cpus=$env:NUMBER_OF_PROCESSORS\"\n\
+                                \"memory=$([uint64]$o.TotalVisibleMemorySize * 1024)\"\n\
+                                \"distro=$($o.Caption)\"";
+
+/* Version banner and temp dir (with forward slashes) out of DETECT_COMMAND's output */
+pub fn parse_detect(output: &str) -> Option<(String, String)> {
+    let mut lines = output.lines().map(|l| l.trim()).filter(|l| !l.is_empty());
+    let version = lines.find(|l| l.contains("Windows"))?.to_string();
+    let temp_dir = lines.next().filter(|l| !l.contains('%'))?.replace('\\', "/");
+    return Some((version, temp_dir.trim_end_matches('/').to_string()));
+}
+
+/* Wraps a value in single quotes so that PowerShell takes it literally */
+pub fn ps_quote(value: &str) -> String {
+    return format!("'{}'", ps_escape(value));
+}
+
+/* The value as it has to appear inside a single-quoted PowerShell string */
+pub fn ps_escape(value: &str) -> String {
+    return value.replace('\'', "''");
+}
+
+/* Command line running script in a fresh PowerShell */
+pub fn powershell(script: &str) -> String {
+    return format!("powershell -NoProfile -NonInteractive -EncodedCommand {}", encode(script));
+}
+
+pub fn mkdir_script(dirs: &[String]) -> String {
+    let list: Vec<String> = dirs.iter().map(|d| ps_quote(d)).collect();
+    return format!("$ErrorActionPreference = 'Stop'; New-Item -ItemType Directory -Force -Path {} | Out-Null",
+                   list.join(", "));
+}
+
+/* Prints "<sha256> <path>" like sha256sum does */
+pub fn checksum_script(path: &str) -> String {
+    return format!("$ErrorActionPreference = 'Stop'; \
+                    (Get-FileHash -Algorithm SHA256 -LiteralPath {0}).Hash.ToLower() + ' ' + {0}", ps_quote(path));
+}
+
+/* Same as the POSIX artifact cache lookup: creates the cache, prints match and marks the archive used */
+pub fn artifact_lookup_script(cache_dir: &str, checksum: &str, file: &str) -> String {
+    return format!("$ErrorActionPreference = 'Stop'; $d = '{0}'; $m = \"$d/manifest\"\n\
+                    New-Item -ItemType Directory -Force -Path $d | Out-Null\n\
+                    if ((Test-Path -LiteralPath \"$d/{2}\") -and (Test-Path -LiteralPath $m)) {{\n\
+                        $l = @(Get-Content -LiteralPath $m)\n\
+                        $hit = @($l | Where-Object {{ $_.StartsWith('{1} ') }})\n\
+                        if ($hit) {{ @($l | Where-Object {{ -not $_.StartsWith('{1} ') }}) + $hit | Set-Content -LiteralPath $m; 'match' }}\n\
+                    }}", ps_escape(cache_dir), checksum, ps_escape(file));
+}
+
+/* Same as the POSIX stat script; mode is 444 for read-only items, 644 or 755 for directories otherwise */
+pub fn stat_script(path: &str) -> String {
+    return format!("$i = Get-Item -LiteralPath {} -Force -ErrorAction SilentlyContinue\n\
+                    if (-not $i) {{ 'missing'; exit 0 }}\n\
+                    $dir = [int]$i.PSIsContainer\n\
+                    \"size=$(if ($dir) {{ 0 }} else {{ $i.Length }})\"\n\
+                    \"mode=$(if ($i.Attributes -band [IO.FileAttributes]::ReadOnly) {{ 444 }} elseif ($dir) {{ 755 }} else {{ 644 }})\"\n\
+                    \"mtime=$([DateTimeOffset]::new($i.LastWriteTimeUtc).ToUnixTimeSeconds())\"\n\
+                    \"dir=$dir\"", ps_quote(path));
+}
+
+/* Same as the POSIX free space script, the filesystem being the drive */
+pub fn free_space_script(paths: &[String]) -> String {
+    let list: Vec<String> = paths.iter().map(|p| ps_quote(p)).collect();
+    return format!("foreach ($p in @({})) {{\n\
+                        while ($p -and -not (Test-Path -LiteralPath $p)) {{ $p = Split-Path -Parent $p }}\n\
+                        $d = (Get-Item -LiteralPath $p).PSDrive\n\
+                        \"$($d.Name) $($d.Free)\"\n\
+                    }}", list.join(", "));
+}
+
+/* Windows 10 ships bsdtar; Expand-Archive came with PowerShell 5 */
+pub const PREREQUISITES: &[(&str, Option<&str>)] = &[("tar", Some("3.0")), ("powershell", Some("5.0"))];
+
+/* Same as the POSIX prerequisites script */
+pub fn prerequisites_script() -> String {
+    return "if (Get-Command tar -ErrorAction SilentlyContinue) { \"tar found $(tar --version 2>&1 | Select-Object -First 1)\" } \
+            else { 'tar missing' }\n\
+            \"powershell found $($PSVersionTable.PSVersion)\"".to_string();
+}
+
+/* Same as the POSIX fetch, with Invoke-WebRequest for HTTP */
+pub fn fetch_script(artifact: &ArtifactUrl, dest: &str) -> String {
+    if artifact.is_s3() {
+        return format!("aws s3 cp --only-show-errors {} {}; exit $LASTEXITCODE", ps_quote(&artifact.url), ps_quote(dest));
+    }
+    return format!("$ErrorActionPreference = 'Stop'; $ProgressPreference = 'SilentlyContinue'; \
+                    Invoke-WebRequest -UseBasicParsing -Uri {} -OutFile {}", ps_quote(&artifact.url), ps_quote(dest));
+}
+
+/* Same as the POSIX artifact cache store */
+pub fn artifact_store_script(cache_dir: &str, checksum: &str, file: &str, source: &str, keep: usize) -> String {
+    return format!("$ErrorActionPreference = 'Stop'; $d = '{0}'; $m = \"$d/manifest\"\n\
+                    $l = @(if (Test-Path -LiteralPath $m) {{ Get-Content -LiteralPath $m }}) | Where-Object {{ -not $_.StartsWith('{1} ') }}\n\
+                    $l = @($l) + '{1} {2} {3}'\n\
+                    if ($l.Count -gt {4}) {{\n\
+                        $l[0..($l.Count - {4} - 1)] | ForEach-Object {{ \
+                            Remove-Item -Force -ErrorAction SilentlyContinue -LiteralPath \"$d/$($_.Split(' ')[1])\" }}\n\
+                        $l = $l[($l.Count - {4})..($l.Count - 1)]\n\
+                    }}\n\
+                    $l | Set-Content -LiteralPath $m", ps_escape(cache_dir), checksum, ps_escape(file), ps_escape(source), keep);
+}
+
+pub fn remove_script(paths: &[String]) -> String {
+    let list: Vec<String> = paths.iter().map(|p| ps_quote(p)).collect();
+    return format!("Remove-Item -Recurse -Force -ErrorAction SilentlyContinue -Path {}; exit 0", list.join(", "));
+}
+
+/* tar.exe ships with Windows 10 and later and reads every tarball format */
+pub fn extract_script(format: &ArchiveFormat, archive: &str, dir: &str) -> Option<String> {
+    let unpack = match format {
+        ArchiveFormat::Zip => format!("Expand-Archive -Force -LiteralPath {} -DestinationPath {}",
+                                      ps_quote(archive), ps_quote(dir)),
+        ArchiveFormat::Directory => return None,
+        _ => format!("tar -xf {} -C {}; if ($LASTEXITCODE -ne 0) {{ exit $LASTEXITCODE }}",
+                     ps_quote(archive), ps_quote(dir)),
+    };
+    return Some(format!("{}; {}", mkdir_script(&[dir.to_string()]), unpack));
+}
+
+/* Prints the two lines of the bind files, as cat does on the others */
+pub fn read_bind_script(remote_dir: &str) -> String {
+    return format!("$ErrorActionPreference = 'Stop'; Get-Content -LiteralPath '{0}/bind_addr', '{0}/bind_port'",
+                   ps_escape(remote_dir));
+}
+
+/* Same as the POSIX alive probe: the alive command, then bind_addr= and bind_port= if it succeeded */
+pub fn alive_probe_script(alive: &str, remote_dir: &str) -> String {
+    return format!("$null = & {{\n{0}\n}}\nif (-not $?) {{ exit 1 }}\n\
+                    \"bind_addr=$(Get-Content -LiteralPath '{1}/bind_addr')\"\n\
+                    \"bind_port=$(Get-Content -LiteralPath '{1}/bind_port')\"",
+                   alive, ps_escape(remote_dir));
+}
+
+/* Prints in_use=1 and pid=<n> when something listens on the TCP port, like the POSIX check */
+pub fn port_owner_script(port: u16) -> String {
+    return format!(
+        "$c = Get-NetTCPConnection -State Listen -LocalPort {} -ErrorAction SilentlyContinue | Select-Object -First 1\n\
+         if ($c) {{ 'in_use=1'; \"pid=$($c.OwningProcess)\" }}", port);
+}
+
+/* Prints port=<n> for every listening TCP port and the dynamic range, like the POSIX script */
+pub fn listening_ports_script() -> String {
+    return "Get-NetTCPConnection -State Listen -ErrorAction SilentlyContinue | ForEach-Object { \"port=$($_.LocalPort)\" }\n\
+            $s = Get-NetTCPSetting -SettingName Internet -ErrorAction SilentlyContinue\n\
+            if ($s) { \"range=$($s.DynamicPortRangeStartPort) $($s.DynamicPortRangeStartPort + $s.DynamicPortRangeNumberOfPorts - 1)\" }"
+        .to_string();
+}
+
+/*
+ * Rotates the logs, starts launch through WMI so that it outlives the SSH
+ * session (whose job object takes its children along), records pid and
+ * bind files and prints "pid <n>" once the process survived startup.
+ */
+pub fn start_script(launch: &str, remote_dir: &str, log_dir: &str, bind_addr: &str, bind_port: u16) -> String {
+    let run = format!("{} >> '{1}/stdout.log' 2>> '{1}/stderr.log'", launch, ps_escape(log_dir));
+    let command_line = format!("powershell -NoProfile -NonInteractive -WindowStyle Hidden -EncodedCommand {}",
+                               encode(&run));
+    return format!(
+        "$ErrorActionPreference = 'Stop'\n\
+         $l = '{1}'\n\
+         New-Item -ItemType Directory -Force -Path $l | Out-Null\n\
+         foreach ($f in 'stdout', 'stderr') {{ \
+             if (Test-Path -LiteralPath \"$l/$f.log\") {{ Move-Item -Force -LiteralPath \"$l/$f.log\" \"$l/$f.log.1\" }} }}\n\
+         $r = Invoke-CimMethod -ClassName Win32_Process -MethodName Create -Arguments @{{ CommandLine = {2} }}\n\
+         if ($r.ReturnValue -ne 0) {{ [Console]::Error.WriteLine('process creation failed: ' + $r.ReturnValue); exit 1 }}\n\
+         $p = $r.ProcessId\n\
+         Set-Content -LiteralPath '{0}/pid' $p\n\
+         Set-Content -LiteralPath '{0}/bind_addr' {3}\n\
+         Set-Content -LiteralPath '{0}/bind_port' {4}\n\
+         Start-Sleep 4\n\
+         if (Get-Process -Id $p -ErrorAction SilentlyContinue) {{ \"pid $p\" }} else {{ \
+             [Console]::Error.WriteLine((Get-Content -LiteralPath '{1}/stderr.log' -Tail 20 \
+                                         -ErrorAction SilentlyContinue) -join \"`n\"); exit 1 }}",
+        ps_escape(remote_dir), ps_escape(log_dir), ps_quote(&command_line), ps_quote(bind_addr), bind_port);
+}
+
+/*
+ * taskkill on the process tree, forced once grace seconds pass; prints
+ * not-running, terminated or killed like the POSIX stop does.
+ */
+pub fn stop_script(remote_dir: &str, grace: u64) -> String {
+    return format!(
+        "$f = '{0}/pid'\n\
+         $p = Get-Content -LiteralPath $f -ErrorAction SilentlyContinue\n\
+         if (-not $p -or -not (Get-Process -Id $p -ErrorAction SilentlyContinue)) {{ \
+             Remove-Item -Force -LiteralPath $f -ErrorAction SilentlyContinue; 'not-running'; exit 0 }}\n\
+         taskkill /T /PID $p 2>&1 | Out-Null\n\
+         $i = 0\n\
+         while ((Get-Process -Id $p -ErrorAction SilentlyContinue) -and $i -lt {1}) {{ Start-Sleep 1; $i++ }}\n\
+         if (Get-Process -Id $p -ErrorAction SilentlyContinue) {{ taskkill /F /T /PID $p 2>&1 | Out-Null; 'killed' }} \
+         else {{ 'terminated' }}\n\
+         Remove-Item -Force -LiteralPath $f -ErrorAction SilentlyContinue",
+        ps_escape(remote_dir), grace);
+}
+
+/* What -EncodedCommand takes: base64 of the UTF-16LE text */
+fn encode(script: &str) -> String {
+    let utf16: Vec<u8> = script.encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+    return base64(&utf16);
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    return out;
+}